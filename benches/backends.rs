@@ -0,0 +1,66 @@
+//! Compares the evaluation backends on recursive programs over `examples/std.stlc`.
+//!
+//! Run with `cargo bench`, the programs can be filtered by passing a substring of their name,
+//! e.g. `cargo bench -- fib`.
+
+use std::{
+    hint::black_box,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use nom::{combinator::all_consuming, Parser};
+use stlc_project::{
+    module::parse::parse_module,
+    term::{parse::parse_term, Term},
+    vm,
+};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("sum", "sum (range 0 200)"),
+    ("reverse", "reverse (range 0 100)"),
+    (
+        "fib",
+        "let fib = fix (fun fib : Integer -> Integer, fun n : Integer, \
+            if n < 2 then n else fib (n - 1) + fib (n - 2)) in fib 15",
+    ),
+];
+
+/// Parses `code` with the declarations of `examples/std.stlc` in scope.
+fn parse_with_std(code: &str) -> Term {
+    let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/examples");
+    let std = std::fs::read_to_string(examples.join("std.stlc")).unwrap();
+    let module = all_consuming(parse_module).parse(&std).unwrap().1;
+    let body = all_consuming(parse_term).parse(code).unwrap().1;
+    module.to_term(examples, body).unwrap()
+}
+
+/// Runs `f` repeatedly for about a second and prints the mean time per run.
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    let budget = Duration::from_secs(1);
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs == 0 || start.elapsed() < budget {
+        black_box(f());
+        runs += 1;
+    }
+    let mean = start.elapsed() / runs;
+    println!("  {name:<12} {mean:>12.2?}  ({runs} runs)");
+}
+
+fn main() {
+    let filter = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or_default();
+
+    for (name, code) in PROGRAMS.iter().filter(|(name, _)| name.contains(&filter)) {
+        let t = parse_with_std(code);
+        let program = vm::compile(&t);
+        println!("{name}: {code}");
+        bench("multistep", || t.clone().multistep());
+        bench("eval", || t.eval().unwrap().to_term());
+        bench("vm", || program.run().unwrap().to_term());
+        bench("vm+compile", || vm::compile(&t).run().unwrap().to_term());
+    }
+}
@@ -0,0 +1,299 @@
+//! # Integer arithmetic
+//!
+//! Integers are stored as an `i32` when they fit, and as a big integer otherwise. The arithmetic
+//! on them is exact, and the [`Arithmetic`] selected when evaluating decides what happens to a
+//! result that does not fit in an `i32`:
+//!
+//! - [`Arithmetic::Checked`] fails with [`EvalError::Overflow`], which is the default.
+//! - [`Arithmetic::Arbitrary`] keeps it as a big integer.
+//!
+//! With [`Arithmetic::Checked`], a term with an integer literal which does not fit in an `i32`
+//! fails with [`EvalError::Overflow`] before it is evaluated.
+//!
+//! The arithmetic is selected for all back-ends at once with [`with_arithmetic`].
+//!
+//! ```rust
+//! # use stlc_project::arith::{with_arithmetic, Arithmetic};
+//! # use stlc_project::term::{util::*, EvalError};
+//! let t = mul(i32::MAX, 2);
+//! assert_eq!(t.clone().try_multistep(), Err(EvalError::Overflow { redex: t.clone().into() }));
+//! let v = with_arithmetic(Arithmetic::Arbitrary, || t.eval()).unwrap();
+//! assert_eq!(v.to_string(), "4294967294");
+//!
+//! let int = |i: &str| Int(i.parse().unwrap());
+//! let t = sub(int("99999999999"), int("99999999990"));
+//! assert!(matches!(t.eval(), Err(EvalError::Overflow { .. })));
+//! assert_eq!(with_arithmetic(Arithmetic::Arbitrary, || t.eval()).unwrap().to_string(), "9");
+//! ```
+//!
+//! [`EvalError::Overflow`]: crate::term::EvalError::Overflow
+
+use std::{cell::Cell, cmp::Ordering, fmt, str::FromStr, sync::Arc};
+
+use num_bigint::{BigInt, Sign};
+
+use crate::term::{EvalError, Term};
+
+/// An integer of any size.
+///
+/// An integer which fits in an `i32` is always stored as one, so that integers can be compared
+/// and hashed by their representation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Integer(Repr);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Repr {
+    Small(i32),
+    /// Never fits in an `i32`, shared so that terms holding it can be sent across threads
+    Big(Arc<BigInt>),
+}
+
+impl Integer {
+    /// Returns the integer as an `i32`, if it fits.
+    pub fn to_i32(&self) -> Option<i32> {
+        match self.0 {
+            Repr::Small(i) => Some(i),
+            Repr::Big(_) => None,
+        }
+    }
+
+    /// Returns the integer as an index, if it is not negative and fits in a `usize`.
+    pub fn to_usize(&self) -> Option<usize> {
+        match &self.0 {
+            Repr::Small(i) => usize::try_from(*i).ok(),
+            Repr::Big(i) => usize::try_from(&**i).ok(),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == Repr::Small(0)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match &self.0 {
+            Repr::Small(i) => *i < 0,
+            Repr::Big(i) => i.sign() == Sign::Minus,
+        }
+    }
+
+    fn to_big(&self) -> BigInt {
+        match &self.0 {
+            Repr::Small(i) => BigInt::from(*i),
+            Repr::Big(i) => (**i).clone(),
+        }
+    }
+
+    /// Applies `small` on two small integers, falling back to `big` if either of them is big or
+    /// `small` overflows.
+    fn op2(
+        &self,
+        other: &Integer,
+        small: fn(i32, i32) -> Option<i32>,
+        big: fn(BigInt, BigInt) -> BigInt,
+    ) -> Integer {
+        if let (Repr::Small(i1), Repr::Small(i2)) = (&self.0, &other.0)
+            && let Some(i) = small(*i1, *i2)
+        {
+            return Integer::from(i);
+        }
+        Integer::from(big(self.to_big(), other.to_big()))
+    }
+
+    pub fn add(&self, other: &Integer) -> Integer {
+        self.op2(other, i32::checked_add, |i1, i2| i1 + i2)
+    }
+
+    pub fn sub(&self, other: &Integer) -> Integer {
+        self.op2(other, i32::checked_sub, |i1, i2| i1 - i2)
+    }
+
+    pub fn mul(&self, other: &Integer) -> Integer {
+        self.op2(other, i32::checked_mul, |i1, i2| i1 * i2)
+    }
+
+    /// Division rounding towards zero, which panics if `other` is zero.
+    pub fn div(&self, other: &Integer) -> Integer {
+        self.op2(other, i32::checked_div, |i1, i2| i1 / i2)
+    }
+
+    /// Remainder with the sign of `self`, which panics if `other` is zero.
+    pub fn rem(&self, other: &Integer) -> Integer {
+        self.op2(other, i32::checked_rem, |i1, i2| i1 % i2)
+    }
+
+    pub fn neg(&self) -> Integer {
+        match &self.0 {
+            Repr::Small(i) => match i.checked_neg() {
+                Some(i) => Integer::from(i),
+                None => Integer::from(-BigInt::from(*i)),
+            },
+            Repr::Big(i) => Integer::from(-(**i).clone()),
+        }
+    }
+}
+
+impl From<i32> for Integer {
+    fn from(i: i32) -> Self {
+        Integer(Repr::Small(i))
+    }
+}
+
+impl From<usize> for Integer {
+    fn from(i: usize) -> Self {
+        match i32::try_from(i) {
+            Ok(i) => Integer::from(i),
+            Err(_) => Integer::from(BigInt::from(i)),
+        }
+    }
+}
+
+impl From<BigInt> for Integer {
+    fn from(i: BigInt) -> Self {
+        match i32::try_from(&i) {
+            Ok(i) => Integer::from(i),
+            Err(_) => Integer(Repr::Big(Arc::new(i))),
+        }
+    }
+}
+
+impl PartialEq<i32> for Integer {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == Repr::Small(*other)
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (&self.0, &other.0) {
+            (Repr::Small(i1), Repr::Small(i2)) => i1.cmp(i2),
+            _ => self.to_big().cmp(&other.to_big()),
+        }
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Integer {
+    type Err = num_bigint::ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i32>() {
+            Ok(i) => Ok(Integer::from(i)),
+            Err(_) => s.parse::<BigInt>().map(Integer::from),
+        }
+    }
+}
+
+impl fmt::Display for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Repr::Small(i) => write!(f, "{i}"),
+            Repr::Big(i) => write!(f, "{i}"),
+        }
+    }
+}
+
+/// What to do with the result of arithmetic which does not fit in an `i32`, selected with
+/// `--arith checked|arbitrary` or `:arith` in the REPL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    /// Fail with [`EvalError::Overflow`](crate::term::EvalError::Overflow)
+    #[default]
+    Checked,
+    /// Keep the result as a big integer
+    Arbitrary,
+}
+
+impl Arithmetic {
+    /// Returns the arithmetic selected with [`with_arithmetic`], [`Arithmetic::Checked`] outside.
+    pub fn current() -> Self {
+        ARITHMETIC.get()
+    }
+
+    /// Returns the result `i` of arithmetic, or `None` if it overflows with this arithmetic.
+    pub fn check(self, i: Integer) -> Option<Integer> {
+        match (self, &i.0) {
+            (Arithmetic::Checked, Repr::Big(_)) => None,
+            _ => Some(i),
+        }
+    }
+
+    /// Checks that the integer literals of `t` fit with this arithmetic, otherwise fails with
+    /// [`EvalError::Overflow`] on the first one that does not.
+    pub(crate) fn check_literals(self, t: &Term) -> Result<(), EvalError> {
+        let mut terms = vec![t];
+        while let Some(t) = terms.pop() {
+            match t {
+                Term::Int(i) if self.check(i.clone()).is_none() => {
+                    return Err(EvalError::Overflow {
+                        redex: Box::new(t.clone()),
+                    });
+                }
+                t => terms.extend(t.children().into_iter().rev()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Arithmetic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checked" => Ok(Arithmetic::Checked),
+            "arbitrary" => Ok(Arithmetic::Arbitrary),
+            _ => Err(format!("unknown arithmetic `{s}`, expected checked or arbitrary")),
+        }
+    }
+}
+
+impl fmt::Display for Arithmetic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arithmetic::Checked => write!(f, "checked"),
+            Arithmetic::Arbitrary => write!(f, "arbitrary"),
+        }
+    }
+}
+
+thread_local! {
+    static ARITHMETIC: Cell<Arithmetic> = const { Cell::new(Arithmetic::Checked) };
+}
+
+/// Runs `f` with `arithmetic` selected for every back-end on the current thread.
+///
+/// The previous arithmetic is restored afterwards, also if `f` panics.
+///
+/// ```rust
+/// # use stlc_project::arith::{with_arithmetic, Arithmetic};
+/// let result = std::panic::catch_unwind(|| with_arithmetic(Arithmetic::Arbitrary, || panic!()));
+/// assert!(result.is_err());
+/// assert_eq!(Arithmetic::current(), Arithmetic::Checked);
+/// ```
+pub fn with_arithmetic<T>(arithmetic: Arithmetic, f: impl FnOnce() -> T) -> T {
+    struct Restore(Arithmetic);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ARITHMETIC.set(self.0);
+        }
+    }
+
+    let _restore = Restore(ARITHMETIC.replace(arithmetic));
+    f()
+}
+
+/// Applies the arithmetic operator `op`, returning `None` if the result overflows with the
+/// current [`Arithmetic`].
+pub(crate) fn checked(
+    op: fn(&Integer, &Integer) -> Integer,
+    i1: &Integer,
+    i2: &Integer,
+) -> Option<Integer> {
+    Arithmetic::current().check(op(i1, i2))
+}
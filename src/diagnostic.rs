@@ -0,0 +1,411 @@
+//! # Diagnostics
+//!
+//! Renders parse, type and evaluation errors together with the location of the offending code.
+//!
+//! ```rust
+//! # use stlc_project::diagnostic::{Diagnostic, Source};
+//! # use stlc_project::span::record_spans;
+//! # use stlc_project::term::parse::parse_term;
+//! let code = "if True then 1 else False";
+//! let (result, spans) = record_spans(code, parse_term);
+//! let source = Source::new("<repl>", code, spans);
+//! let (_, t) = result.unwrap();
+//! let err = t.type_check_located().unwrap_err();
+//! assert_eq!(
+//!     Diagnostic::from_type_error(&[source], &err).to_string(),
+//!     "\
+//! error: branches have different types: expected ℤ, found 𝟚 in False
+//!  --> <repl>:1:21
+//!   |
+//! 1 | if True then 1 else False
+//!   |                     ^^^^^"
+//! );
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::{
+    parse::ParseError,
+    r#type::{Type, TypeError},
+    span::{Located, Span, SpanTable},
+    term::{EvalError, Term},
+};
+
+/// A source file along with the spans recorded while parsing it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Source {
+    /// The name shown in diagnostics, usually the path of the file.
+    pub name: String,
+    pub code: String,
+    pub spans: SpanTable,
+}
+
+impl Source {
+    pub fn new(name: impl ToString, code: impl ToString, spans: SpanTable) -> Self {
+        Source {
+            name: name.to_string(),
+            code: code.to_string(),
+            spans,
+        }
+    }
+
+    /// Computes the line and column of `span`, along with the text of the line.
+    fn locate(&self, span: Span) -> Location {
+        let code = &self.code;
+        let start = span.start.min(code.len());
+        let line_start = code[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = code[start..].find('\n').map_or(code.len(), |i| start + i);
+        let end = span.end.clamp(start, line_end);
+        Location {
+            name: self.name.clone(),
+            line: code[..start].matches('\n').count() + 1,
+            column: code[line_start..start].chars().count() + 1,
+            text: code[line_start..line_end].to_string(),
+            width: code[start..end].chars().count().max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Location {
+    name: String,
+    /// 1-based line number
+    line: usize,
+    /// 1-based column, counted in characters
+    column: usize,
+    /// The text of the line containing the span
+    text: String,
+    /// Width of the span on the line, counted in characters
+    width: usize,
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Severity {
+    #[default]
+    Error,
+    /// The code is accepted, but likely contains a mistake.
+    Warning,
+}
+
+/// An error or warning message, optionally pointing to a location in a [`Source`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub severity: Severity,
+    location: Option<Location>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic without a location.
+    pub fn new(message: impl ToString) -> Self {
+        Diagnostic {
+            message: message.to_string(),
+            severity: Severity::Error,
+            location: None,
+        }
+    }
+
+    /// Creates a diagnostic pointing to `span` in `source`.
+    pub fn at(message: impl ToString, source: &Source, span: Span) -> Self {
+        Diagnostic {
+            message: message.to_string(),
+            severity: Severity::Error,
+            location: Some(source.locate(span)),
+        }
+    }
+
+    /// Creates diagnostics for the warnings recorded while parsing `source`.
+    ///
+    /// ```rust
+    /// # use stlc_project::diagnostic::{Diagnostic, Source};
+    /// # use stlc_project::span::record_spans;
+    /// # use stlc_project::term::parse::parse_term;
+    /// let code = "match 1 with | n => n | 0 => 1";
+    /// let (result, spans) = record_spans(code, parse_term);
+    /// assert!(result.is_ok());
+    /// let source = Source::new("<repl>", code, spans);
+    /// assert_eq!(
+    ///     Diagnostic::warnings(&source)[0].to_string(),
+    ///     "\
+    /// warning: pattern `0` is unreachable
+    ///  --> <repl>:1:25
+    ///   |
+    /// 1 | match 1 with | n => n | 0 => 1
+    ///   |                         ^"
+    /// );
+    /// ```
+    pub fn warnings(source: &Source) -> Vec<Self> {
+        source
+            .spans
+            .warnings
+            .iter()
+            .map(|(span, message)| Diagnostic {
+                severity: Severity::Warning,
+                ..Diagnostic::at(message, source, *span)
+            })
+            .collect()
+    }
+
+    /// Creates a diagnostic pointing to the span which `span` finds in the first of the `sources`.
+    fn at_span(
+        message: impl ToString,
+        sources: &[Source],
+        span: impl Fn(&SpanTable) -> Option<Span>,
+    ) -> Self {
+        let message = message.to_string();
+        sources
+            .iter()
+            .find_map(|source| Some((source, span(&source.spans)?)))
+            .map_or_else(
+                || Diagnostic::new(&message),
+                |(source, span)| Diagnostic::at(&message, source, span),
+            )
+    }
+
+    /// Creates a diagnostic for an error returned by parsing `code`, which is named `name`.
+    ///
+    /// The error points to the token where the parser gave up, and is explained by the message
+    /// of the error if the parsers gave one. The remaining input of the error must be a suffix of
+    /// `code`, which is the case for any copy of the parsed code.
+    ///
+    /// ```rust
+    /// # use nom::{combinator::all_consuming, Parser};
+    /// # use stlc_project::diagnostic::Diagnostic;
+    /// # use stlc_project::term::parse::parse_term;
+    /// let code = "let x = 1 in\nx )";
+    /// let err = all_consuming(parse_term).parse(code).unwrap_err();
+    /// assert_eq!(
+    ///     Diagnostic::from_parse_error("<repl>", code, &err).to_string(),
+    ///     "\
+    /// error: parse error: unexpected `)`
+    ///  --> <repl>:2:3
+    ///   |
+    /// 2 | x )
+    ///   |   ^"
+    /// );
+    ///
+    /// let code = "match [1] with | nil => 0 | cons x nil => x";
+    /// let err = all_consuming(parse_term).parse(code).unwrap_err();
+    /// assert_eq!(
+    ///     Diagnostic::from_parse_error("<repl>", code, &err).to_string(),
+    ///     "\
+    /// error: non-exhaustive match, pattern `cons _ (cons _ _)` is not covered
+    ///  --> <repl>:1:1
+    ///   |
+    /// 1 | match [1] with | nil => 0 | cons x nil => x
+    ///   | ^^^^^"
+    /// );
+    /// ```
+    pub fn from_parse_error(
+        name: impl ToString,
+        code: &str,
+        err: &nom::Err<ParseError<&str>>,
+    ) -> Self {
+        let (input, message) = match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => (e.input, e.message.as_ref()),
+            nom::Err::Incomplete(_) => return Diagnostic::new("parse error: incomplete input"),
+        };
+        // The parsers only ever consume input, so the offset is given by the remaining length
+        let Some(start) = code.ends_with(input).then(|| code.len() - input.len()) else {
+            return Diagnostic::new(format!("parse error: {err}"));
+        };
+        let token = input.split_whitespace().next().unwrap_or("");
+        let message = match message {
+            Some(message) => message.clone(),
+            None if token.is_empty() => "parse error: unexpected end of input".to_string(),
+            None => format!("parse error: unexpected `{token}`"),
+        };
+        let start = start + (input.len() - input.trim_start().len());
+        let span = Span {
+            start,
+            end: start + token.len(),
+        };
+        let source = Source::new(name, code, SpanTable::default());
+        Diagnostic::at(message, &source, span)
+    }
+
+    /// Creates a diagnostic for a type error, pointing to the offending term or type annotation
+    /// if it is found in `sources`.
+    ///
+    /// The offending term is looked for inside the node where type checking failed, see
+    /// [`Term::type_check_located`], or else the node itself is pointed to.
+    ///
+    /// ```rust
+    /// # use stlc_project::diagnostic::{Diagnostic, Source};
+    /// # use stlc_project::span::record_spans;
+    /// # use stlc_project::term::parse::parse_term;
+    /// let code = "let x = True in (fun x : Integer, x + 1) x";
+    /// let (result, spans) = record_spans(code, parse_term);
+    /// let source = Source::new("<repl>", code, spans);
+    /// let (_, t) = result.unwrap();
+    /// let err = t.type_check_located().unwrap_err();
+    /// // The argument is pointed to rather than the variable of the same name in the body
+    /// assert_eq!(
+    ///     Diagnostic::from_type_error(&[source], &err).to_string(),
+    ///     "\
+    /// error: wrong argument type: expected ℤ, found 𝟚 in x
+    ///  --> <repl>:1:42
+    ///   |
+    /// 1 | let x = True in (fun x : Integer, x + 1) x
+    ///   |                                          ^"
+    /// );
+    /// ```
+    pub fn from_type_error(sources: &[Source], err: &Located<TypeError>) -> Self {
+        use TypeError::*;
+        let Located { error, node } = err;
+        let span = |spans: &SpanTable| match error {
+            UndefinedVariable(_) => spans.term_span(node),
+            UndefinedTypeVariable(a) => {
+                find_type(spans, node, |ty| matches!(ty, Type::Var(b) if b == a))
+            }
+            UndefinedType(name) => {
+                find_type(spans, node, |ty| matches!(ty, Type::Named(other) if other == name))
+            }
+            DuplicateLabel { ty, .. } => find_type(spans, node, |other| other == ty),
+            // The last binding of the name is the duplicate one
+            DuplicateBinding { var, .. } => match node {
+                Term::LetRec { bindings, .. } => bindings
+                    .iter()
+                    .rposition(|(other, ..)| other == var)
+                    .and_then(|i| {
+                        spans
+                            .declaration_span(&bindings[i].0)
+                            .or_else(|| spans.child_span(node, i))
+                    }),
+                _ => None,
+            },
+            WrongAppTypeLeft { term, .. }
+            | ArgumentMismatch { term, .. }
+            | OccursCheck { term, .. }
+            | NotComparable { term, .. }
+            | BranchMismatch { term, .. }
+            | ExpectedBoolean { term, .. }
+            | ExpectedInteger { term, .. }
+            | ExpectedString { term, .. }
+            | NotOrdered { term, .. }
+            | OperandMismatch { term, .. }
+            | NotAProduct { term, .. }
+            | NotATuple { term, .. }
+            | DuplicateField { term, .. }
+            | NoSuchField { term, .. }
+            | FieldMismatch { term, .. }
+            | NotAList { term, .. }
+            | NotASum { term, .. }
+            | NotAVariant { term, .. }
+            | NoSuchLabel { term, .. }
+            | MissingArm { term, .. }
+            | RedundantArm { term, .. }
+            | ConsMismatch { term, .. }
+            | FixNotAFunction { term, .. }
+            | FixMismatch { term, .. }
+            | LetRecMismatch { term, .. }
+            | NotAForall { term, .. }
+            | TypeVariableEscape { term, .. }
+            | NotARecursiveType { term, .. }
+            | FoldMismatch { term, .. } => find_term(spans, node, term),
+        }
+        .or_else(|| spans.term_span(node));
+        Diagnostic::at_span(error, sources, span)
+    }
+
+    /// Creates a diagnostic for an evaluation error, pointing to the stuck redex or the offending
+    /// value if either of them is found in `sources`.
+    ///
+    /// They are looked for inside the node which got stuck, see [`Term::eval_located`], or else
+    /// the node itself is pointed to.
+    pub fn from_eval_error(sources: &[Source], err: &Located<EvalError>) -> Self {
+        use EvalError::*;
+        let Located { error, node } = err;
+        let span = |spans: &SpanTable| match error {
+            FreeVariable(_) => spans.term_span(node),
+            IsValue(t) | DivisionByZero { redex: t } | Overflow { redex: t } => {
+                find_term(spans, node, t)
+            }
+            NotAFunction { redex, value }
+            | NotABoolean { redex, value }
+            | NotAnInteger { redex, value }
+            | NotAString { redex, value }
+            | NotAChar { redex, value }
+            | IndexOutOfBounds { redex, value }
+            | NotAPair { redex, value }
+            | NotATuple { redex, value }
+            | NotARecord { redex, value }
+            | NotAList { redex, value }
+            | NotASum { redex, value }
+            | NotAVariant { redex, value }
+            | NotComparable { redex, value }
+            | NotATypeAbstraction { redex, value }
+            | NotAFold { redex, value } => {
+                find_term(spans, node, redex).or_else(|| find_term(spans, node, value))
+            }
+        }
+        .or_else(|| spans.term_span(node));
+        Diagnostic::at_span(error, sources, span)
+    }
+}
+
+/// Returns the span of the outermost subterm of `node` equal to `t` with a span in `spans`,
+/// looking for it breadth first.
+fn find_term(spans: &SpanTable, node: &Term, t: &Term) -> Option<Span> {
+    let mut queue = VecDeque::from([(None, 0, node)]);
+    while let Some((parent, index, u)) = queue.pop_front() {
+        if u == t {
+            let span = match parent {
+                Some(parent) => spans.child_span(parent, index),
+                None => spans.term_span(u),
+            };
+            if span.is_some() {
+                return span;
+            }
+        }
+        queue.extend(u.children().into_iter().enumerate().map(|(i, child)| (Some(u), i, child)));
+    }
+    None
+}
+
+/// Returns the span of the first type satisfying `pred` with a span in `spans`, among the type
+/// annotations of `node` and the types inside them.
+fn find_type(spans: &SpanTable, node: &Term, pred: impl Fn(&Type) -> bool) -> Option<Span> {
+    let mut queue: VecDeque<_> = node.types().into();
+    while let Some(ty) = queue.pop_front() {
+        if pred(ty)
+            && let Some(span) = spans.type_span(ty)
+        {
+            return Some(span);
+        }
+        queue.extend(ty.children());
+    }
+    None
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message)?,
+            Severity::Warning => write!(f, "warning: {}", self.message)?,
+        }
+        if let Some(Location {
+            name,
+            line,
+            column,
+            text,
+            width,
+        }) = &self.location
+        {
+            let gutter = " ".repeat(line.to_string().len());
+            write!(f, "\n{gutter}--> {name}:{line}:{column}")?;
+            write!(f, "\n{gutter} |")?;
+            write!(f, "\n{line} | {text}")?;
+            write!(
+                f,
+                "\n{gutter} | {}{}",
+                " ".repeat(column - 1),
+                "^".repeat(*width)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
@@ -0,0 +1,837 @@
+//! # Big-step evaluation
+//!
+//! An environment based interpreter for [`Term`]s, evaluating a term directly to a [`Value`].
+//!
+//! Unlike [`Term::multistep`], which substitutes values into terms and thus clones the bodies of
+//! abstractions on every application, the interpreter evaluates abstractions to closures which
+//! borrow their body from the evaluated term and capture their environment by reference counting.
+//!
+//! Both evaluators agree on the values of all terms, a value of the interpreter can be converted
+//! back to a term with [`Value::to_term`]. Type applications bind the type variable of the type
+//! abstraction in the environment rather than substituting it into the body, the types are
+//! substituted into the annotations of a value when it is read back.
+//!
+//! ```rust
+//! # use stlc_project::term::util::*;
+//! let t = app(abs("x", Integer, add("x", 1)), 2);
+//! assert_eq!(t.eval().unwrap().to_term(), t.multistep());
+//! let t = tapp(tabs("a", abs("x", tvar("a"), "x")), Integer);
+//! assert_eq!(t.eval().unwrap().to_term(), abs("x", Integer, "x"));
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::r#type::Type;
+use crate::arith::{checked, Arithmetic, Integer};
+use crate::span::Located;
+use crate::term::{EvalError, StuckError, Term};
+
+/// The result of evaluating a term with [`Term::eval`].
+///
+/// The value borrows the abstractions and type annotations of the evaluated term.
+/// The annotations are read back along with the types bound to their type variables, see
+/// [`Annotation`].
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    Unit,
+    Bool(bool),
+    Int(Integer),
+    Str(Rc<str>),
+    Char(char),
+    Closure(Closure<'a>),
+    Pair(Rc<Value<'a>>, Rc<Value<'a>>),
+    Tuple(Rc<[Value<'a>]>),
+    Record(Rc<[(&'a str, Value<'a>)]>),
+    Nil(Annotation<'a>),
+    Cons(Rc<Value<'a>>, Rc<Value<'a>>),
+    Inl(Rc<Value<'a>>, Annotation<'a>),
+    Inr(Rc<Value<'a>>, Annotation<'a>),
+    Tag(&'a str, Rc<Value<'a>>, Annotation<'a>),
+    Fold(Rc<Value<'a>>, Annotation<'a>),
+    /// A type abstraction, whose body is evaluated when it is applied to a type
+    TAbs {
+        var: &'a str,
+        body: &'a Term,
+        env: Env<'a>,
+    },
+}
+
+/// An abstraction along with the environment it was evaluated in.
+#[derive(Debug, Clone)]
+pub struct Closure<'a> {
+    var: &'a str,
+    ty: &'a Type,
+    body: &'a Term,
+    env: Env<'a>,
+}
+
+/// A type annotation of the evaluated term, along with the types bound to its type variables.
+#[derive(Debug, Clone)]
+pub struct Annotation<'a> {
+    ty: &'a Type,
+    types: TypeEnv,
+}
+
+impl Annotation<'_> {
+    /// Returns the annotated type with the bound types substituted.
+    pub fn to_type(&self) -> Type {
+        self.types.close_type(self.ty)
+    }
+}
+
+/// A persistent environment mapping the type variables of the enclosing type abstractions to the
+/// types they were applied to.
+///
+/// The types are bound once they are closed, i.e. they do not mention the type variables bound
+/// by the environment, so they can be substituted all at once.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TypeEnv(Option<Rc<TypeFrame>>);
+
+#[derive(Debug)]
+struct TypeFrame {
+    var: String,
+    ty: Type,
+    next: TypeEnv,
+}
+
+impl TypeEnv {
+    /// Returns a new environment with `var` bound to the closed type `ty`.
+    pub(crate) fn bind(&self, var: &str, ty: Type) -> Self {
+        TypeEnv(Some(Rc::new(TypeFrame {
+            var: var.to_string(),
+            ty,
+            next: self.clone(),
+        })))
+    }
+
+    /// Returns the substitution of all the bound type variables, the innermost one taking
+    /// precedence.
+    fn substitution(&self) -> HashMap<String, Type> {
+        let mut substitution = HashMap::new();
+        let mut env = self;
+        while let Some(frame) = &env.0 {
+            substitution
+                .entry(frame.var.clone())
+                .or_insert_with(|| frame.ty.clone());
+            env = &frame.next;
+        }
+        substitution
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Substitutes the bound types into `ty`.
+    pub(crate) fn close_type(&self, ty: &Type) -> Type {
+        match self.0 {
+            None => ty.clone(),
+            Some(_) => ty.subst_type_vars(&self.substitution()),
+        }
+    }
+
+    /// Substitutes the bound types into the annotations of `t`.
+    pub(crate) fn close(&self, t: Term) -> Term {
+        match self.0 {
+            None => t,
+            Some(_) => t.subst_type_vars(&self.substitution()),
+        }
+    }
+}
+
+/// A persistent environment mapping variables to their values, and type variables to types.
+#[derive(Debug, Clone, Default)]
+pub struct Env<'a> {
+    frames: Option<Rc<Frame<'a>>>,
+    types: TypeEnv,
+}
+
+#[derive(Debug)]
+struct Frame<'a> {
+    var: &'a str,
+    binding: Binding<'a>,
+    next: Env<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum Binding<'a> {
+    Value(Value<'a>),
+    /// The variable is bound to `fix` of the closure, which is unfolded whenever the variable is used
+    Fix(Closure<'a>),
+    /// The variable is bound to the `index`-th of the recursive `bindings`, evaluated in `env`
+    /// along with all of them whenever the variable is used
+    Rec {
+        bindings: &'a [(String, Type, Term)],
+        index: usize,
+        env: Env<'a>,
+    },
+}
+
+impl<'a> Env<'a> {
+    /// Returns an empty environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bind(&self, var: &'a str, binding: Binding<'a>) -> Self {
+        Env {
+            frames: Some(Rc::new(Frame {
+                var,
+                binding,
+                next: self.clone(),
+            })),
+            types: self.types.clone(),
+        }
+    }
+
+    /// Returns a new environment with the type variable `var` bound to `ty`, which is closed
+    /// in the environment `outer` it was written in.
+    fn bind_type(&self, var: &str, ty: &Type, outer: &Env) -> Self {
+        Env {
+            frames: self.frames.clone(),
+            types: self.types.bind(var, outer.types.close_type(ty)),
+        }
+    }
+
+    fn annotation(&self, ty: &'a Type) -> Annotation<'a> {
+        Annotation {
+            ty,
+            types: self.types.clone(),
+        }
+    }
+
+    /// Returns a new environment with `var` bound to `value`.
+    pub fn insert(&self, var: &'a str, value: Value<'a>) -> Self {
+        self.bind(var, Binding::Value(value))
+    }
+
+    /// Returns a new environment with each of the recursive `bindings` bound to itself.
+    fn bind_rec(&self, bindings: &'a [(String, Type, Term)]) -> Self {
+        bindings.iter().enumerate().fold(self.clone(), |rec, (index, (var, ..))| {
+            rec.bind(
+                var,
+                Binding::Rec {
+                    bindings,
+                    index,
+                    env: self.clone(),
+                },
+            )
+        })
+    }
+
+    fn lookup(&self, var: &str) -> Option<&Binding<'a>> {
+        let mut env = self;
+        while let Some(frame) = &env.frames {
+            if frame.var == var {
+                return Some(&frame.binding);
+            }
+            env = &frame.next;
+        }
+        None
+    }
+
+    /// Substitutes the bound types and the values of the free variables of `t` into `t`.
+    fn close(&self, t: Term) -> Term {
+        let mut t = self.types.close(t);
+        let free_vars: HashSet<String> = t.free_vars();
+        for x in &free_vars {
+            let v = match self.lookup(x) {
+                Some(Binding::Value(v)) => v.to_term(),
+                Some(Binding::Fix(c)) => Term::Fix(Box::new(c.to_term())),
+                Some(Binding::Rec {
+                    bindings,
+                    index,
+                    env,
+                }) => env.close(Term::LetRec {
+                    bindings: bindings.to_vec(),
+                    body: Box::new(bindings[*index].2.clone()),
+                }),
+                None => continue,
+            };
+            t = t.subst(x, v);
+        }
+        t
+    }
+}
+
+impl Closure<'_> {
+    /// Converts the closure to an abstraction, substituting the captured environment into its body.
+    pub fn to_term(&self) -> Term {
+        self.env.close(Term::Abs {
+            var: self.var.to_string(),
+            ty: self.ty.clone(),
+            body: Box::new(self.body.clone()),
+        })
+    }
+}
+
+impl Value<'_> {
+    /// Converts the value back to a term, which is a value in the sense of [`Term::is_value`].
+    pub fn to_term(&self) -> Term {
+        match self {
+            Value::Unit => Term::Unit,
+            Value::Bool(true) => Term::True,
+            Value::Bool(false) => Term::False,
+            Value::Int(i) => Term::Int(i.clone()),
+            Value::Str(s) => Term::Str(s.to_string()),
+            Value::Char(c) => Term::Char(*c),
+            Value::Closure(c) => c.to_term(),
+            Value::Pair(v1, v2) => Term::Pair(Box::new(v1.to_term()), Box::new(v2.to_term())),
+            Value::Tuple(vs) => Term::Tuple(vs.iter().map(Value::to_term).collect()),
+            Value::Record(fields) => Term::Record(
+                fields
+                    .iter()
+                    .map(|(l, v)| (l.to_string(), v.to_term()))
+                    .collect(),
+            ),
+            Value::Nil(ty) => Term::Nil(ty.to_type()),
+            Value::Cons(v1, v2) => Term::Cons(Box::new(v1.to_term()), Box::new(v2.to_term())),
+            Value::Inl(v, ty) => Term::Inl(Box::new(v.to_term()), ty.to_type()),
+            Value::Inr(v, ty) => Term::Inr(Box::new(v.to_term()), ty.to_type()),
+            Value::Tag(l, v, ty) => Term::Tag(l.to_string(), Box::new(v.to_term()), ty.to_type()),
+            Value::Fold(v, ty) => Term::Fold(Box::new(v.to_term()), ty.to_type()),
+            Value::TAbs { var, body, env } => env.close(Term::TAbs {
+                var: var.to_string(),
+                body: Box::new((*body).clone()),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_term())
+    }
+}
+
+/// Compares two values structurally.
+///
+/// Returns the offending value if either of the values contains a closure.
+fn values_equal<'v, 'a>(v1: &'v Value<'a>, v2: &'v Value<'a>) -> Result<bool, &'v Value<'a>> {
+    match (v1, v2) {
+        (Value::Closure(_) | Value::TAbs { .. }, _) => Err(v1),
+        (_, Value::Closure(_) | Value::TAbs { .. }) => Err(v2),
+        (Value::Unit, Value::Unit) => Ok(true),
+        (Value::Int(i1), Value::Int(i2)) => Ok(i1 == i2),
+        (Value::Str(s1), Value::Str(s2)) => Ok(s1 == s2),
+        (Value::Char(c1), Value::Char(c2)) => Ok(c1 == c2),
+        (Value::Bool(b1), Value::Bool(b2)) => Ok(b1 == b2),
+        (Value::Nil(_), Value::Nil(_)) => Ok(true),
+        (Value::Pair(a1, b1), Value::Pair(a2, b2)) | (Value::Cons(a1, b1), Value::Cons(a2, b2)) => {
+            let first = values_equal(a1, a2)?;
+            Ok(values_equal(b1, b2)? && first)
+        }
+        (Value::Tuple(vs1), Value::Tuple(vs2)) => {
+            let mut equal = vs1.len() == vs2.len();
+            for (v1, v2) in vs1.iter().zip(vs2.iter()) {
+                equal = values_equal(v1, v2)? && equal;
+            }
+            Ok(equal)
+        }
+        (Value::Record(fields1), Value::Record(fields2)) => {
+            let mut equal = fields1.len() == fields2.len();
+            for (l, v1) in fields1.iter() {
+                equal = match fields2.iter().find(|(l2, _)| l2 == l) {
+                    Some((_, v2)) => values_equal(v1, v2)? && equal,
+                    None => false,
+                };
+            }
+            Ok(equal)
+        }
+        (Value::Inl(v1, _), Value::Inl(v2, _)) | (Value::Inr(v1, _), Value::Inr(v2, _)) => {
+            values_equal(v1, v2)
+        }
+        (Value::Tag(l1, v1, _), Value::Tag(l2, v2, _)) => Ok(values_equal(v1, v2)? && l1 == l2),
+        (Value::Fold(v1, _), Value::Fold(v2, _)) => values_equal(v1, v2),
+        _ => Ok(false),
+    }
+}
+
+/// Orders two integers, characters or strings.
+///
+/// Returns the constructor of the error and the offending value if they are not both of the same
+/// of these types, where the first value determines the expected type of the second one.
+fn compare<'v, 'a>(
+    v1: &'v Value<'a>,
+    v2: &'v Value<'a>,
+) -> Result<Ordering, (StuckError, &'v Value<'a>)> {
+    match (v1, v2) {
+        (Value::Int(i1), Value::Int(i2)) => Ok(i1.cmp(i2)),
+        (Value::Char(c1), Value::Char(c2)) => Ok(c1.cmp(c2)),
+        (Value::Str(s1), Value::Str(s2)) => Ok(s1.cmp(s2)),
+        (Value::Int(_), _) => Err((not_an_integer, v2)),
+        (Value::Char(_), _) => Err((not_a_char, v2)),
+        (Value::Str(_), _) => Err((not_a_string, v2)),
+        _ => Err((not_an_integer, v1)),
+    }
+}
+
+/// What the interpreter does next: evaluate a term in an environment, or return a value to the
+/// innermost continuation.
+enum Next<'a> {
+    Eval(&'a Term, Env<'a>),
+    Return(Value<'a>),
+}
+
+/// A term whose operands are being evaluated from left to right, the values of the operands
+/// evaluated so far being on top of the value stack from `base`.
+struct Cont<'a> {
+    t: &'a Term,
+    env: Env<'a>,
+    base: usize,
+}
+
+/// Returns the `i`-th operand of `t`, i.e. the subterms evaluated before `t` is reduced.
+///
+/// The other subterms are only evaluated once `t` is reduced, such as the branches of an `if`,
+/// the second operand of `&&`, or the body of a `let`.
+fn operand(t: &Term, i: usize) -> Option<&Term> {
+    use Term::*;
+
+    match t {
+        Var(_) | Abs { .. } | Unit | True | False | Int(_) | Str(_) | Char(_) | Nil(_)
+        | TAbs { .. } | Data { .. } | LetRec { .. } => None,
+        Let { val_t: t1, .. }
+        | Ite { cond: t1, .. }
+        | And(t1, _)
+        | Or(t1, _)
+        | Not(t1)
+        | Neg(t1)
+        | StrLen(t1)
+        | IntToString(t1)
+        | Fst(t1)
+        | Snd(t1)
+        | Proj(t1, _)
+        | Field(t1, _)
+        | LCase { t: t1, .. }
+        | Inl(t1, _)
+        | Inr(t1, _)
+        | Case { t: t1, .. }
+        | Tag(_, t1, _)
+        | Match { t: t1, .. }
+        | Fix(t1)
+        | TApp(t1, _)
+        | Fold(t1, _)
+        | Unfold(t1) => (i == 0).then_some(t1),
+        App(t1, t2)
+        | Add(t1, t2)
+        | Sub(t1, t2)
+        | Mul(t1, t2)
+        | Div(t1, t2)
+        | Mod(t1, t2)
+        | Concat(t1, t2)
+        | CharAt(t1, t2)
+        | Eq(t1, t2)
+        | Ne(t1, t2)
+        | Lt(t1, t2)
+        | Le(t1, t2)
+        | Gt(t1, t2)
+        | Ge(t1, t2)
+        | Pair(t1, t2)
+        | Cons(t1, t2)
+        | Append(t1, t2) => [t1, t2].get(i).map(|t| &***t),
+        Tuple(ts) => ts.get(i),
+        Record(fields) => fields.get(i).map(|(_, t)| t),
+        Update(t1, updates) => match i {
+            0 => Some(t1),
+            i => updates.get(i - 1).map(|(_, t)| t),
+        },
+    }
+}
+
+/// Evaluates `t` in the environment `env`.
+///
+/// The interpreter keeps the terms waiting for the values of their operands on an explicit stack
+/// of continuations rather than on the Rust stack, so that deep recursion in the evaluated term
+/// does not overflow it.
+///
+/// # Errors
+///
+/// Returns the same errors as [`Term::try_step`], with the stuck redex as it was written in `t`.
+pub fn eval<'a>(t: &'a Term, env: &Env<'a>) -> Result<Value<'a>, EvalError> {
+    eval_located(t, env).map_err(|e| e.error)
+}
+
+/// Like [`eval`], but also returns the node of `t` which got stuck on error.
+pub fn eval_located<'a>(t: &'a Term, env: &Env<'a>) -> Result<Value<'a>, Located<'a, EvalError>> {
+    let located = |node| move |error| Located { error, node };
+    let mut stack: Vec<Cont<'a>> = vec![];
+    let mut values: Vec<Value<'a>> = vec![];
+    let mut next = Next::Eval(t, env.clone());
+    loop {
+        next = match next {
+            Next::Eval(t, env) => match operand(t, 0) {
+                Some(t1) => {
+                    stack.push(Cont {
+                        t,
+                        env: env.clone(),
+                        base: values.len(),
+                    });
+                    Next::Eval(t1, env)
+                }
+                None => reduce(t, env, &[]).map_err(located(t))?,
+            },
+            Next::Return(v) => {
+                let Some(cont) = stack.last() else {
+                    return Ok(v);
+                };
+                values.push(v);
+                match operand(cont.t, values.len() - cont.base) {
+                    Some(t1) => Next::Eval(t1, cont.env.clone()),
+                    None => {
+                        let Cont { t, env, base } =
+                            stack.pop().expect("the continuation to be on the stack");
+                        let next = reduce(t, env, &values[base..]).map_err(located(t))?;
+                        values.truncate(base);
+                        next
+                    }
+                }
+            }
+        };
+    }
+}
+
+/// Reduces `t` in the environment `env` once the values of all its [`operand`]s are known.
+fn reduce<'a>(t: &'a Term, env: Env<'a>, values: &[Value<'a>]) -> Result<Next<'a>, EvalError> {
+    use Term::*;
+
+    let stuck = |ctor: fn(Box<Term>, Box<Term>) -> EvalError, value: &Value| {
+        ctor(Box::new(t.clone()), Box::new(value.to_term()))
+    };
+    let int = |v: &Value<'a>| match v {
+        Value::Int(i) => Ok(i.clone()),
+        v => Err(stuck(not_an_integer, v)),
+    };
+    let arith = |i: Option<Integer>| {
+        i.map(|i| Next::Return(Value::Int(i)))
+            .ok_or_else(|| EvalError::Overflow {
+                redex: Box::new(t.clone()),
+            })
+    };
+    let string = |v: &Value<'a>| match v {
+        Value::Str(s) => Ok(s.clone()),
+        v => Err(stuck(not_a_string, v)),
+    };
+    let ord = |op: fn(Ordering) -> bool| match compare(&values[0], &values[1]) {
+        Ok(ordering) => Ok(Next::Return(Value::Bool(op(ordering)))),
+        Err((ctor, v)) => Err(stuck(ctor, v)),
+    };
+
+    let value = match (t, values) {
+        (Var(x), []) => match env.lookup(x) {
+            Some(Binding::Value(v)) => v.clone(),
+            Some(Binding::Fix(c)) => return Ok(unfold_fix(c)),
+            Some(Binding::Rec {
+                bindings,
+                index,
+                env,
+            }) => return Ok(Next::Eval(&bindings[*index].2, env.bind_rec(bindings))),
+            None => return Err(EvalError::FreeVariable(x.clone())),
+        },
+        (Abs { var, ty, body }, []) => Value::Closure(Closure {
+            var,
+            ty,
+            body,
+            env,
+        }),
+        (App(..), [Value::Closure(c), v2]) => {
+            return Ok(Next::Eval(c.body, c.env.insert(c.var, v2.clone())));
+        }
+        (App(..), [v1, _]) => return Err(stuck(not_a_function, v1)),
+        (Let { var, body, .. }, [v]) => return Ok(Next::Eval(body, env.insert(var, v.clone()))),
+        (LetRec { bindings, body }, []) => return Ok(Next::Eval(body, env.bind_rec(bindings))),
+
+        (Unit, []) => Value::Unit,
+        (True, []) => Value::Bool(true),
+        (False, []) => Value::Bool(false),
+        (
+            Ite {
+                if_true, if_false, ..
+            },
+            [v],
+        ) => match v {
+            Value::Bool(true) => return Ok(Next::Eval(if_true, env)),
+            Value::Bool(false) => return Ok(Next::Eval(if_false, env)),
+            v => return Err(stuck(not_a_boolean, v)),
+        },
+        (And(_, t2) | Or(_, t2), [v]) => match (v, t) {
+            (Value::Bool(true), And(..)) | (Value::Bool(false), Or(..)) => {
+                return Ok(Next::Eval(t2, env));
+            }
+            (Value::Bool(b), _) => Value::Bool(*b),
+            (v, _) => return Err(stuck(not_a_boolean, v)),
+        },
+        (Not(_), [v]) => match v {
+            Value::Bool(b) => Value::Bool(!b),
+            v => return Err(stuck(not_a_boolean, v)),
+        },
+
+        (Int(i), []) => Value::Int(i.clone()),
+        (Add(..), [v1, v2]) => return arith(checked(Integer::add, &int(v1)?, &int(v2)?)),
+        (Sub(..), [v1, v2]) => return arith(checked(Integer::sub, &int(v1)?, &int(v2)?)),
+        (Mul(..), [v1, v2]) => return arith(checked(Integer::mul, &int(v1)?, &int(v2)?)),
+        (Div(..) | Mod(..), [v1, v2]) => {
+            let i1 = int(v1)?;
+            return match int(v2)? {
+                i2 if i2.is_zero() => Err(EvalError::DivisionByZero {
+                    redex: Box::new(t.clone()),
+                }),
+                i2 if matches!(t, Div(..)) => arith(checked(Integer::div, &i1, &i2)),
+                i2 => arith(checked(Integer::rem, &i1, &i2)),
+            };
+        }
+        (Neg(_), [v]) => return arith(Arithmetic::current().check(int(v)?.neg())),
+
+        (Eq(..) | Ne(..), [v1, v2]) => match values_equal(v1, v2) {
+            Ok(equal) => Value::Bool(equal == matches!(t, Eq(..))),
+            Err(v) => return Err(stuck(not_comparable, v)),
+        },
+        (Lt(..), [_, _]) => return ord(Ordering::is_lt),
+        (Le(..), [_, _]) => return ord(Ordering::is_le),
+        (Gt(..), [_, _]) => return ord(Ordering::is_gt),
+        (Ge(..), [_, _]) => return ord(Ordering::is_ge),
+
+        (Str(s), []) => Value::Str(s.as_str().into()),
+        (Char(c), []) => Value::Char(*c),
+        (Concat(..), [v1, v2]) => {
+            let s1 = string(v1)?;
+            let s2 = string(v2)?;
+            Value::Str(format!("{s1}{s2}").into())
+        }
+        (StrLen(_), [v]) => Value::Int(string(v)?.chars().count().into()),
+        (CharAt(..), [v1, v2]) => {
+            let s = string(v1)?;
+            let i = int(v2)?;
+            i.to_usize()
+                .and_then(|i| s.chars().nth(i))
+                .map(Value::Char)
+                .ok_or_else(|| stuck(index_out_of_bounds, &Value::Int(i)))?
+        }
+        (IntToString(_), [v]) => Value::Str(int(v)?.to_string().into()),
+
+        (Pair(..), [v1, v2]) => Value::Pair(Rc::new(v1.clone()), Rc::new(v2.clone())),
+        (Fst(_), [v]) => match v {
+            Value::Pair(v1, _) => (**v1).clone(),
+            v => return Err(stuck(not_a_pair, v)),
+        },
+        (Snd(_), [v]) => match v {
+            Value::Pair(_, v2) => (**v2).clone(),
+            v => return Err(stuck(not_a_pair, v)),
+        },
+        (Tuple(_), _) => Value::Tuple(values.into()),
+        (Proj(_, i), [v]) => match (v, i) {
+            (Value::Pair(v1, _), 0) => (**v1).clone(),
+            (Value::Pair(_, v2), 1) => (**v2).clone(),
+            (Value::Tuple(vs), i) if *i < vs.len() => vs[*i].clone(),
+            (v, _) => return Err(stuck(not_a_tuple, v)),
+        },
+        (Record(fields), _) => Value::Record(
+            fields
+                .iter()
+                .map(|(l, _)| l.as_str())
+                .zip(values.iter().cloned())
+                .collect(),
+        ),
+        (Field(_, l), [v]) => match v {
+            Value::Record(fields) => fields.iter().find(|(l2, _)| l2 == l),
+            _ => None,
+        }
+        .map(|(_, v)| v.clone())
+        .ok_or_else(|| stuck(not_a_record, v))?,
+        (Update(_, updates), [v, us @ ..]) => match v {
+            Value::Record(fields)
+                if updates.iter().all(|(l, _)| fields.iter().any(|(l2, _)| l2 == l)) =>
+            {
+                let mut fields = fields.to_vec();
+                for ((l, _), u) in updates.iter().zip(us) {
+                    let field = fields.iter_mut().find(|(l2, _)| l2 == l);
+                    field.expect("the field to exist").1 = u.clone();
+                }
+                Value::Record(fields.into())
+            }
+            v => return Err(stuck(not_a_record, v)),
+        },
+
+        (Nil(ty), []) => Value::Nil(env.annotation(ty)),
+        (Cons(..), [v1, v2]) => Value::Cons(Rc::new(v1.clone()), Rc::new(v2.clone())),
+        (
+            LCase {
+                nil_t,
+                head_var,
+                tail_var,
+                cons_t,
+                ..
+            },
+            [v],
+        ) => match v {
+            Value::Nil(_) => return Ok(Next::Eval(nil_t, env)),
+            Value::Cons(h, tl) => {
+                let env = env
+                    .insert(head_var, (**h).clone())
+                    .insert(tail_var, (**tl).clone());
+                return Ok(Next::Eval(cons_t, env));
+            }
+            v => return Err(stuck(not_a_list, v)),
+        },
+        (Append(..), [v1, v2]) => {
+            let mut heads = vec![];
+            let mut v1 = v1;
+            loop {
+                match v1 {
+                    Value::Nil(_) => break,
+                    Value::Cons(h, tl) => {
+                        heads.push(h.clone());
+                        v1 = tl;
+                    }
+                    v => return Err(stuck(not_a_list, v)),
+                }
+            }
+            let v2 = v2.clone();
+            heads.into_iter().rev().fold(v2, |tl, h| Value::Cons(h, Rc::new(tl)))
+        }
+
+        (Inl(_, ty), [v]) => Value::Inl(Rc::new(v.clone()), env.annotation(ty)),
+        (Inr(_, ty), [v]) => Value::Inr(Rc::new(v.clone()), env.annotation(ty)),
+        (
+            Case {
+                inl_var,
+                inl_t,
+                inr_var,
+                inr_t,
+                ..
+            },
+            [v],
+        ) => match v {
+            Value::Inl(v, _) => return Ok(Next::Eval(inl_t, env.insert(inl_var, (**v).clone()))),
+            Value::Inr(v, _) => return Ok(Next::Eval(inr_t, env.insert(inr_var, (**v).clone()))),
+            v => return Err(stuck(not_a_sum, v)),
+        },
+
+        (Tag(l, _, ty), [v]) => Value::Tag(l, Rc::new(v.clone()), env.annotation(ty)),
+        (Match { arms, .. }, [v]) => {
+            let arm = match v {
+                Value::Tag(l, v, _) => arms
+                    .iter()
+                    .find(|(l2, _, _)| l2 == l)
+                    .map(|(_, var, arm)| (var, arm, v)),
+                _ => None,
+            };
+            return match arm {
+                Some((var, arm, v)) => Ok(Next::Eval(arm, env.insert(var, (**v).clone()))),
+                None => Err(stuck(not_a_variant, v)),
+            };
+        }
+
+        (Fix(_), [v]) => match v {
+            Value::Closure(c) => return Ok(unfold_fix(c)),
+            v => return Err(stuck(not_a_function, v)),
+        },
+
+        (TAbs { var, body }, []) => Value::TAbs { var, body, env },
+        (TApp(_, ty), [v]) => match v {
+            Value::TAbs {
+                var,
+                body,
+                env: inner,
+            } => return Ok(Next::Eval(body, inner.bind_type(var, ty, &env))),
+            v => return Err(stuck(not_a_type_abstraction, v)),
+        },
+        (Data { body, .. }, []) => return Ok(Next::Eval(body, env)),
+
+        (Fold(_, ty), [v]) => Value::Fold(Rc::new(v.clone()), env.annotation(ty)),
+        (Unfold(_), [v]) => match v {
+            Value::Fold(v, _) => (**v).clone(),
+            v => return Err(stuck(not_a_fold, v)),
+        },
+
+        _ => unreachable!("{t} to be reduced with the values of its {} operands", values.len()),
+    };
+    Ok(Next::Return(value))
+}
+
+/// Continues with the body of `c` with its variable bound to `fix c`.
+fn unfold_fix<'a>(c: &Closure<'a>) -> Next<'a> {
+    Next::Eval(c.body, c.env.bind(c.var, Binding::Fix(c.clone())))
+}
+
+fn not_a_function(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAFunction { redex, value }
+}
+fn not_a_boolean(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotABoolean { redex, value }
+}
+fn not_an_integer(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAnInteger { redex, value }
+}
+fn not_a_string(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAString { redex, value }
+}
+fn not_a_char(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAChar { redex, value }
+}
+fn index_out_of_bounds(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::IndexOutOfBounds { redex, value }
+}
+fn not_a_pair(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAPair { redex, value }
+}
+fn not_a_tuple(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotATuple { redex, value }
+}
+fn not_a_record(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotARecord { redex, value }
+}
+fn not_a_list(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAList { redex, value }
+}
+fn not_a_sum(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotASum { redex, value }
+}
+fn not_a_variant(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAVariant { redex, value }
+}
+fn not_comparable(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotComparable { redex, value }
+}
+fn not_a_fold(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAFold { redex, value }
+}
+fn not_a_type_abstraction(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotATypeAbstraction { redex, value }
+}
+
+impl Term {
+    /// Evaluates the term to a [`Value`] with the big-step interpreter, see [`crate::eval`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Term::try_multistep`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// let t = letin("f", abs("x", Integer, pair("x", add("x", 1))), app("f", 1));
+    /// assert_eq!(t.eval().unwrap().to_term(), pair(1, 2));
+    /// ```
+    pub fn eval(&self) -> Result<Value<'_>, EvalError> {
+        self.eval_located().map_err(|e| e.error)
+    }
+
+    /// Like [`Term::eval`], but also returns the node of `self` which got stuck on error.
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// let t = add(1, div(2, 0));
+    /// let Add(_, div) = &t else { unreachable!() };
+    /// assert!(std::ptr::eq(t.eval_located().unwrap_err().node, &**div));
+    /// ```
+    pub fn eval_located(&self) -> Result<Value<'_>, Located<'_, EvalError>> {
+        Arithmetic::current()
+            .check_literals(self)
+            .map_err(|error| Located { error, node: self })?;
+        eval_located(self, &Env::new())
+    }
+}
@@ -1,4 +1,9 @@
+pub mod arith;
 pub mod parse;
 pub mod term;
 pub mod r#type;
-pub mod module;
\ No newline at end of file
+pub mod module;
+pub mod span;
+pub mod diagnostic;
+pub mod eval;
+pub mod vm;
\ No newline at end of file
@@ -34,8 +34,15 @@ However, no points are awarded for completing this challenge.
 */
 
 use stlc_project::{
+    arith::{with_arithmetic, Arithmetic},
+    diagnostic::{Diagnostic, Source},
     module::{parse::parse_module, Module},
-    term::parse::parse_term,
+    span::{record_spans, Located, SpanTable},
+    term::{
+        parse::parse_term,
+        step::{Fuel, Outcome},
+    },
+    vm,
 };
 use nom::combinator::all_consuming;
 use nom::Parser;
@@ -47,21 +54,159 @@ use std::{
 
 use rustyline::{error::ReadlineError, DefaultEditor};
 
+/// The evaluator used by the REPL, selected with `--backend step|eval|vm`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Backend {
+    /// Small-step substitution, see `Term::multistep`
+    #[default]
+    Step,
+    /// The big-step interpreter, see `stlc_project::eval`
+    Eval,
+    /// The bytecode VM, see `stlc_project::vm`
+    Vm,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "step" => Ok(Backend::Step),
+            "eval" => Ok(Backend::Eval),
+            "vm" => Ok(Backend::Vm),
+            _ => Err(format!("unknown backend `{s}`, expected step, eval or vm")),
+        }
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Step => write!(f, "step"),
+            Backend::Eval => write!(f, "eval"),
+            Backend::Vm => write!(f, "vm"),
+        }
+    }
+}
+
+/// The text printed by the `:help` command.
+const HELP: &str = "\
+Enter a term to type check and evaluate it, or a command:
+  :fuel [N | off]               show or limit the number of evaluation steps,
+                                only supported by the step backend
+  :arith [checked | arbitrary]  show or select the arithmetic
+  :help                         show this text
+  :q                            quit";
+
+/// Parses the module of the prelude `source`, recording its spans into it.
+///
+/// Spans only find the nodes parsed along with them, not copies of them, so the prelude is parsed
+/// again for every term entered in the REPL.
+fn parse_prelude(source: &mut Source) -> Result<Module, Diagnostic> {
+    let (result, spans) =
+        record_spans(&source.code, |code| all_consuming(parse_module).parse(code));
+    source.spans = spans;
+    result
+        .map(|(_, module)| module)
+        .map_err(|e| Diagnostic::from_parse_error(&source.name, &source.code, &e))
+}
+
 fn process(
     file: Option<&str>,
     line: &str,
-    module: Module,
+    prelude: Option<&Source>,
+    backend: Backend,
+    arithmetic: Arithmetic,
+    fuel: Option<Fuel>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let (_, body) = all_consuming(parse_term)
-        .parse(line)
-        .map_err(|e| e.to_string())?;
+    let (result, spans) = record_spans(line, |code| all_consuming(parse_term).parse(code));
+    let source = Source::new("<repl>", line, spans);
+    let (_, body) =
+        result.map_err(|e| Diagnostic::from_parse_error(&source.name, &source.code, &e))?;
+    for warning in Diagnostic::warnings(&source) {
+        eprintln!("{warning}");
+    }
+    let mut prelude = prelude.cloned();
+    let module = match &mut prelude {
+        Some(source) => parse_prelude(source)?,
+        None => Module::new(),
+    };
+    let mut sources = vec![source];
+    sources.extend(prelude);
     let basepath = if let Some(p) = file {
         current_dir()?.join(Path::new(p).parent().expect("import to have a parent"))
     } else {
         current_dir()?
     };
     let t = module.to_term(basepath, body)?;
-    println!("{1} :: {0}", t.type_check()?, t.multistep());
+    let ty = t
+        .type_check_located()
+        .map_err(|e| Diagnostic::from_type_error(&sources, &e))?;
+    let eval_error = |e: Located<_>| Diagnostic::from_eval_error(&sources, &e);
+    // The step backend reduces a copy of the term, so its errors are looked for in the whole term
+    let step_error = |error| eval_error(Located { error, node: &t });
+    with_arithmetic(arithmetic, || {
+        match fuel {
+            None => match backend {
+                Backend::Step => {
+                    println!("{} :: {ty}", t.clone().try_multistep().map_err(step_error)?)
+                }
+                Backend::Eval => println!("{} :: {ty}", t.eval_located().map_err(eval_error)?),
+                Backend::Vm => {
+                    println!("{} :: {ty}", vm::compile(&t).run_located().map_err(eval_error)?)
+                }
+            },
+            Some(fuel) => match t.clone().try_multistep_fuel(fuel).map_err(step_error)? {
+                Outcome::Value(v) => println!("{v} :: {ty}"),
+                Outcome::OutOfFuel(t) => {
+                    println!("out of fuel after {} steps: {t} :: {ty}", fuel.steps)
+                }
+            },
+        }
+        Ok(())
+    })
+}
+
+/// Handles the `:fuel` command, which shows or sets the step budget of the REPL.
+///
+/// - `:fuel` prints the current budget
+/// - `:fuel N` limits evaluation to `N` steps
+/// - `:fuel off` removes the limit
+///
+/// Only the step backend counts steps, so a budget cannot be set with another backend.
+fn set_fuel(
+    arg: &str,
+    fuel: &mut Option<Fuel>,
+    backend: Backend,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match arg {
+        "" => {}
+        "off" => *fuel = None,
+        _ if backend != Backend::Step => {
+            return Err(format!("fuel is only supported by the step backend, not {backend}").into())
+        }
+        n => *fuel = Some(Fuel::new(n.parse()?)),
+    }
+    match fuel {
+        None => println!("fuel: unlimited"),
+        Some(fuel) => println!("fuel: {} steps", fuel.steps),
+    }
+    Ok(())
+}
+
+/// Handles the `:arith` command, which shows or selects the arithmetic of the REPL.
+///
+/// - `:arith` prints the current arithmetic
+/// - `:arith checked` fails on results which do not fit in an `i32`
+/// - `:arith arbitrary` uses arbitrary-precision integers
+fn set_arithmetic(
+    arg: &str,
+    arithmetic: &mut Arithmetic,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !arg.is_empty() {
+        *arithmetic = arg.parse()?;
+    }
+    println!("arith: {arithmetic}");
     Ok(())
 }
 
@@ -72,9 +217,15 @@ fn prompt<T: AsRef<str>>(file: Option<T>) -> String {
     }
 }
 
-fn start_repl(file: Option<&str>, module: Module) -> Result<(), Box<dyn std::error::Error>> {
+fn start_repl(
+    file: Option<&str>,
+    prelude: Option<&Source>,
+    backend: Backend,
+    mut arithmetic: Arithmetic,
+) -> Result<(), Box<dyn std::error::Error>> {
     let p = prompt(file);
     let mut rl = DefaultEditor::new()?;
+    let mut fuel = None;
 
     loop {
         let readline = rl.readline(&p);
@@ -85,7 +236,18 @@ fn start_repl(file: Option<&str>, module: Module) -> Result<(), Box<dyn std::err
                     break;
                 }
 
-                if let Err(e) = process(file, &line, module.clone()) {
+                let line = line.trim();
+                let result = if let Some(arg) = line.strip_prefix(":fuel") {
+                    set_fuel(arg.trim(), &mut fuel, backend)
+                } else if line == ":help" {
+                    println!("{HELP}");
+                    Ok(())
+                } else if let Some(arg) = line.strip_prefix(":arith") {
+                    set_arithmetic(arg.trim(), &mut arithmetic)
+                } else {
+                    process(file, line, prelude, backend, arithmetic, fuel)
+                };
+                if let Err(e) = result {
                     eprintln!("{e}");
                 }
             }
@@ -102,16 +264,34 @@ fn start_repl(file: Option<&str>, module: Module) -> Result<(), Box<dyn std::err
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    match args().nth(1) {
+    let mut args = args().skip(1);
+    let mut file = None;
+    let mut backend = Backend::default();
+    let mut arithmetic = Arithmetic::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => backend = args.next().ok_or("missing backend after --backend")?.parse()?,
+            "--arith" => {
+                arithmetic = args.next().ok_or("missing arithmetic after --arith")?.parse()?
+            }
+            _ => file = Some(arg),
+        }
+    }
+
+    match file {
         Some(name) => {
             let code = std::fs::read_to_string(&name).expect("failed to import file");
-            let m = all_consuming(parse_module)
-                .parse(&code)
-                .expect("failed to parse code")
-                .1;
-            start_repl(Some(&name), m)?;
+            let mut source = Source::new(&name, &code, SpanTable::default());
+            if let Err(e) = parse_prelude(&mut source) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            for warning in Diagnostic::warnings(&source) {
+                eprintln!("{warning}");
+            }
+            start_repl(Some(&name), Some(&source), backend, arithmetic)?;
         }
-        None => start_repl(None, Module::new())?,
+        None => start_repl(None, None, backend, arithmetic)?,
     };
     Ok(())
 }
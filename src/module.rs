@@ -2,27 +2,42 @@
 //!
 //! Modules are collections of declarations.
 //! A declaration consists of a name, a type and a term.
-//! A declaration may refer to any previous declaration or to itself recursively.
+//! A declaration may refer to any declaration of the module, including itself and declarations
+//! further down, so mutually recursive declarations can be written in any order.
 //!
 //! For example the following code represents a module:
 //!
 //! ```stlc
+//! y : Integer
+//! y = x
+//!
 //! x : Integer
 //! x = 5
+//! ```
 //!
-//! y : Integer
-//! y = x
+//! Modules may also declare data types, which can be used in the signatures of all declarations
+//! of the module and of the modules importing it:
+//!
+//! ```stlc
+//! data Tree = Leaf | Node Tree Integer Tree
+//!
+//! size : Tree -> Integer
+//! size = fun t : Tree,
+//!     match t of
+//!     | Leaf => 0
+//!     | Node l x r => size l + 1 + size r
 //! ```
 //!
 //! The main entrypoint to using a module is [`Module::to_term`].
 
+use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 
 use nom::combinator::all_consuming;
 use nom::Parser;
 
-use crate::{r#type::Type, term::Term};
+use crate::{diagnostic::Diagnostic, r#type::Type, term::util::letrec, term::Term};
 use parse::parse_module;
 use Term::*;
 
@@ -32,7 +47,7 @@ pub mod parse;
 pub struct Declaration(pub String, pub Type, pub Term);
 
 impl Declaration {
-    /// Converts a recursive declaration to a [`Term::Let`] with a [`Term::Fix`] inside. Takes in the inner body term.
+    /// Converts a recursive declaration to a [`Term::LetRec`]. Takes in the inner body term.
     ///
     /// ## Examples
     ///
@@ -43,18 +58,199 @@ impl Declaration {
     /// x = 5
     /// ```
     ///
-    /// Is converted to a `let x = fix (fun x : Integer, 5) in <body>` with the argument `body` filled in.
+    /// Is converted to a `let rec x : Integer = 5 in <body>` with the argument `body` filled in.
+    ///
+    /// A type signature with free type variables is generalized over them, so `id : a -> a` is
+    /// the same as `id : forall a. a -> a`. The type variables can be used in the annotations of
+    /// the term, where they stand for the type the declaration is used at.
+    ///
+    /// ```rust
+    /// # use stlc_project::module::Declaration;
+    /// # use stlc_project::term::util::*;
+    /// let id = Declaration("id".to_string(), arrow(tvar("a"), tvar("a")), abs("x", tvar("a"), "x"));
+    /// let t = id.to_term_fix(pair(app("id", 1), app("id", True)));
+    /// assert_eq!(t.type_check(), Ok(prod(Integer, Boolean)));
+    /// ```
     pub fn to_term_fix(self, body: Term) -> Term {
+        letrec([self.generalize()], body)
+    }
+
+    /// Returns the name, the type generalized over its free type variables and the term of the
+    /// declaration.
+    fn generalize(self) -> (String, Type, Term) {
         let Declaration(name, ty, term) = self;
+        let vars = ty.free_type_vars();
+        let ty = if vars.is_empty() {
+            ty
+        } else {
+            Type::Forall(vars, Box::new(ty))
+        };
+        (name, ty, term)
+    }
+}
+
+/// Splits the declarations into the strongly connected components of the graph of references
+/// between them, so that the declarations of a component are mutually recursive.
+///
+/// The components are returned in dependency order, a component only referring to the ones
+/// before it, and otherwise in the order of the declarations. The indices of a component are
+/// sorted.
+fn components(decls: &[Declaration]) -> Vec<Vec<usize>> {
+    struct Tarjan {
+        edges: Vec<Vec<usize>>,
+        /// The order in which the declarations were visited
+        indices: Vec<Option<usize>>,
+        next: usize,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
 
-        Let {
-            var: name.clone(),
-            val_t: Box::new(Fix(Box::new(Abs {
-                var: name,
-                ty: ty.clone(),
+    impl Tarjan {
+        fn visit(&mut self, v: usize) -> usize {
+            let index = self.next;
+            self.next += 1;
+            self.indices[v] = Some(index);
+            // The lowest index of a declaration on the stack reachable from `v`
+            let mut low = index;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+            for w in self.edges[v].clone() {
+                match self.indices[w] {
+                    None => low = low.min(self.visit(w)),
+                    Some(index) if self.on_stack[w] => low = low.min(index),
+                    Some(_) => {}
+                }
+            }
+            if low == index {
+                let mut component = vec![];
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+            low
+        }
+    }
+
+    let names: HashMap<&str, usize> = decls
+        .iter()
+        .enumerate()
+        .map(|(i, Declaration(name, ..))| (name.as_str(), i))
+        .collect();
+    let edges = decls
+        .iter()
+        .map(|Declaration(_, _, term)| {
+            let mut edges: Vec<usize> = term
+                .free_vars()
+                .iter()
+                .filter_map(|x| names.get(x.as_str()).copied())
+                .collect();
+            edges.sort();
+            edges
+        })
+        .collect();
+    let mut tarjan = Tarjan {
+        edges,
+        indices: vec![None; decls.len()],
+        next: 0,
+        stack: vec![],
+        on_stack: vec![false; decls.len()],
+        components: vec![],
+    };
+    for v in 0..decls.len() {
+        if tarjan.indices[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+    tarjan.components
+}
+
+/// A data type declaration with a name and constructors, each with the types of its arguments.
+///
+/// ```stlc
+/// data Tree = Leaf | Node Tree Integer Tree
+/// ```
+///
+/// declares the type `Tree` standing for the variant type `<Leaf : Unit, Node : (Tree, Integer, Tree)>`,
+/// and the constructors `Leaf : Tree` and `Node : Tree -> Integer -> Tree -> Tree`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Data(pub String, pub Vec<(String, Vec<Type>)>);
+
+impl Data {
+    /// Returns the variant type the data type stands for.
+    ///
+    /// A constructor carries [`Type::Unit`] if it has no arguments, the type of its argument if
+    /// it has one, and a pair or tuple of the types of its arguments otherwise.
+    pub fn to_type(&self) -> Type {
+        let mut fields: Vec<_> = self
+            .1
+            .iter()
+            .map(|(constructor, args)| {
+                let ty = match args.as_slice() {
+                    [] => Type::Unit,
+                    [ty] => ty.clone(),
+                    [ty1, ty2] => Type::Prod(Box::new(ty1.clone()), Box::new(ty2.clone())),
+                    tys => Type::Tuple(tys.to_vec()),
+                };
+                (constructor.clone(), ty)
+            })
+            .collect();
+        fields.sort_by(|(l1, _), (l2, _)| l1.cmp(l2));
+        Type::Variant(fields)
+    }
+
+    /// Converts the declaration to a [`Term::Data`] binding the constructors with [`Term::Let`]s.
+    /// Takes in the inner body term.
+    ///
+    /// ```rust
+    /// # use stlc_project::module::Data;
+    /// # use stlc_project::term::util::*;
+    /// let nat = Data("Nat".to_string(), vec![
+    ///     ("Zero".to_string(), vec![]),
+    ///     ("Succ".to_string(), vec![named("Nat")]),
+    /// ]);
+    /// let t = nat.to_term(app("Succ", "Zero"));
+    /// assert_eq!(t.type_check(), Ok(named("Nat")));
+    /// assert_eq!(t.multistep(), tag("Succ", tag("Zero", Unit, named("Nat")), named("Nat")));
+    /// ```
+    pub fn to_term(self, body: Term) -> Term {
+        let ty = self.to_type();
+        let Data(name, constructors) = self;
+        let named = Type::Named(name.clone());
+
+        let mut term = body;
+        for (constructor, args) in constructors.into_iter().rev() {
+            let vars: Vec<String> = (0..args.len()).map(|i| format!("x{i}")).collect();
+            let mut payload: Vec<Term> = vars.iter().cloned().map(Var).collect();
+            let payload = match payload.len() {
+                0 => Unit,
+                1 => payload.remove(0),
+                2 => Pair(Box::new(payload.remove(0)), Box::new(payload.remove(0))),
+                _ => Tuple(payload),
+            };
+            let tag = Tag(constructor.clone(), Box::new(payload), named.clone());
+            let val_t = vars.into_iter().zip(args).rev().fold(tag, |body, (var, ty)| Abs {
+                var,
+                ty,
+                body: Box::new(body),
+            });
+            term = Let {
+                var: constructor,
+                val_t: Box::new(val_t),
                 body: Box::new(term),
-            }))),
-            body: Box::new(body),
+            };
+        }
+
+        Term::Data {
+            name,
+            ty,
+            body: Box::new(term),
         }
     }
 }
@@ -79,17 +275,16 @@ impl Import {
         let import_path = basepath.as_ref().join(&self.0);
         let import_base = import_path.parent().expect("import to have a parent");
         let code = read_to_string(&import_path)?;
-        let result = all_consuming(parse_module)
+        all_consuming(parse_module)
             .parse(&code)
-            .map_err(|err| err.to_string())?
+            .map_err(|err| Diagnostic::from_parse_error(import_path.display(), &code, &err))?
             .1
-            .to_term(&import_base, body);
-        result
+            .to_term(import_base, body)
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Module(pub Vec<Import>, pub Vec<Declaration>);
+pub struct Module(pub Vec<Import>, pub Vec<Declaration>, pub Vec<Data>);
 
 impl Module {
     /// Returns an empty module.
@@ -127,6 +322,27 @@ impl Module {
     /// in [body]
     /// ```
     ///
+    /// The declarations are ordered so that each of them comes after the ones it refers to, and
+    /// mutually recursive declarations are converted together to a single `let rec`, see [`letrec`].
+    ///
+    /// ```rust
+    /// # use nom::{combinator::all_consuming, Parser};
+    /// # use stlc_project::{module::parse::parse_module, term::util::*};
+    /// let code = "
+    /// odd : Integer -> Boolean
+    /// odd = fun n : Integer, if n == 0 then False else even (n - 1)
+    ///
+    /// even : Integer -> Boolean
+    /// even = fun n : Integer, if n == 0 then True else odd (n - 1)
+    /// ";
+    /// let module = all_consuming(parse_module).parse(code.trim()).unwrap().1;
+    /// let t = module.to_term(".", pair(app("even", 10), app("odd", 10))).unwrap();
+    /// assert_eq!(t.type_check(), Ok(prod(Boolean, Boolean)));
+    /// assert_eq!(t.multistep(), pair(True, False));
+    /// ```
+    ///
+    /// The data types of the module are declared around all of its declarations, see [`Data::to_term`].
+    ///
     /// The `basepath` should be set to the module's parent directory in order for imports to work properly.
     pub fn to_term(
         self,
@@ -135,9 +351,19 @@ impl Module {
     ) -> Result<Term, Box<dyn std::error::Error>> {
         let mut term = body;
 
-        // Process declarations from bottom to top
-        for decl in self.1.into_iter().rev() {
-            term = decl.to_term_fix(term);
+        // Process groups of mutually recursive declarations from bottom to top
+        let components = components(&self.1);
+        let mut decls: Vec<_> = self.1.into_iter().map(Some).collect();
+        for component in components.into_iter().rev() {
+            let group = component.into_iter().map(|i| {
+                let decl = decls[i].take().expect("components to be disjoint");
+                decl.generalize()
+            });
+            term = letrec(group, term);
+        }
+
+        for data in self.2.into_iter().rev() {
+            term = data.to_term(term);
         }
 
         // Process imports from bottom to top
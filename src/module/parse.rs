@@ -5,17 +5,19 @@ use nom::{
     character::complete::*,
     combinator::all_consuming,
     error::ErrorKind,
-    multi::{many1, separated_list1},
-    IResult, Parser,
+    combinator::opt,
+    multi::{many0, many1, separated_list1},
+    Parser,
 };
 
 use crate::{
-    parse::ws0,
-    r#type::parse::parse_type,
+    parse::{ws0, IResult},
+    span::record_declaration,
+    r#type::parse::{parse_type, parse_type_name, parse_type_primary},
     term::parse::{parse_term, parse_variable_name},
 };
 
-use super::{Declaration, Import, Module};
+use super::{Data, Declaration, Import, Module};
 
 /// ```stlc
 /// a : Integer
@@ -24,7 +26,7 @@ use super::{Declaration, Import, Module};
 ///
 /// Is converted to a `Declaration("a", Integer, 5)`
 pub fn parse_declaration(input: &str) -> IResult<&str, Declaration> {
-    (
+    let (rest, decl) = (
         parse_variable_name,
         ws0(char(':')),
         (parse_type),
@@ -41,7 +43,9 @@ pub fn parse_declaration(input: &str) -> IResult<&str, Declaration> {
                 Err(ErrorKind::Fail)
             }
         })
-        .parse(input)
+        .parse(input)?;
+    record_declaration(input, rest, &decl);
+    Ok((rest, decl))
 }
 
 /// ```stlc
@@ -70,6 +74,37 @@ pub fn parse_import(input: &str) -> IResult<&str, Import> {
         .parse(input)
 }
 
+/// ```stlc
+/// data Tree = Leaf | Node Tree Integer Tree
+/// ```
+///
+/// Is converted to a `Data("Tree", [("Leaf", []), ("Node", [Tree, Integer, Tree])])`
+///
+/// The constructors may also be written on separate lines, each starting with `|`.
+pub fn parse_data(input: &str) -> IResult<&str, Data> {
+    let constructor = (
+        parse_type_name,
+        many0((space1, parse_type_primary).map(|(_, ty)| ty)),
+    );
+    (
+        tag("data"),
+        space1,
+        parse_type_name,
+        ws0(char('=')),
+        opt((char('|'), multispace0)),
+        separated_list1(ws0(char('|')), constructor),
+    )
+        .map(|(_, _, name, _, _, constructors)| Data(name, constructors))
+        .parse(input)
+}
+
+/// A top level block of a module.
+enum Block {
+    Imports(Vec<Import>),
+    Declaration(Declaration),
+    Data(Data),
+}
+
 /// ```stlc
 /// a : Integer
 /// a = 5
@@ -85,27 +120,30 @@ pub fn parse_import(input: &str) -> IResult<&str, Import> {
 /// - `Declaration("a", Integer, 5)`
 /// - `Declaration("sum", List Integer -> Integer, [...])`
 ///
-/// A module does not have to have imports or declarations.
+/// A module does not have to have imports, data types or declarations.
 pub fn parse_module(input: &str) -> IResult<&str, Module> {
     // Split the code at each "empty line"
     let blocks = input.trim().split("\n\n");
     let mut imports = vec![];
     let mut decls = vec![];
+    let mut datas = vec![];
 
     for block in blocks {
-        // For each block, try to parse it as an import or a declaration
+        // For each block, try to parse it as imports, a declaration or a data type
         let (_, res) = all_consuming(
             many1((parse_import, multispace0).map(|(import, _)| import))
-                .map(Ok)
-                .or(parse_declaration.map(Err)),
+                .map(Block::Imports)
+                .or(parse_declaration.map(Block::Declaration))
+                .or(parse_data.map(Block::Data)),
         )
         .parse(block)?;
 
-        // Push the resulting import or declaration to the respective list
+        // Push the result to the respective list
         match res {
-            Ok(mut import) => imports.append(&mut import),
-            Err(decl) => decls.push(decl),
+            Block::Imports(mut import) => imports.append(&mut import),
+            Block::Declaration(decl) => decls.push(decl),
+            Block::Data(data) => datas.push(data),
         }
     }
-    Ok(("", Module(imports, decls)))
+    Ok(("", Module(imports, decls, datas)))
 }
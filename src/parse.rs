@@ -1,23 +1,76 @@
+use std::fmt;
+
 use nom::{
     character::complete::{multispace0, multispace1},
+    error::{ErrorKind, FromExternalError},
     sequence::delimited,
     Parser,
 };
 
-pub fn ws0<'a, F: 'a, O>(
-    inner: F,
-) -> impl Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>>
+/// The error of the parsers: where and why they gave up.
+///
+/// Most errors only record the combinator which failed, but problems which the parsers find on
+/// their own, such as non-exhaustive matches, come with a message explaining them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError<I> {
+    /// The remaining input when the parser gave up.
+    pub input: I,
+    pub kind: ErrorKind,
+    pub message: Option<String>,
+}
+
+impl<I> ParseError<I> {
+    /// Creates an error explained by `message` about the code starting at `input`.
+    pub fn with_message(input: I, message: impl ToString) -> Self {
+        ParseError {
+            input,
+            kind: ErrorKind::Fail,
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+impl<I> nom::error::ParseError<I> for ParseError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        ParseError {
+            input,
+            kind,
+            message: None,
+        }
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for ParseError<I> {
+    fn from_external_error(input: I, kind: ErrorKind, _: E) -> Self {
+        nom::error::ParseError::from_error_kind(input, kind)
+    }
+}
+
+impl<I: fmt::Display> fmt::Display for ParseError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{message} at: {}", self.input),
+            None => write!(f, "error {:?} at: {}", self.kind, self.input),
+        }
+    }
+}
+
+pub type IResult<I, O> = nom::IResult<I, O, ParseError<I>>;
+
+pub fn ws0<'a, F, O>(inner: F) -> impl Parser<&'a str, Output = O, Error = ParseError<&'a str>>
 where
-    F: Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>>,
+    F: 'a + Parser<&'a str, Output = O, Error = ParseError<&'a str>>,
 {
     delimited(multispace0, inner, multispace0)
 }
 
-pub fn ws1<'a, F: 'a, O>(
-    inner: F,
-) -> impl Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>>
+pub fn ws1<'a, F, O>(inner: F) -> impl Parser<&'a str, Output = O, Error = ParseError<&'a str>>
 where
-    F: Parser<&'a str, Output = O, Error = nom::error::Error<&'a str>>,
+    F: 'a + Parser<&'a str, Output = O, Error = ParseError<&'a str>>,
 {
     delimited(multispace1, inner, multispace1)
 }
@@ -0,0 +1,305 @@
+//! # Source spans
+//!
+//! The parsers record the byte span of every [`Term`], [`Type`] and [`Declaration`] they produce
+//! into a side table, so that errors about a term can be pointed back to the source code.
+//! Warnings which the parsers find on their own, such as unreachable arms of a match, are recorded
+//! into the table along with their span.
+//!
+//! Nodes are identified by the memory they own, which stays in place when a node is moved into
+//! its parent: the allocation of their first child, or the name of a variable. Equal nodes parsed
+//! from different places in the source, such as two occurrences of the same variable, thus have
+//! different spans, and a copy of a node has none. Nodes owning no memory, such as literals, are
+//! located within their parent.
+//!
+//! Recording is only active inside [`record_spans`], otherwise the parsers behave as usual.
+//!
+//! ```rust
+//! # use stlc_project::span::{record_spans, Span};
+//! # use stlc_project::term::{parse::parse_term, util::*};
+//! let (result, spans) = record_spans("x + (2 * x)", parse_term);
+//! let (_, t) = result.unwrap();
+//! assert_eq!(spans.term_span(&t), Some(Span { start: 0, end: 11 }));
+//! let Add(lhs, rhs) = &t else { unreachable!() };
+//! assert_eq!(spans.term_span(lhs), Some(Span { start: 0, end: 1 }));
+//! assert_eq!(spans.term_span(rhs), Some(Span { start: 5, end: 10 }));
+//! assert_eq!(spans.child_span(rhs, 0), Some(Span { start: 5, end: 6 }));
+//! let Mul(_, x) = &**rhs else { unreachable!() };
+//! assert_eq!(spans.term_span(x), Some(Span { start: 9, end: 10 }));
+//!
+//! // An equal term which was not parsed from the source has no span
+//! assert_eq!(spans.term_span(&mul(2, "x")), None);
+//! ```
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use crate::{module::Declaration, r#type::Type, term::Term};
+
+/// A range of bytes `start..end` in the source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    fn contains(&self, other: &Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+}
+
+/// An error along with the node of the evaluated or type checked term it arose in.
+///
+/// The node is borrowed from the term, so that it can be found in the [`SpanTable`] it was
+/// parsed with, unlike the copies of terms carried by the error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Located<'t, E> {
+    pub error: E,
+    pub node: &'t Term,
+}
+
+/// Identifies a node by the address of memory it owns, see the [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct NodeId(usize);
+
+impl NodeId {
+    fn of_name(name: &str) -> Option<Self> {
+        // An empty name owns no memory
+        (!name.is_empty()).then_some(NodeId(name.as_ptr() as usize))
+    }
+
+    fn of_term(t: &Term) -> Option<Self> {
+        match t {
+            Term::Var(x) => NodeId::of_name(x),
+            t => t.children().first().map(|child| NodeId(*child as *const Term as usize)),
+        }
+    }
+
+    fn of_type(ty: &Type) -> Option<Self> {
+        match ty {
+            Type::Var(a) | Type::Named(a) => NodeId::of_name(a),
+            ty => ty.children().first().map(|child| NodeId(*child as *const Type as usize)),
+        }
+    }
+}
+
+/// Fingerprints the kind and children of a node, so that a node allocated where a node dropped
+/// by the parsers used to be is not mistaken for it.
+fn shape<T>(node: &T, children: Vec<&T>, name: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    mem::discriminant(node).hash(&mut hasher);
+    for child in children {
+        (child as *const T).hash(&mut hasher);
+    }
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn term_shape(t: &Term) -> u64 {
+    let name = match t {
+        Term::Var(x) => Some(x.as_str()),
+        _ => None,
+    };
+    shape(t, t.children(), name)
+}
+
+fn type_shape(ty: &Type) -> u64 {
+    let name = match ty {
+        Type::Var(a) | Type::Named(a) => Some(a.as_str()),
+        _ => None,
+    };
+    shape(ty, ty.children(), name)
+}
+
+/// The span of a node, along with its [`shape`] when it was parsed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Entry {
+    span: Span,
+    shape: u64,
+}
+
+/// Records `span` for the node `id` of the given `shape`.
+///
+/// A node which is already recorded keeps its innermost span, e.g. without the parentheses.
+fn insert(entries: &mut HashMap<NodeId, Entry>, id: NodeId, span: Span, shape: u64) {
+    let entry = Entry { span, shape };
+    entries
+        .entry(id)
+        .and_modify(|old| {
+            if old.shape != shape || !span.contains(&old.span) {
+                *old = entry
+            }
+        })
+        .or_insert(entry);
+}
+
+fn lookup(entries: &HashMap<NodeId, Entry>, id: Option<NodeId>, shape: u64) -> Option<Span> {
+    entries
+        .get(&id?)
+        .filter(|entry| entry.shape == shape)
+        .map(|entry| entry.span)
+}
+
+/// The spans of the nodes produced while parsing a source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpanTable {
+    terms: HashMap<NodeId, Entry>,
+    /// Terms owning no memory, in the order they were parsed
+    leaves: Vec<(Span, Term)>,
+    types: HashMap<NodeId, Entry>,
+    /// Declarations are identified by their name, see [`SpanTable::declaration_span`].
+    declarations: HashMap<NodeId, (Span, String)>,
+    /// Warnings found by the parsers, such as unreachable arms of a match.
+    pub warnings: Vec<(Span, String)>,
+}
+
+impl SpanTable {
+    /// Returns the span of the term `t` if it was parsed from this source.
+    ///
+    /// Terms owning no memory, such as literals, are only found by [`SpanTable::child_span`].
+    pub fn term_span(&self, t: &Term) -> Option<Span> {
+        lookup(&self.terms, NodeId::of_term(t), term_shape(t))
+    }
+
+    /// Returns the span of the subterm `index` of `parent`, counting its subterms in the order
+    /// they are written, if it was parsed from this source.
+    ///
+    /// Unlike [`SpanTable::term_span`], this also finds the terms owning no memory, which are
+    /// located among the ones equal to them inside the span of `parent`.
+    pub fn child_span(&self, parent: &Term, index: usize) -> Option<Span> {
+        let children = parent.children();
+        let child = children.get(index)?;
+        if NodeId::of_term(child).is_some() {
+            return self.term_span(child);
+        }
+        let outer = self.term_span(parent)?;
+        // Leaves inside the other children belong to them
+        let inner: Vec<Span> = children.iter().filter_map(|t| self.term_span(t)).collect();
+        let mut candidates: Vec<Span> = self
+            .leaves
+            .iter()
+            .filter(|(span, t)| {
+                t == *child && outer.contains(span) && !inner.iter().any(|s| s.contains(span))
+            })
+            .map(|(span, _)| *span)
+            .collect();
+        // A parenthesized leaf is recorded both with and without its parentheses
+        let all = candidates.clone();
+        candidates.retain(|span| !all.iter().any(|other| other != span && span.contains(other)));
+        candidates.sort_by_key(|span| span.start);
+        candidates.dedup();
+        let nth = children[..index].iter().filter(|t| **t == *child).count();
+        candidates.get(nth).copied()
+    }
+
+    /// Returns the span of the type `ty` if it was parsed from this source.
+    ///
+    /// Types owning no memory, such as [`Type::Integer`], have no span.
+    pub fn type_span(&self, ty: &Type) -> Option<Span> {
+        lookup(&self.types, NodeId::of_type(ty), type_shape(ty))
+    }
+
+    /// Returns the span of the declaration whose name is `name`, if it was parsed from this
+    /// source.
+    ///
+    /// The name is found by its address rather than its text, so `name` must be the name of the
+    /// declaration itself, e.g. moved into the binding of a module's term, and not a copy of it.
+    pub fn declaration_span(&self, name: &str) -> Option<Span> {
+        self.declarations
+            .get(&NodeId::of_name(name)?)
+            .filter(|(_, other)| other == name)
+            .map(|(span, _)| *span)
+    }
+}
+
+struct Recorder {
+    /// Address of the first byte of the source
+    base: usize,
+    len: usize,
+    table: SpanTable,
+}
+
+impl Recorder {
+    /// Computes the span between the start of `input` and the start of `rest`,
+    /// if both are slices of the source.
+    fn span(&self, input: &str, rest: &str) -> Option<Span> {
+        let start = (input.as_ptr() as usize).checked_sub(self.base)?;
+        let end = (rest.as_ptr() as usize).checked_sub(self.base)?;
+        (start <= end && end <= self.len).then_some(Span { start, end })
+    }
+}
+
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+}
+
+/// Runs `parser` on `source`, recording the spans of all nodes parsed from it.
+///
+/// The spans are only valid as long as the nodes returned by `parser` are alive, as they are
+/// identified by the memory they own.
+pub fn record_spans<'a, T>(source: &'a str, parser: impl FnOnce(&'a str) -> T) -> (T, SpanTable) {
+    let recorder = Recorder {
+        base: source.as_ptr() as usize,
+        len: source.len(),
+        table: SpanTable::default(),
+    };
+    let previous = RECORDER.replace(Some(recorder));
+    let result = parser(source);
+    let recorder = RECORDER
+        .replace(previous)
+        .expect("recorder to be active until the end of record_spans");
+    (result, recorder.table)
+}
+
+/// Runs `push` with the span of the node parsed from `input` with `rest` remaining, if recording.
+fn record(input: &str, rest: &str, push: impl FnOnce(&mut SpanTable, Span)) {
+    RECORDER.with_borrow_mut(|recorder| {
+        if let Some(recorder) = recorder
+            && let Some(span) = recorder.span(input, rest)
+        {
+            push(&mut recorder.table, span);
+        }
+    })
+}
+
+/// Records the span of the term `t` parsed from `input` with `rest` remaining.
+pub(crate) fn record_term(input: &str, rest: &str, t: &Term) {
+    record(input, rest, |table, span| match NodeId::of_term(t) {
+        Some(id) => insert(&mut table.terms, id, span, term_shape(t)),
+        // The same code may be parsed several times when the parsers backtrack
+        None if !table.leaves.contains(&(span, t.clone())) => table.leaves.push((span, t.clone())),
+        None => {}
+    })
+}
+
+/// Records the span of the type `ty` parsed from `input` with `rest` remaining.
+pub(crate) fn record_type(input: &str, rest: &str, ty: &Type) {
+    record(input, rest, |table, span| {
+        if let Some(id) = NodeId::of_type(ty) {
+            insert(&mut table.types, id, span, type_shape(ty))
+        }
+    })
+}
+
+/// Records the span of the declaration `decl` parsed from `input` with `rest` remaining.
+pub(crate) fn record_declaration(input: &str, rest: &str, decl: &Declaration) {
+    record(input, rest, |table, span| {
+        if let Some(id) = NodeId::of_name(&decl.0) {
+            table.declarations.insert(id, (span, decl.0.clone()));
+        }
+    })
+}
+
+/// Records a warning about the code parsed from `input` with `rest` remaining.
+pub(crate) fn record_warning(input: &str, rest: &str, message: impl ToString) {
+    // The same code may be parsed several times when the parsers backtrack
+    record(input, rest, |table, span| {
+        let warning = (span, message.to_string());
+        if !table.warnings.contains(&warning) {
+            table.warnings.push(warning)
+        }
+    })
+}
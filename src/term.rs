@@ -1,7 +1,9 @@
 use super::r#type::Type;
+use crate::arith::Integer;
 
 mod display;
 pub mod parse;
+pub mod pattern;
 pub mod step;
 pub mod subst;
 pub mod util;
@@ -33,6 +35,18 @@ pub enum Term {
         val_t: Box<Term>,
         body: Box<Term>,
     },
+    /// Recursive bindings `let rec f : T = t and g : U = u in body`, which may refer to each other
+    /// and to themselves. Each occurrence of `f` unfolds to `let rec ... in t`, just like a
+    /// [`Term::Fix`].
+    LetRec {
+        bindings: Vec<(String, Type, Term)>,
+        body: Box<Term>,
+    },
+
+    // ==============================Unit stuff==============================
+
+    /// The unit value, the only value of type [`Type::Unit`]
+    Unit,
 
     // ==============================Boolean stuff==============================
 
@@ -46,17 +60,44 @@ pub enum Term {
         if_true: Box<Term>,
         if_false: Box<Term>,
     },
+    /// Conjunction, `t1 && t2`, which does not evaluate `t2` if `t1` is false
+    And(Box<Term>, Box<Term>),
+    /// Disjunction, `t1 || t2`, which does not evaluate `t2` if `t1` is true
+    Or(Box<Term>, Box<Term>),
+    /// Negation of a boolean, `not t`
+    Not(Box<Term>),
 
     // ==============================Integer stuff==============================
 
     /// An integer value
-    Int(i32),
+    Int(Integer),
     /// Addition of two terms
     Add(Box<Term>, Box<Term>),
     /// Subtraction of two terms
     Sub(Box<Term>, Box<Term>),
     /// Multiplication of two terms
     Mul(Box<Term>, Box<Term>),
+    /// Division of two terms, rounding towards zero
+    Div(Box<Term>, Box<Term>),
+    /// Remainder of the division of two terms, which has the sign of the dividend
+    Mod(Box<Term>, Box<Term>),
+    /// Negation of a term
+    Neg(Box<Term>),
+
+    // ==============================String stuff==============================
+
+    /// A string value
+    Str(String),
+    /// A character value
+    Char(char),
+    /// Concatenation of two strings, `t1 ^ t2`
+    Concat(Box<Term>, Box<Term>),
+    /// The number of characters of a string, `strlen t`
+    StrLen(Box<Term>),
+    /// The character of a string at an index counted from `0`, `charat t1 t2`
+    CharAt(Box<Term>, Box<Term>),
+    /// The decimal representation of an integer, `itos t`
+    IntToString(Box<Term>),
 
     // ============================Comparison stuff============================
 
@@ -64,7 +105,7 @@ pub enum Term {
     Eq(Box<Term>, Box<Term>),
     /// Non-equality
     Ne(Box<Term>, Box<Term>),
-    /// Less than
+    /// Less than, on integers, characters or strings
     Lt(Box<Term>, Box<Term>),
     /// Less than or equal
     Le(Box<Term>, Box<Term>),
@@ -82,6 +123,19 @@ pub enum Term {
     Fst(Box<Term>),
     /// The second term in the pair
     Snd(Box<Term>),
+    /// A tuple of three or more terms, `(t0, t1, t2)`
+    Tuple(Vec<Term>),
+    /// The component of a tuple or pair at an index counted from `0`, `t.0`
+    Proj(Box<Term>, usize),
+
+    // ===========================Record stuff============================
+
+    /// A record of labelled fields, `{x = t1, y = t2}`
+    Record(Vec<(String, Term)>),
+    /// The field of a record with a label, `t.x`
+    Field(Box<Term>, String),
+    /// A copy of a record with some of its fields replaced, `{t with x = t1}`
+    Update(Box<Term>, Vec<(String, Term)>),
 
     // ===========================List stuff============================
 
@@ -89,6 +143,8 @@ pub enum Term {
      Nil(Type),
      /// The recursive constructor for lists, holds the head and the tail in the following order: `Cons(head, tail)`.
      Cons(Box<Term>, Box<Term>),
+     /// Concatenation of two lists, `t1 ++ t2`
+     Append(Box<Term>, Box<Term>),
      /// Case analysis for lists
      ///
      /// ```text
@@ -125,15 +181,167 @@ pub enum Term {
         inr_t: Box<Term>,
     },
 
+    // ===========================Variant stuff===========================
+
+    /// Injection into a variant type with a label, `tag l t T`, holding the whole variant type
+    Tag(String, Box<Term>, Type),
+    /// Case analysis for variant types, with one arm per label
+    ///
+    /// ```text
+    /// match t of
+    /// | l1 var1 => t1
+    /// | l2 var2 => t2
+    /// ```
+    Match {
+        t: Box<Term>,
+        arms: Vec<(String, String, Term)>,
+    },
+
     // ============================Fix stuff============================
     /// Fixed point combinator.
     /// Calculates the fixed point of the inner function.
     Fix(Box<Term>),
- 
+
+    // ========================Polymorphism stuff========================
+
+    /// Type abstraction `Fun a, body`, quantifying `body` over the type variable `var`
+    TAbs {
+        var: String,
+        body: Box<Term>,
+    },
+    /// Type application `t [T]`, instantiating the type abstraction `t` at `T`
+    TApp(Box<Term>, Type),
+
+    // ==========================Data type stuff==========================
+
+    /// Declaration of the data type `name`, standing for the variant type `ty` in `body`.
+    ///
+    /// The data type is nominal, it only equals itself, and `ty` may refer to it recursively.
+    /// It is declared in modules with `data`, see [`crate::module::Data`].
+    Data {
+        name: String,
+        ty: Type,
+        body: Box<Term>,
+    },
+
+    // =======================Recursive type stuff=======================
+
+    /// Folding into the recursive type `μa. T`, `fold t T`, where `t` has the unrolled type
+    Fold(Box<Term>, Type),
+    /// Unfolding a term of a recursive type `μa. T` to its unrolled type, `unfold t`
+    Unfold(Box<Term>),
 
     
 }
 
+/// An error produced when evaluation of a term gets stuck.
+///
+/// The redex variants carry the stuck `redex` and the offending `value` inside it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// A free variable was reached during evaluation.
+    FreeVariable(String),
+    /// The term is already a value and cannot be stepped.
+    IsValue(Box<Term>),
+    /// Application or `fix` of a value that is not an abstraction.
+    NotAFunction { redex: Box<Term>, value: Box<Term> },
+    /// If-then-else with a condition that is not a boolean.
+    NotABoolean { redex: Box<Term>, value: Box<Term> },
+    /// Arithmetic or comparison with an operand that is not an integer.
+    NotAnInteger { redex: Box<Term>, value: Box<Term> },
+    /// Division or remainder by zero.
+    DivisionByZero { redex: Box<Term> },
+    /// Arithmetic whose result does not fit in an `i32` with [`Arithmetic::Checked`].
+    ///
+    /// [`Arithmetic::Checked`]: crate::arith::Arithmetic::Checked
+    Overflow { redex: Box<Term> },
+    /// A string operation or comparison with an operand that is not a string.
+    NotAString { redex: Box<Term>, value: Box<Term> },
+    /// Comparison of a character with a value that is not a character.
+    NotAChar { redex: Box<Term>, value: Box<Term> },
+    /// `charat` with an index outside of the string, holds the index as `value`.
+    IndexOutOfBounds { redex: Box<Term>, value: Box<Term> },
+    /// `fst` or `snd` of a value that is not a pair.
+    NotAPair { redex: Box<Term>, value: Box<Term> },
+    /// Projection of a value that is not a tuple or pair with a component at the index.
+    NotATuple { redex: Box<Term>, value: Box<Term> },
+    /// Field access or update of a value that is not a record with the fields.
+    NotARecord { redex: Box<Term>, value: Box<Term> },
+    /// `lcase` of a value that is not a list.
+    NotAList { redex: Box<Term>, value: Box<Term> },
+    /// `case` of a value that is not a sum.
+    NotASum { redex: Box<Term>, value: Box<Term> },
+    /// `match` of a value that is not a variant with an arm for its label.
+    NotAVariant { redex: Box<Term>, value: Box<Term> },
+    /// Equality of values containing an abstraction.
+    NotComparable { redex: Box<Term>, value: Box<Term> },
+    /// Type application of a value that is not a type abstraction.
+    NotATypeAbstraction { redex: Box<Term>, value: Box<Term> },
+    /// `unfold` of a value that is not a `fold`.
+    NotAFold { redex: Box<Term>, value: Box<Term> },
+}
+
+/// Builds a redex variant of [`EvalError`] from the stuck redex and the offending value.
+pub(crate) type StuckError = fn(Box<Term>, Box<Term>) -> EvalError;
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::FreeVariable(x) => write!(f, "cannot evaluate a free variable: {x}"),
+            EvalError::IsValue(t) => write!(f, "cannot step a value: {t}"),
+            EvalError::NotAFunction { redex, value } => {
+                write!(f, "expected an abstraction, found {value} in {redex}")
+            }
+            EvalError::NotABoolean { redex, value } => {
+                write!(f, "expected a boolean, found {value} in {redex}")
+            }
+            EvalError::NotAnInteger { redex, value } => {
+                write!(f, "expected an integer, found {value} in {redex}")
+            }
+            EvalError::DivisionByZero { redex } => write!(f, "division by zero in {redex}"),
+            EvalError::Overflow { redex } => write!(f, "integer overflow in {redex}"),
+            EvalError::NotAString { redex, value } => {
+                write!(f, "expected a string, found {value} in {redex}")
+            }
+            EvalError::NotAChar { redex, value } => {
+                write!(f, "expected a character, found {value} in {redex}")
+            }
+            EvalError::IndexOutOfBounds { redex, value } => {
+                write!(f, "index {value} is out of bounds in {redex}")
+            }
+            EvalError::NotAPair { redex, value } => {
+                write!(f, "expected a pair, found {value} in {redex}")
+            }
+            EvalError::NotATuple { redex, value } => {
+                write!(f, "expected a tuple with enough components, found {value} in {redex}")
+            }
+            EvalError::NotARecord { redex, value } => {
+                write!(f, "expected a record with the fields, found {value} in {redex}")
+            }
+            EvalError::NotAList { redex, value } => {
+                write!(f, "expected a list, found {value} in {redex}")
+            }
+            EvalError::NotASum { redex, value } => {
+                write!(f, "expected a sum, found {value} in {redex}")
+            }
+            EvalError::NotAVariant { redex, value } => {
+                write!(f, "expected a variant with an arm for its label, found {value} in {redex}")
+            }
+            EvalError::NotComparable { redex, value } => {
+                write!(f, "cannot compare abstraction {value} in {redex}")
+            }
+            EvalError::NotAFold { redex, value } => {
+                write!(f, "expected a fold, found {value} in {redex}")
+            }
+            EvalError::NotATypeAbstraction { redex, value } => {
+                write!(f, "expected a type abstraction, found {value} in {redex}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
 use Term::*;
 
 impl Term {
@@ -146,49 +354,176 @@ impl Term {
     ///
     /// **Abstraction is a value:**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert!(abs("x", Boolean, var("x")).is_value());
     /// ```
     ///
     /// **True and false are values:**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert!(True.is_value());
     /// assert!(False.is_value());
     /// ```
     ///
     /// **Variable is not a value:**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert!(!var("x").is_value());
     /// ```
     ///
     /// **Application is not a value:**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert!(!app(id22(), id2()).is_value());
     /// ```
     ///
     /// **Let expression is not a value:**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// let let_expr = letin("x", id2(), var("x"));
     /// assert!(!let_expr.is_value());
     /// ```
     pub fn is_value(&self) -> bool {
-        //todo!()
-
         match self {
-            Abs { .. } | True | False | Int(_) => true,
+            Abs { .. } | TAbs { .. } | Unit | True | False | Int(_) | Str(_) | Char(_) => true,
             Pair(t1, t2) => t1.is_value() && t2.is_value(),
+            Tuple(ts) => ts.iter().all(Term::is_value),
+            Record(fields) => fields.iter().all(|(_, t)| t.is_value()),
             Nil(_) => true,
             Cons(t1, t2) => t1.is_value() && t2.is_value(),
             Inl(t, _) => t.is_value(),
             Inr(t, _) => t.is_value(),
+            Tag(_, t, _) => t.is_value(),
+            Fold(t, _) => t.is_value(),
             
 
 
             _ => false,
         }
     }
+
+    /// Returns the number of nodes in the term, not counting types.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// assert_eq!(Int(5.into()).size(), 1);
+    /// assert_eq!(abs("x", Integer, add("x", 1)).size(), 4);
+    /// ```
+    pub fn size(&self) -> usize {
+        1 + match self {
+            Var(_) | Unit | True | False | Int(_) | Str(_) | Char(_) | Nil(_) => 0,
+            Abs { body, .. } | TAbs { body, .. } | Data { body, .. } => body.size(),
+            Let { val_t, body, .. } => val_t.size() + body.size(),
+            LetRec { bindings, body } => {
+                bindings.iter().map(|(_, _, t)| t.size()).sum::<usize>() + body.size()
+            }
+            Ite {
+                cond,
+                if_true,
+                if_false,
+            } => cond.size() + if_true.size() + if_false.size(),
+            App(t1, t2)
+            | Add(t1, t2)
+            | Sub(t1, t2)
+            | Mul(t1, t2)
+            | Div(t1, t2)
+            | Mod(t1, t2)
+            | Eq(t1, t2)
+            | Ne(t1, t2)
+            | Lt(t1, t2)
+            | Le(t1, t2)
+            | Gt(t1, t2)
+            | Ge(t1, t2)
+            | And(t1, t2)
+            | Or(t1, t2)
+            | Pair(t1, t2)
+            | Cons(t1, t2)
+            | Append(t1, t2)
+            | Concat(t1, t2)
+            | CharAt(t1, t2) => t1.size() + t2.size(),
+            Fst(t) | Snd(t) | Inl(t, _) | Inr(t, _) | Fix(t) | TApp(t, _) | StrLen(t)
+            | IntToString(t) | Neg(t) | Not(t) | Proj(t, _) | Tag(_, t, _)
+            | Fold(t, _) | Unfold(t) => t.size(),
+            Tuple(ts) => ts.iter().map(Term::size).sum(),
+            Record(fields) => fields.iter().map(|(_, t)| t.size()).sum(),
+            Field(t, _) => t.size(),
+            Update(t, fields) => t.size() + fields.iter().map(|(_, t)| t.size()).sum::<usize>(),
+            LCase {
+                t, nil_t, cons_t, ..
+            } => t.size() + nil_t.size() + cons_t.size(),
+            Case {
+                t, inl_t, inr_t, ..
+            } => t.size() + inl_t.size() + inr_t.size(),
+            Match { t, arms } => t.size() + arms.iter().map(|(_, _, t)| t.size()).sum::<usize>(),
+        }
+    }
+
+    /// Returns the terms directly inside `self`, from left to right.
+    pub(crate) fn children(&self) -> Vec<&Term> {
+        match self {
+            Var(_) | Unit | True | False | Int(_) | Str(_) | Char(_) | Nil(_) => vec![],
+            Abs { body, .. } | TAbs { body, .. } | Data { body, .. } => vec![body],
+            Let { val_t, body, .. } => vec![val_t, body],
+            LetRec { bindings, body } => bindings
+                .iter()
+                .map(|(_, _, t)| t)
+                .chain([&**body])
+                .collect(),
+            Ite {
+                cond,
+                if_true,
+                if_false,
+            } => vec![cond, if_true, if_false],
+            App(t1, t2)
+            | Add(t1, t2)
+            | Sub(t1, t2)
+            | Mul(t1, t2)
+            | Div(t1, t2)
+            | Mod(t1, t2)
+            | Eq(t1, t2)
+            | Ne(t1, t2)
+            | Lt(t1, t2)
+            | Le(t1, t2)
+            | Gt(t1, t2)
+            | Ge(t1, t2)
+            | And(t1, t2)
+            | Or(t1, t2)
+            | Pair(t1, t2)
+            | Cons(t1, t2)
+            | Append(t1, t2)
+            | Concat(t1, t2)
+            | CharAt(t1, t2) => vec![t1, t2],
+            Fst(t) | Snd(t) | Inl(t, _) | Inr(t, _) | Fix(t) | TApp(t, _) | StrLen(t)
+            | IntToString(t) | Neg(t) | Not(t) | Proj(t, _) | Tag(_, t, _) | Fold(t, _)
+            | Unfold(t) | Field(t, _) => vec![t],
+            Tuple(ts) => ts.iter().collect(),
+            Record(fields) => fields.iter().map(|(_, t)| t).collect(),
+            Update(t, fields) => [&**t].into_iter().chain(fields.iter().map(|(_, t)| t)).collect(),
+            LCase {
+                t, nil_t, cons_t, ..
+            } => vec![t, nil_t, cons_t],
+            Case {
+                t, inl_t, inr_t, ..
+            } => vec![t, inl_t, inr_t],
+            Match { t, arms } => [&**t].into_iter().chain(arms.iter().map(|(_, _, t)| t)).collect(),
+        }
+    }
+
+    /// Returns the type annotations directly inside `self`, from left to right.
+    pub(crate) fn types(&self) -> Vec<&Type> {
+        match self {
+            Abs { ty, .. }
+            | Nil(ty)
+            | Inl(_, ty)
+            | Inr(_, ty)
+            | Tag(_, _, ty)
+            | TApp(_, ty)
+            | Data { ty, .. }
+            | Fold(_, ty) => vec![ty],
+            LetRec { bindings, .. } => bindings.iter().map(|(_, ty, _)| ty).collect(),
+            _ => vec![],
+        }
+    }
 }
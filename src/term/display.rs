@@ -1,4 +1,5 @@
 use super::Term::{self, *};
+use crate::r#type::Type;
 
 /// Formats a [`Term::Cons`] recursively as `, 1, 2, 3` and [`Term::Nil`] as `]`.
 fn fmt_list(f: &mut std::fmt::Formatter<'_>, t: &Term) -> std::fmt::Result {
@@ -12,11 +13,81 @@ fn fmt_list(f: &mut std::fmt::Formatter<'_>, t: &Term) -> std::fmt::Result {
     }
 }
 
+/// Determines whether `t` is a chain of [`Term::Cons`] ending in [`Term::Nil`].
+fn is_list_literal(t: &Term) -> bool {
+    match t {
+        Nil(_) => true,
+        Cons(_, tail) => is_list_literal(tail),
+        _ => false,
+    }
+}
+
+/// Writes the character `c` as it appears in a literal delimited by `quote`, escaping it if needed.
+fn fmt_escaped(f: &mut std::fmt::Formatter<'_>, c: char, quote: char) -> std::fmt::Result {
+    match c {
+        '\n' => write!(f, "\\n"),
+        '\t' => write!(f, "\\t"),
+        '\r' => write!(f, "\\r"),
+        '\0' => write!(f, "\\0"),
+        '\\' => write!(f, "\\\\"),
+        c if c == quote => write!(f, "\\{c}"),
+        c => write!(f, "{c}"),
+    }
+}
+
+/// Determines whether `t` is the abstraction `fun _ : Unit, ...` of a sequencing, see [`crate::term::util::seq`].
+fn is_seq(t: &Term) -> bool {
+    matches!(t, Abs { var, ty: Type::Unit, .. } if var == "_")
+}
+
+/// Determines whether `t` can be projected from without parentheses, as in `t.0` or `t.x`.
+fn is_atomic(t: &Term) -> bool {
+    matches!(
+        t,
+        Var(_)
+            | Unit
+            | True
+            | False
+            | Str(_)
+            | Char(_)
+            | Pair(..)
+            | Tuple(_)
+            | Proj(..)
+            | Record(_)
+            | Field(..)
+            | Update(..)
+            | Tag(..)
+    )
+}
+
+/// Determines whether `t` can be the argument of `fold` or `unfold` without parentheses.
+fn is_argument(t: &Term) -> bool {
+    is_atomic(t) || matches!(t, Int(n) if !n.is_negative())
+}
+
+/// Formats the fields of a record as `x = 1, y = 2`.
+fn fmt_fields(f: &mut std::fmt::Formatter<'_>, fields: &[(String, Term)]) -> std::fmt::Result {
+    for (i, (l, t)) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{l} = {t}")?;
+    }
+    Ok(())
+}
+
 impl std::fmt::Display for Term {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Var(x) => write!(f, "{x}"),
             Abs { var, ty, body } => write!(f, "𝜆 {var} : {ty}. {body}"),
+            // Sequencing `t1; t2` is sugar for `(fun _ : Unit, t2) t1`
+            App(term1, term2) if is_seq(term1) => {
+                let Abs { body, .. } = &**term1 else {
+                    unreachable!()
+                };
+                write!(f, "{term2}; {body}")
+            }
             App(term1, term2) => match (&**term1, &**term2) {
                 (term1 @ (Var(_) | True | False), term2 @ (Var(_) | True | False)) => {
                     write!(f, "{term1} {term2}")
@@ -31,6 +102,17 @@ impl std::fmt::Display for Term {
                 _ => write!(f, "({term1}) ({term2})"),
             },
             Let { var, val_t, body } => write!(f, "let {var} = {val_t} in {body}"),
+            LetRec { bindings, body } => {
+                write!(f, "let rec ")?;
+                for (i, (var, ty, t)) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " and ")?;
+                    }
+                    write!(f, "{var} : {ty} = {t}")?;
+                }
+                write!(f, " in {body}")
+            }
+            Unit => write!(f, "unit"),
             True => write!(f, "True"),
             False => write!(f, "False"),
             Ite {
@@ -38,10 +120,34 @@ impl std::fmt::Display for Term {
                 if_true,
                 if_false,
             } => write!(f, "if {cond} then {if_true} else {if_false}"),
+            And(term1, term2) => write!(f, "{term1} && {term2}"),
+            Or(term1, term2) => write!(f, "{term1} || {term2}"),
+            Not(term) => write!(f, "not {term}"),
             Int(n) => write!(f, "{n}"),
+            Str(s) => {
+                write!(f, "\"")?;
+                s.chars().try_for_each(|c| fmt_escaped(f, c, '"'))?;
+                write!(f, "\"")
+            }
+            Char(c) => {
+                write!(f, "'")?;
+                fmt_escaped(f, *c, '\'')?;
+                write!(f, "'")
+            }
+            Concat(term1, term2) => write!(f, "{term1} ^ {term2}"),
+            StrLen(term) => write!(f, "strlen {term}"),
+            CharAt(term1, term2) => write!(f, "charat {term1} {term2}"),
+            IntToString(term) => write!(f, "itos {term}"),
             Add(term1, term2) => write!(f, "{term1} + {term2}"),
             Sub(term1, term2) => write!(f, "{term1} - {term2}"),
             Mul(term1, term2) => write!(f, "{term1} * {term2}"),
+            Div(term1, term2) => write!(f, "{term1} / {term2}"),
+            Mod(term1, term2) => write!(f, "{term1} % {term2}"),
+            Neg(term) => match &**term {
+                Int(i) if !i.is_negative() => write!(f, "-{i}"),
+                term @ Var(_) => write!(f, "-{term}"),
+                term => write!(f, "-({term})"),
+            },
             Eq(term1, term2) => write!(f, "{term1} == {term2}"),
             Ne(term1, term2) => write!(f, "{term1} != {term2}"),
             Lt(term1, term2) => write!(f, "{term1} < {term2}"),
@@ -49,13 +155,33 @@ impl std::fmt::Display for Term {
             Gt(term1, term2) => write!(f, "{term1} > {term2}"),
             Ge(term1, term2) => write!(f, "{term1} >= {term2}"),
             Pair(term1, term2) => write!(f, "({term1}, {term2})"),
+            Tuple(terms) => {
+                let terms: Vec<_> = terms.iter().map(Term::to_string).collect();
+                write!(f, "({})", terms.join(", "))
+            }
+            Proj(term, i) if is_atomic(term) => write!(f, "{term}.{i}"),
+            Proj(term, i) => write!(f, "({term}).{i}"),
+            Record(fields) => {
+                write!(f, "{{")?;
+                fmt_fields(f, fields)?;
+                write!(f, "}}")
+            }
+            Field(term, l) if is_atomic(term) => write!(f, "{term}.{l}"),
+            Field(term, l) => write!(f, "({term}).{l}"),
+            Update(term, fields) => {
+                write!(f, "{{{term} with ")?;
+                fmt_fields(f, fields)?;
+                write!(f, "}}")
+            }
             Fst(term) => write!(f, "fst {term}"),
             Snd(term) => write!(f, "snd {term}"),
             Nil(_) => write!(f, "[]"),
-            Cons(x, xs) => {
+            Cons(x, xs) if is_list_literal(xs) => {
                 write!(f, "[{x}")?;
                 fmt_list(f, xs)
             }
+            Cons(x, xs) => write!(f, "{x} :: {xs}"),
+            Append(xs, ys) => write!(f, "{xs} ++ {ys}"),
             LCase {
                 t,
                 nil_t,
@@ -78,7 +204,29 @@ impl std::fmt::Display for Term {
                 f,
                 "case {t} of | inl {inl_var} ⇒ {inl_t} | inr {inr_var} ⇒ {inr_t}"
             ),
+            Tag(l, term, _) => write!(f, "<{l} = {term}>"),
+            Match { t, arms } => {
+                write!(f, "match {t} of")?;
+                for (l, var, arm) in arms {
+                    if var == "_" {
+                        write!(f, " | {l} ⇒ {arm}")?;
+                    } else {
+                        write!(f, " | {l} {var} ⇒ {arm}")?;
+                    }
+                }
+                Ok(())
+            }
             Fix(term) => write!(f, "fix {term}"),
+            TAbs { var, body } => write!(f, "Λ {var}. {body}"),
+            Data { name, ty, body } => write!(f, "data {name} = {ty} in {body}"),
+            Fold(term, _) if is_argument(term) => write!(f, "fold {term}"),
+            Fold(term, _) => write!(f, "fold ({term})"),
+            Unfold(term) if is_argument(term) => write!(f, "unfold {term}"),
+            Unfold(term) => write!(f, "unfold ({term})"),
+            TApp(term, ty) => match &**term {
+                term @ (Var(_) | TApp(..)) => write!(f, "{term} [{ty}]"),
+                term => write!(f, "({term}) [{ty}]"),
+            },
         }
     }
 }
@@ -1,16 +1,49 @@
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric0, char, digit1, multispace0, multispace1},
-    combinator::{value, verify},
-    multi::fold_many0,
-    sequence::delimited,
-    IResult, Parser,
+    character::complete::{
+        alpha1, alphanumeric0, char, digit1, multispace0, multispace1, none_of,
+    },
+    combinator::{consumed, opt, value, verify},
+    error::ErrorKind,
+    multi::{many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, preceded},
+    Parser,
 };
 
+use super::pattern::{desugar_match, Desugared, Pattern};
+use super::subst::fresh_name;
+use super::util::{letrec, seq};
 use super::Term::{self, *};
 use crate::parse::*;
 use crate::r#type::{parse::*, Type};
+use crate::span::{record_term, record_warning};
+
+type BinOp = fn(Box<Term>, Box<Term>) -> Term;
+
+/// Parses `operand (op operand)*` associating to the left.
+///
+/// The span of every intermediate node is recorded, see [`crate::span`].
+fn parse_left_assoc<'a, O>(
+    input: &'a str,
+    mut operand: impl Parser<&'a str, Output = Term, Error = ParseError<&'a str>>,
+    mut op: impl Parser<&'a str, Output = O, Error = ParseError<&'a str>>,
+    combine: impl Fn(Term, O, Term) -> Term,
+) -> IResult<&'a str, Term> {
+    let (mut rest, mut lhs) = operand.parse(input)?;
+    loop {
+        let Ok((after_op, o)) = op.parse(rest) else {
+            break;
+        };
+        let Ok((after, rhs)) = operand.parse(after_op) else {
+            break;
+        };
+        lhs = combine(lhs, o, rhs);
+        record_term(input, after, &lhs);
+        rest = after;
+    }
+    Ok((rest, lhs))
+}
 
 pub fn parse_variable_name(input: &str) -> IResult<&str, String> {
     verify(
@@ -18,7 +51,9 @@ pub fn parse_variable_name(input: &str) -> IResult<&str, String> {
         |name: &str| {
             ![
                 "fun", "let", "in", "if", "then", "else", "True", "False", "Integer", "Boolean",
-                "fst", "snd", "List", "inl", "inr", "case", "lcase", "of", "nil", "cons", "fix",
+                "fst", "snd", "List", "inl", "inr", "case", "lcase", "of", "nil", "cons", "fix", "forall", "Fun",
+                "unit", "Unit", "String", "Char", "strlen", "charat", "itos", "not", "with",
+                "tag", "match", "fold", "unfold", "and",
             ]
             .contains(&name)
         },
@@ -30,23 +65,52 @@ fn parse_var(input: &str) -> IResult<&str, Term> {
     parse_variable_name.map(Var).parse(input)
 }
 
+/// Parses an optional type annotation `: T`, which is a [`Type::Hole`] if left out.
+fn parse_annotation(input: &str) -> IResult<&str, Type> {
+    opt((multispace0, char(':'), multispace0, parse_type))
+        .map(|annotation| annotation.map_or(Type::Hole, |(_, _, _, ty)| ty))
+        .parse(input)
+}
+
+/// Parses a type argument ` T`, which is a [`Type::Hole`] if left out.
+fn parse_type_argument(input: &str) -> IResult<&str, Type> {
+    opt((multispace1, parse_type_primary))
+        .map(|argument| argument.map_or(Type::Hole, |(_, ty)| ty))
+        .parse(input)
+}
+
 fn parse_abs(input: &str) -> IResult<&str, Term> {
     (
         tag("fun"),
         multispace1,
         parse_variable_name,
+        parse_annotation,
         multispace0,
-        char(':'),
+        char(','),
         multispace0,
-        parse_type,
+        parse_term,
+    )
+        .map(|(_0, _1, var, ty, _4, _5, _6, body)| Abs {
+            var,
+            ty,
+            body: body.into(),
+        })
+        .parse(input)
+}
+
+/// Parses a type abstraction `Fun a, t`.
+fn parse_tabs(input: &str) -> IResult<&str, Term> {
+    (
+        tag("Fun"),
+        multispace1,
+        parse_type_var_name,
         multispace0,
         char(','),
         multispace0,
         parse_term,
     )
-        .map(|(_0, _1, var, _3, _4, _5, ty, _6, _7, _8, body)| Abs {
+        .map(|(_0, _1, var, _3, _4, _5, body)| TAbs {
             var,
-            ty,
             body: body.into(),
         })
         .parse(input)
@@ -56,15 +120,37 @@ fn parse_paren(input: &str) -> IResult<&str, Term> {
     delimited(char('('), ws0(parse_term), char(')')).parse(input)
 }
 
-fn parse_app(input: &str) -> IResult<&str, Term> {
-    let (rest, t1) = parse_term_primary.parse(input)?;
+/// The argument of an application, a term or a type.
+enum Argument {
+    Term(Term),
+    Type(Type),
+}
 
-    fold_many0(
-        (multispace1, parse_term_primary),
-        move || t1.clone(),
-        |fun, (_, arg)| App(Box::new(fun), Box::new(arg)),
-    )
-    .parse(rest)
+/// Parses the argument of an application, which is a term or a type argument `[T]`.
+///
+/// Type arguments take precedence over list literals, so `f [a]` is a type application and a
+/// singleton list of a variable needs parentheses, `f ([a])`.
+fn parse_argument(input: &str) -> IResult<&str, Argument> {
+    alt((
+        (multispace0, delimited(char('['), ws0(parse_type), char(']')))
+            .map(|(_, ty)| Argument::Type(ty)),
+        (multispace1, parse_term_primary).map(|(_, arg)| Argument::Term(arg)),
+    ))
+    .parse(input)
+}
+
+/// Parses applications to terms and types, associating to the left.
+fn parse_app(input: &str) -> IResult<&str, Term> {
+    let (mut rest, mut fun) = parse_term_primary(input)?;
+    while let Ok((after, arg)) = parse_argument(rest) {
+        fun = match arg {
+            Argument::Term(arg) => App(Box::new(fun), Box::new(arg)),
+            Argument::Type(ty) => TApp(Box::new(fun), ty),
+        };
+        record_term(input, after, &fun);
+        rest = after;
+    }
+    Ok((rest, fun))
 }
 
 fn parse_int(input: &str) -> IResult<&str, Term> {
@@ -75,6 +161,65 @@ fn parse_bool(input: &str) -> IResult<&str, Term> {
     alt((value(True, tag("True")), value(False, tag("False")))).parse(input)
 }
 
+/// Parses an escape sequence `\n`, `\t`, `\r`, `\0`, `\\`, `\"` or `\'`.
+fn parse_escape(input: &str) -> IResult<&str, char> {
+    preceded(
+        char('\\'),
+        alt((
+            value('\n', char('n')),
+            value('\t', char('t')),
+            value('\r', char('r')),
+            value('\0', char('0')),
+            value('\\', char('\\')),
+            value('"', char('"')),
+            value('\'', char('\'')),
+        )),
+    )
+    .parse(input)
+}
+
+/// Parses a string literal `"..."`, which may contain escape sequences.
+fn parse_str(input: &str) -> IResult<&str, Term> {
+    delimited(char('"'), many0(alt((none_of("\\\""), parse_escape))), char('"'))
+        .map(|chars| Str(chars.into_iter().collect()))
+        .parse(input)
+}
+
+/// Parses a character literal `'c'`, which may be an escape sequence.
+fn parse_char(input: &str) -> IResult<&str, Term> {
+    delimited(char('\''), alt((none_of("\\'"), parse_escape)), char('\''))
+        .map(Char)
+        .parse(input)
+}
+
+/// Parses the string primitives `strlen t`, `charat t1 t2` and `itos t`.
+fn parse_str_primitive(input: &str) -> IResult<&str, Term> {
+    alt((
+        (
+            alt((
+                value(StrLen as fn(Box<Term>) -> Term, tag("strlen")),
+                value(IntToString as fn(Box<Term>) -> Term, tag("itos")),
+            )),
+            multispace1,
+            parse_term_primary,
+        )
+            .map(|(op, _, t)| op(t.into())),
+        (
+            tag("charat"),
+            multispace1,
+            parse_term_primary,
+            multispace1,
+            parse_term_primary,
+        )
+            .map(|(_, _, t1, _, t2)| CharAt(t1.into(), t2.into())),
+    ))
+    .parse(input)
+}
+
+fn parse_unit(input: &str) -> IResult<&str, Term> {
+    value(Unit, tag("unit")).parse(input)
+}
+
 fn parse_ite(input: &str) -> IResult<&str, Term> {
     (
         tag("if"),
@@ -93,13 +238,21 @@ fn parse_ite(input: &str) -> IResult<&str, Term> {
         .parse(input)
 }
 
+/// Parses a pair `(t1, t2)` or a tuple `(t1, t2, t3)` of three or more components.
 fn parse_pair(input: &str) -> IResult<&str, Term> {
     delimited(
         char('('),
-        (ws0(parse_term), char(','), ws0(parse_term)),
+        (ws0(parse_term), many1(preceded(char(','), ws0(parse_term)))),
         char(')'),
     )
-    .map(|(t1, _, t2)| Pair(t1.into(), t2.into()))
+    .map(|(t1, mut ts)| {
+        if ts.len() == 1 {
+            Pair(t1.into(), ts.remove(0).into())
+        } else {
+            ts.insert(0, t1);
+            Tuple(ts)
+        }
+    })
     .parse(input)
 }
 
@@ -117,8 +270,8 @@ fn parse_fst_snd(input: &str) -> IResult<&str, Term> {
 }
 
 fn parse_nil(input: &str) -> IResult<&str, Term> {
-    (tag("nil"), multispace1, parse_type_primary)
-        .map(|(_nil, _ws, ty)| Nil(ty.into()))
+    (tag("nil"), parse_type_argument)
+        .map(|(_nil, ty)| Nil(ty))
         .parse(input)
 }
 
@@ -186,10 +339,9 @@ fn parse_inl_inr(input: &str) -> IResult<&str, Term> {
         )),
         multispace1,
         parse_term_primary,
-        multispace1,
-        parse_type_primary,
+        parse_type_argument,
     )
-        .map(|(op, _, t, _, ty)| op(t.into(), ty))
+        .map(|(op, _, t, ty)| op(t.into(), ty))
         .parse(input)
 }
 
@@ -249,7 +401,173 @@ fn parse_case(input: &str) -> IResult<&str, Term> {
         .parse(input)
 }
 
+/// Parses an injection `tag l t T` into the variant type `T`.
+fn parse_tag(input: &str) -> IResult<&str, Term> {
+    (
+        tag("tag"),
+        multispace1,
+        parse_variable_name,
+        multispace1,
+        parse_term_primary,
+        parse_type_argument,
+    )
+        .map(|(_, _, l, _, t, ty)| Tag(l, t.into(), ty))
+        .parse(input)
+}
+
+/// Parses a case analysis `match t of | l1 x => t1 | l2 y => t2` of a variant.
+///
+/// An arm may bind no variable, `| l => t`, or several, `| l x y z => t`, which are bound to the
+/// components of the pair or tuple carried by the variant. Such arms bind the content to a
+/// variable which is fresh in the arm, see [`fresh_name`].
+fn parse_match(input: &str) -> IResult<&str, Term> {
+    let arm = (
+        tag("|"),
+        multispace0,
+        parse_variable_name,
+        many0(preceded(multispace1, parse_variable_name)),
+        multispace0,
+        tag("=>"),
+        multispace0,
+        parse_term,
+    )
+        .map(|(_, _, l, mut vars, _, _, _, t)| {
+            if vars.len() == 1 {
+                return (l, vars.remove(0), t);
+            }
+            let mut avoid = t.free_vars();
+            avoid.extend(vars.iter().cloned());
+            let content = fresh_name("v", &avoid);
+            let t = vars.into_iter().enumerate().rev().fold(t, |t, (i, var)| Let {
+                var,
+                val_t: Box::new(Proj(Box::new(Var(content.clone())), i)),
+                body: Box::new(t),
+            });
+            (l, content, t)
+        });
+    (
+        tag("match"),
+        multispace1,
+        parse_term,
+        multispace1,
+        tag("of"),
+        many1(preceded(multispace0, arm)),
+    )
+        .map(|(_, _, t, _, _, arms)| Match {
+            t: t.into(),
+            arms,
+        })
+        .parse(input)
+}
+
+/// Parses a pattern which needs no parentheses as the argument of a constructor.
+fn parse_pattern_primary(input: &str) -> IResult<&str, Pattern> {
+    alt((
+        parse_variable_name.map(Pattern::Var),
+        value(Pattern::Wildcard, char('_')),
+        value(Pattern::Bool(true), tag("True")),
+        value(Pattern::Bool(false), tag("False")),
+        value(Pattern::Unit, tag("unit")),
+        value(Pattern::Nil, tag("nil")),
+        (opt(char('-')), digit1).map_res(|(minus, digits): (Option<char>, &str)| {
+            format!("{}{digits}", if minus.is_some() { "-" } else { "" })
+                .parse()
+                .map(Pattern::Int)
+        }),
+        delimited(
+            char('('),
+            (ws0(parse_pattern), char(','), ws0(parse_pattern)),
+            char(')'),
+        )
+        .map(|(p1, _, p2)| Pattern::Pair(Box::new(p1), Box::new(p2))),
+        delimited(char('('), ws0(parse_pattern), char(')')),
+    ))
+    .parse(input)
+}
+
+/// Parses a pattern `_`, `x`, `unit`, `True`, `5`, `(p1, p2)`, `nil`, `cons p1 p2`, `inl p` or `inr p`.
+fn parse_pattern(input: &str) -> IResult<&str, Pattern> {
+    alt((
+        (
+            tag("cons"),
+            multispace1,
+            parse_pattern_primary,
+            multispace1,
+            parse_pattern_primary,
+        )
+            .map(|(_, _, p1, _, p2)| Pattern::Cons(Box::new(p1), Box::new(p2))),
+        (
+            alt((
+                value(Pattern::Inl as fn(Box<Pattern>) -> Pattern, tag("inl")),
+                value(Pattern::Inr as fn(Box<Pattern>) -> Pattern, tag("inr")),
+            )),
+            multispace1,
+            parse_pattern_primary,
+        )
+            .map(|(op, _, p)| op(Box::new(p))),
+        parse_pattern_primary,
+    ))
+    .parse(input)
+}
+
+/// Parses a match `match t with | p1 => t1 | p2 => t2` of nested patterns, desugared by
+/// [`desugar_match`].
+///
+/// Fails with the reason if the arms are not exhaustive, and records a warning for every arm which
+/// can never be taken.
+fn parse_pattern_match(input: &str) -> IResult<&str, Term> {
+    let arm = (
+        tag("|"),
+        multispace0,
+        consumed(parse_pattern),
+        ws0(tag("=>")),
+        parse_term,
+    )
+        .map(|(_, _, (source, p), _, t)| (source, (p, t)));
+    let (rest, (_, _, t, _, _, arms)) = (
+        tag("match"),
+        multispace1,
+        parse_term,
+        multispace1,
+        tag("with"),
+        many1(preceded(multispace0, arm)),
+    )
+        .parse(input)?;
+    let (sources, arms): (Vec<&str>, Vec<(Pattern, Term)>) = arms.into_iter().unzip();
+    match desugar_match(t, arms.clone()) {
+        Ok(Desugared { term, unreachable }) => {
+            for i in unreachable {
+                let source = sources[i];
+                let message = format!("pattern `{}` is unreachable", arms[i].0);
+                record_warning(source, &source[source.len()..], message);
+            }
+            Ok((rest, term))
+        }
+        Err(err) => Err(nom::Err::Failure(ParseError::with_message(input, err))),
+    }
+}
+
+/// Parses the recursive bindings `rec f : T = t1 and g : U = t2` of a `let rec`.
+fn parse_rec_bindings(input: &str) -> IResult<&str, Vec<(String, Type, Term)>> {
+    let binding = (parse_variable_name, parse_annotation, ws0(char('=')), parse_term)
+        .map(|(var, ty, _, t)| (var, ty, t));
+    preceded(
+        (tag("rec"), multispace1),
+        separated_list1((multispace1, tag("and"), multispace1), binding),
+    )
+    .parse(input)
+}
+
+/// Parses a `let x = t1 in t2`, or a `let rec f : T = t1 and g : U = t2 in t3` of bindings which
+/// may refer to each other, see [`crate::term::util::letrec`].
 fn parse_let(input: &str) -> IResult<&str, Term> {
+    let (rest, bindings) =
+        opt(preceded((tag("let"), multispace1), parse_rec_bindings)).parse(input)?;
+    if let Some(bindings) = bindings {
+        let (rest, _) = (multispace1, tag("in"), multispace1).parse(rest)?;
+        let (rest, body) = parse_term(rest)?;
+        return Ok((rest, letrec(bindings, body)));
+    }
     (
         tag("let"),
         multispace1,
@@ -277,76 +595,247 @@ fn parse_fix(input: &str) -> IResult<&str, Term> {
         .parse(input)
 }
 
+/// Parses a `fold t T` into the recursive type `T`, which must be given.
+fn parse_fold(input: &str) -> IResult<&str, Term> {
+    (
+        tag("fold"),
+        multispace1,
+        parse_term_primary,
+        multispace1,
+        parse_type_primary,
+    )
+        .map(|(_, _, t, _, ty)| Fold(Box::new(t), ty))
+        .parse(input)
+}
+
+fn parse_unfold(input: &str) -> IResult<&str, Term> {
+    (tag("unfold"), multispace1, parse_term_primary)
+        .map(|(_, _, t)| Unfold(Box::new(t)))
+        .parse(input)
+}
+
+/// Parses the fields `x = t1, y = t2` of a record or record update.
+fn parse_fields(input: &str) -> IResult<&str, Vec<(String, Term)>> {
+    separated_list1(
+        ws0(char(',')),
+        (parse_variable_name, ws0(char('=')), parse_term).map(|(l, _, t)| (l, t)),
+    )
+    .parse(input)
+}
+
+/// Parses a record `{x = t1, y = t2}` or a record update `{t with x = t1}`.
+fn parse_record(input: &str) -> IResult<&str, Term> {
+    let update = (parse_term, multispace1, tag("with"), multispace1, parse_fields)
+        .map(|(t, _, _, _, fields)| Update(Box::new(t), fields));
+    delimited(
+        (char('{'), multispace0),
+        alt((update, parse_fields.map(Record), value(Record(vec![]), multispace0))),
+        (multispace0, char('}')),
+    )
+    .parse(input)
+}
+
+/// Parses a list literal `[t1, t2, t3]`, optionally annotated with its type as in `[] : [Integer]`.
+fn parse_list(input: &str) -> IResult<&str, Term> {
+    let annotation = (ws0(char(':')), parse_type).map_res(|(_, ty)| match ty {
+        Type::List(ty) => Ok(*ty),
+        _ => Err(ErrorKind::Verify),
+    });
+    (
+        delimited(
+            (char('['), multispace0),
+            separated_list0(ws0(char(',')), parse_term),
+            (multispace0, char(']')),
+        ),
+        opt(annotation),
+    )
+        .map(|(ts, ty)| {
+            let nil = Nil(ty.unwrap_or(Type::Hole));
+            ts.into_iter().rev().fold(nil, |tail, head| Cons(Box::new(head), Box::new(tail)))
+        })
+        .parse(input)
+}
+
+/// A projection `.0` of a tuple or `.x` of a record.
+enum Projection {
+    Index(usize),
+    Field(String),
+}
+
+/// Parses a projection `.0` or `.x`.
+fn parse_projection(input: &str) -> IResult<&str, Projection> {
+    preceded(
+        char('.'),
+        alt((
+            digit1.map_res(str::parse).map(Projection::Index),
+            parse_variable_name.map(Projection::Field),
+        )),
+    )
+    .parse(input)
+}
+
+/// Parses a primary term, possibly followed by projections `t.0.x`.
 pub fn parse_term_primary(input: &str) -> IResult<&str, Term> {
-    alt((parse_paren, parse_var, parse_int, parse_bool, parse_pair)).parse(input)
+    let (mut rest, mut t) = alt((
+        parse_paren,
+        parse_var,
+        parse_int,
+        parse_bool,
+        parse_unit,
+        parse_str,
+        parse_char,
+        parse_pair,
+        parse_record,
+        parse_list,
+    )).parse(input)?;
+    record_term(input, rest, &t);
+    while let Ok((after, projection)) = parse_projection(rest) {
+        t = match projection {
+            Projection::Index(i) => Proj(Box::new(t), i),
+            Projection::Field(l) => Field(Box::new(t), l),
+        };
+        record_term(input, after, &t);
+        rest = after;
+    }
+    Ok((rest, t))
 }
 
+/// Parses a term, possibly a sequencing `t1; t2` which associates to the right.
 pub fn parse_term(input: &str) -> IResult<&str, Term> {
-    alt((
-        parse_comparison,
+    let (rest, t1) = parse_term_single(input)?;
+    let Ok((rest, (_, _, t2))) = (ws0(char(';')), multispace0, parse_term).parse(rest) else {
+        return Ok((rest, t1));
+    };
+    let t = seq(t1, t2);
+    record_term(input, rest, &t);
+    Ok((rest, t))
+}
+
+fn parse_term_single(input: &str) -> IResult<&str, Term> {
+    let (rest, t) = alt((
+        parse_or,
         parse_ite,
         parse_fst_snd,
+        parse_str_primitive,
         parse_nil,
         parse_cons,
         parse_lcase,
         parse_inl_inr,
         parse_case,
+        parse_tag,
+        parse_match,
+        parse_pattern_match,
         parse_let,
         parse_abs,
+        parse_tabs,
         parse_fix,
+        parse_fold,
+        parse_unfold,
     ))
-    .parse(input)
+    .parse(input)?;
+    record_term(input, rest, &t);
+    Ok((rest, t))
 }
 
-/// Parses a multiplication, which is lower in priority than applications, but higher than +/-
-fn parse_mul(input: &str) -> IResult<&str, Term> {
-    // Application parser will return a primary term if no applications are found.
-    let (rest, t1) = parse_app.parse(input)?;
+/// Parses a negation `-t` or a negative literal `-5`, which are lower in priority than applications,
+/// but higher than multiplication.
+///
+/// A `-` directly followed by digits is a negative literal, so `-5` is [`Int`]`(-5)` rather than a
+/// negation. Negative literals are not application arguments, `f -1` is a subtraction.
+fn parse_neg(input: &str) -> IResult<&str, Term> {
+    let (rest, t) = alt((
+        (char('-'), digit1)
+            .map_res(|(_, digits): (char, &str)| format!("-{digits}").parse().map(Int)),
+        (char('-'), multispace0, parse_neg).map(|(_, _, t)| Neg(Box::new(t))),
+        parse_app,
+    ))
+    .parse(input)?;
+    record_term(input, rest, &t);
+    Ok((rest, t))
+}
 
-    fold_many0(
-        (ws0(value(Mul, char('*'))), parse_app),
-        move || t1.clone(),
-        |lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)),
+/// Parses a multiplication, division or remainder, which are lower in priority than negation, but
+/// higher than +/-
+fn parse_mul(input: &str) -> IResult<&str, Term> {
+    // Negation parser will return an application or a primary term if no negation is found.
+    parse_left_assoc(
+        input,
+        parse_neg,
+        ws0(alt((
+            value(Mul as BinOp, char('*')),
+            value(Div as BinOp, char('/')),
+            value(Mod as BinOp, char('%')),
+        ))),
+        |lhs, op, rhs| op(Box::new(lhs), Box::new(rhs)),
     )
-    .parse(rest)
 }
 
-/// Parses for +/-, which is lower in priority than multiplication, but higher than comparison operators
+/// Parses for +/- and string concatenation ^, which are lower in priority than multiplication, but higher than comparison operators
 fn parse_add_sub(input: &str) -> IResult<&str, Term> {
-    let (rest, t1) = parse_mul.parse(input)?;
-
-    fold_many0(
-        (
-            ws0(alt((
-                value(Add as fn(Box<Term>, Box<Term>) -> Term, char('+')),
-                value(Sub as fn(Box<Term>, Box<Term>) -> Term, char('-')),
-            ))),
-            parse_mul,
-        ),
-        move || t1.clone(),
-        |lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)),
+    parse_left_assoc(
+        input,
+        parse_mul,
+        ws0(alt((
+            value(Add as BinOp, char('+')),
+            value(Sub as BinOp, char('-')),
+            value(Concat as BinOp, char('^')),
+        ))),
+        |lhs, op, rhs| op(Box::new(lhs), Box::new(rhs)),
     )
-    .parse(rest)
 }
 
-/// Parses for comparison operators, lowest in priority
-fn parse_comparison(input: &str) -> IResult<&str, Term> {
-    let (rest, t1) = parse_add_sub.parse(input)?;
+/// Parses a disjunction `t1 || t2`, lowest in priority
+fn parse_or(input: &str) -> IResult<&str, Term> {
+    parse_left_assoc(input, parse_and, ws0(tag("||")), |lhs, _, rhs| {
+        Or(Box::new(lhs), Box::new(rhs))
+    })
+}
 
-    fold_many0(
-        (
-            ws0(alt((
-                value(Eq as fn(Box<Term>, Box<Term>) -> Term, tag("==")),
-                value(Ne as fn(Box<Term>, Box<Term>) -> Term, tag("!=")),
-                value(Le as fn(Box<Term>, Box<Term>) -> Term, tag("<=")),
-                value(Ge as fn(Box<Term>, Box<Term>) -> Term, tag(">=")),
-                value(Lt as fn(Box<Term>, Box<Term>) -> Term, tag("<")),
-                value(Gt as fn(Box<Term>, Box<Term>) -> Term, tag(">")),
-            ))),
-            parse_add_sub,
-        ),
-        move || t1.clone(),
-        |lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)),
+/// Parses a conjunction `t1 && t2`, which is higher in priority than `||`
+fn parse_and(input: &str) -> IResult<&str, Term> {
+    parse_left_assoc(input, parse_not, ws0(tag("&&")), |lhs, _, rhs| {
+        And(Box::new(lhs), Box::new(rhs))
+    })
+}
+
+/// Parses a negation `not t`, which is lower in priority than comparisons, so `not x == y` is
+/// `not (x == y)`
+fn parse_not(input: &str) -> IResult<&str, Term> {
+    let (rest, t) = alt((
+        (tag("not"), multispace1, parse_not).map(|(_, _, t)| Not(Box::new(t))),
+        parse_comparison,
+    ))
+    .parse(input)?;
+    record_term(input, rest, &t);
+    Ok((rest, t))
+}
+
+/// Parses a cons `t1 :: t2` or an append `t1 ++ t2`, which associate to the right and are lower in
+/// priority than +/-, but higher than comparison operators
+fn parse_cons_append(input: &str) -> IResult<&str, Term> {
+    let (rest, t1) = parse_add_sub(input)?;
+    let op = ws0(alt((value(Cons as BinOp, tag("::")), value(Append as BinOp, tag("++")))));
+    let Ok((rest, (op, t2))) = (op, parse_cons_append).parse(rest) else {
+        return Ok((rest, t1));
+    };
+    let t = op(Box::new(t1), Box::new(t2));
+    record_term(input, rest, &t);
+    Ok((rest, t))
+}
+
+/// Parses for comparison operators, which are higher in priority than boolean connectives
+fn parse_comparison(input: &str) -> IResult<&str, Term> {
+    parse_left_assoc(
+        input,
+        parse_cons_append,
+        ws0(alt((
+            value(Eq as BinOp, tag("==")),
+            value(Ne as BinOp, tag("!=")),
+            value(Le as BinOp, tag("<=")),
+            value(Ge as BinOp, tag(">=")),
+            value(Lt as BinOp, tag("<")),
+            value(Gt as BinOp, tag(">")),
+        ))),
+        |lhs, op, rhs| op(Box::new(lhs), Box::new(rhs)),
     )
-    .parse(rest)
 }
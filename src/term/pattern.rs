@@ -0,0 +1,484 @@
+//! # Patterns
+//!
+//! Nested patterns of `match t with | p1 => t1 | p2 => t2`, over pairs, lists, sums, integers,
+//! booleans and unit. Matches are desugared by [`desugar_match`] into a decision tree of
+//! [`Case`], [`LCase`], [`Fst`]/[`Snd`] and [`Ite`], which tests every part of the scrutinee at
+//! most once.
+//!
+//! ```stlc
+//! match p with
+//! | (x, cons y ys) => x + y
+//! | (x, nil) => x
+//! ```
+
+use std::collections::HashSet;
+
+use super::Term::{self, *};
+use crate::arith::Integer;
+
+/// A pattern of a `match t with`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`, which matches anything
+    Wildcard,
+    /// A variable, which matches anything and is bound to it in the arm
+    Var(String),
+    Unit,
+    Bool(bool),
+    Int(Integer),
+    Pair(Box<Pattern>, Box<Pattern>),
+    Nil,
+    Cons(Box<Pattern>, Box<Pattern>),
+    Inl(Box<Pattern>),
+    Inr(Box<Pattern>),
+}
+
+/// A reason why a `match t with` cannot be desugared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternError {
+    /// No arm matches the values of the pattern.
+    NonExhaustive(Pattern),
+    /// Two patterns tested at the same place match values of different types.
+    Mismatch(Pattern, Pattern),
+    /// A variable is bound twice in the pattern of an arm.
+    DuplicateVariable(String),
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::NonExhaustive(p) => {
+                write!(f, "non-exhaustive match, pattern `{p}` is not covered")
+            }
+            PatternError::Mismatch(p, q) => {
+                write!(f, "patterns `{p}` and `{q}` have different types")
+            }
+            PatternError::DuplicateVariable(x) => {
+                write!(f, "variable {x} is bound twice in a pattern")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Arguments of constructors need parentheses unless they are atomic
+        let arg = |p: &Pattern| match p {
+            Pattern::Cons(..) | Pattern::Inl(_) | Pattern::Inr(_) => format!("({p})"),
+            Pattern::Int(n) if n.is_negative() => format!("({p})"),
+            p => p.to_string(),
+        };
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Var(x) => write!(f, "{x}"),
+            Pattern::Unit => write!(f, "unit"),
+            Pattern::Bool(true) => write!(f, "True"),
+            Pattern::Bool(false) => write!(f, "False"),
+            Pattern::Int(n) => write!(f, "{n}"),
+            Pattern::Pair(p1, p2) => write!(f, "({p1}, {p2})"),
+            Pattern::Nil => write!(f, "nil"),
+            Pattern::Cons(p1, p2) => write!(f, "cons {} {}", arg(p1), arg(p2)),
+            Pattern::Inl(p) => write!(f, "inl {}", arg(p)),
+            Pattern::Inr(p) => write!(f, "inr {}", arg(p)),
+        }
+    }
+}
+
+/// The constructor at the root of a pattern.
+#[derive(Debug, Clone, PartialEq)]
+enum Head {
+    Unit,
+    Bool(bool),
+    Int(Integer),
+    Pair,
+    Nil,
+    Cons,
+    Inl,
+    Inr,
+}
+
+impl Head {
+    fn arity(&self) -> usize {
+        match self {
+            Head::Unit | Head::Bool(_) | Head::Int(_) | Head::Nil => 0,
+            Head::Inl | Head::Inr => 1,
+            Head::Pair | Head::Cons => 2,
+        }
+    }
+
+    /// Returns all constructors of the type of `self`, or `None` if there are infinitely many.
+    fn signature(&self) -> Option<Vec<Head>> {
+        match self {
+            Head::Unit => Some(vec![Head::Unit]),
+            Head::Bool(_) => Some(vec![Head::Bool(true), Head::Bool(false)]),
+            Head::Int(_) => None,
+            Head::Pair => Some(vec![Head::Pair]),
+            Head::Nil | Head::Cons => Some(vec![Head::Nil, Head::Cons]),
+            Head::Inl | Head::Inr => Some(vec![Head::Inl, Head::Inr]),
+        }
+    }
+
+    fn into_pattern(self, mut args: Vec<Pattern>) -> Pattern {
+        let mut arg = || Box::new(args.remove(0));
+        match self {
+            Head::Unit => Pattern::Unit,
+            Head::Bool(b) => Pattern::Bool(b),
+            Head::Int(n) => Pattern::Int(n),
+            Head::Pair => Pattern::Pair(arg(), arg()),
+            Head::Nil => Pattern::Nil,
+            Head::Cons => Pattern::Cons(arg(), arg()),
+            Head::Inl => Pattern::Inl(arg()),
+            Head::Inr => Pattern::Inr(arg()),
+        }
+    }
+}
+
+impl Pattern {
+    /// Splits the pattern into its constructor and arguments, or returns `None` if it matches anything.
+    fn head(&self) -> Option<(Head, Vec<Pattern>)> {
+        match self {
+            Pattern::Wildcard | Pattern::Var(_) => None,
+            Pattern::Unit => Some((Head::Unit, vec![])),
+            Pattern::Bool(b) => Some((Head::Bool(*b), vec![])),
+            Pattern::Int(n) => Some((Head::Int(n.clone()), vec![])),
+            Pattern::Pair(p1, p2) => Some((Head::Pair, vec![(**p1).clone(), (**p2).clone()])),
+            Pattern::Nil => Some((Head::Nil, vec![])),
+            Pattern::Cons(p1, p2) => Some((Head::Cons, vec![(**p1).clone(), (**p2).clone()])),
+            Pattern::Inl(p) => Some((Head::Inl, vec![(**p).clone()])),
+            Pattern::Inr(p) => Some((Head::Inr, vec![(**p).clone()])),
+        }
+    }
+
+    /// Returns the variables bound by the pattern, in the order they appear.
+    pub fn vars(&self) -> Vec<&str> {
+        match self {
+            Pattern::Var(x) => vec![x],
+            Pattern::Pair(p1, p2) | Pattern::Cons(p1, p2) => {
+                let mut vars = p1.vars();
+                vars.extend(p2.vars());
+                vars
+            }
+            Pattern::Inl(p) | Pattern::Inr(p) => p.vars(),
+            _ => vec![],
+        }
+    }
+}
+
+/// A match that was desugared by [`desugar_match`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Desugared {
+    pub term: Term,
+    /// The indices of the arms which can never be taken, as the arms before them match all their values.
+    pub unreachable: Vec<usize>,
+}
+
+/// Desugars `match t with | p1 => t1 | p2 => t2 | ...` into a decision tree.
+///
+/// The arms are tried from top to bottom. The scrutinee and its parts are bound to variables
+/// `_0`, `_1`, ..., which cannot be written in the source, and the variables of the taken arm
+/// are bound to them with [`Let`]s.
+///
+/// # Errors
+///
+/// Returns a [`PatternError`] if the arms do not cover all values, naming a pattern of the values
+/// which are not covered, or if the arms are not well formed.
+///
+/// # Examples
+///
+/// ```rust
+/// # use stlc_project::term::pattern::{desugar_match, Pattern, PatternError};
+/// # use stlc_project::term::util::*;
+/// let both = |p1, p2| Pattern::Pair(Box::new(p1), Box::new(p2));
+/// let arms = vec![
+///     (both(Pattern::Bool(true), Pattern::Var("x".to_string())), var("x")),
+///     (both(Pattern::Wildcard, Pattern::Int(0.into())), Int(1.into())),
+///     (both(Pattern::Bool(false), Pattern::Wildcard), Int(2.into())),
+///     (both(Pattern::Bool(false), Pattern::Int(1.into())), Int(3.into())),
+/// ];
+/// let desugared = desugar_match(pair(False, 5), arms.clone()).unwrap();
+/// assert_eq!(desugared.term.type_check(), Ok(Integer));
+/// assert_eq!(desugared.term.multistep(), Int(2.into()));
+/// assert_eq!(desugared.unreachable, [3]);
+///
+/// assert_eq!(
+///     desugar_match(pair(False, 5), arms[..2].to_vec()),
+///     Err(PatternError::NonExhaustive(both(Pattern::Bool(false), Pattern::Int(1.into()))))
+/// );
+/// ```
+pub fn desugar_match(t: Term, arms: Vec<(Pattern, Term)>) -> Result<Desugared, PatternError> {
+    for (p, _) in &arms {
+        let vars = p.vars();
+        if let Some((i, _)) = vars
+            .iter()
+            .enumerate()
+            .find(|(i, x)| vars[..*i].contains(x))
+        {
+            return Err(PatternError::DuplicateVariable(vars[i].to_string()));
+        }
+    }
+
+    let mut compiler = Compiler {
+        arms: &arms,
+        reached: vec![false; arms.len()],
+        next: 0,
+    };
+    let scrutinee = compiler.fresh();
+    let rows = arms
+        .iter()
+        .enumerate()
+        .map(|(arm, (p, _))| Row {
+            pats: vec![p.clone()],
+            bindings: vec![],
+            arm,
+        })
+        .collect();
+    let tree = compiler
+        .compile(std::slice::from_ref(&scrutinee), rows)
+        .map_err(|err| match err {
+            Missing::Witness(mut witness) => PatternError::NonExhaustive(witness.remove(0)),
+            Missing::Mismatch(p, q) => PatternError::Mismatch(p, q),
+        })?;
+    let unreachable = (0..arms.len())
+        .filter(|arm| !compiler.reached[*arm])
+        .collect();
+    Ok(Desugared {
+        term: Let {
+            var: scrutinee,
+            val_t: Box::new(t),
+            body: Box::new(tree),
+        },
+        unreachable,
+    })
+}
+
+/// A row of the pattern matrix, matching the occurrences with `pats`.
+#[derive(Debug, Clone)]
+struct Row {
+    pats: Vec<Pattern>,
+    /// The variables of the arm already bound to occurrences
+    bindings: Vec<(String, String)>,
+    arm: usize,
+}
+
+/// A failure to compile a pattern matrix.
+enum Missing {
+    /// The values matching the patterns, one for each occurrence, are not covered.
+    Witness(Vec<Pattern>),
+    Mismatch(Pattern, Pattern),
+}
+
+struct Compiler<'a> {
+    arms: &'a [(Pattern, Term)],
+    /// Whether each arm is taken at some leaf of the decision tree
+    reached: Vec<bool>,
+    next: usize,
+}
+
+impl Compiler<'_> {
+    fn fresh(&mut self) -> String {
+        let name = format!("_{}", self.next);
+        self.next += 1;
+        name
+    }
+
+    /// Compiles the rows matching the variables `occs` into a decision tree.
+    fn compile(&mut self, occs: &[String], rows: Vec<Row>) -> Result<Term, Missing> {
+        let Some(first) = rows.first() else {
+            return Err(Missing::Witness(vec![Pattern::Wildcard; occs.len()]));
+        };
+        let Some(col) = first.pats.iter().position(|p| p.head().is_some()) else {
+            // The first row matches anything, so its arm is taken
+            let row = rows.into_iter().next().expect("rows to be non-empty");
+            self.reached[row.arm] = true;
+            let mut bindings = row.bindings;
+            for (p, occ) in row.pats.iter().zip(occs) {
+                if let Pattern::Var(x) = p {
+                    bindings.push((x.clone(), occ.clone()));
+                }
+            }
+            let body = self.arms[row.arm].1.clone();
+            return Ok(bindings.into_iter().rev().fold(body, |body, (x, occ)| Let {
+                var: x,
+                val_t: Box::new(Var(occ)),
+                body: Box::new(body),
+            }));
+        };
+
+        let mut heads: Vec<(Head, &Pattern)> = vec![];
+        for row in &rows {
+            if let Some((head, _)) = row.pats[col].head()
+                && !heads.iter().any(|(other, _)| *other == head)
+            {
+                heads.push((head, &row.pats[col]));
+            }
+        }
+        let (first_head, first_pattern) = heads[0].clone();
+        let signature = first_head.signature();
+        for (head, p) in &heads {
+            let same_type = match &signature {
+                Some(signature) => signature.contains(head),
+                None => matches!(head, Head::Int(_)),
+            };
+            if !same_type {
+                return Err(Missing::Mismatch(first_pattern.clone(), (*p).clone()));
+            }
+        }
+        let heads: Vec<Head> = heads.into_iter().map(|(head, _)| head).collect();
+
+        let occ = Var(occs[col].clone());
+        match first_head {
+            Head::Unit => self.specialize(occs, &rows, col, Head::Unit, vec![]),
+            Head::Bool(_) => Ok(Ite {
+                cond: Box::new(occ),
+                if_true: Box::new(self.specialize(occs, &rows, col, Head::Bool(true), vec![])?),
+                if_false: Box::new(self.specialize(occs, &rows, col, Head::Bool(false), vec![])?),
+            }),
+            Head::Pair => {
+                let (o1, o2) = (self.fresh(), self.fresh());
+                let body =
+                    self.specialize(occs, &rows, col, Head::Pair, vec![o1.clone(), o2.clone()])?;
+                Ok(Let {
+                    var: o1,
+                    val_t: Box::new(Fst(Box::new(occ.clone()))),
+                    body: Box::new(Let {
+                        var: o2,
+                        val_t: Box::new(Snd(Box::new(occ))),
+                        body: Box::new(body),
+                    }),
+                })
+            }
+            Head::Nil | Head::Cons => {
+                let nil_t = self.specialize(occs, &rows, col, Head::Nil, vec![])?;
+                let (head_var, tail_var) = (self.fresh(), self.fresh());
+                let cons_t = self.specialize(
+                    occs,
+                    &rows,
+                    col,
+                    Head::Cons,
+                    vec![head_var.clone(), tail_var.clone()],
+                )?;
+                Ok(LCase {
+                    t: Box::new(occ),
+                    nil_t: Box::new(nil_t),
+                    head_var,
+                    tail_var,
+                    cons_t: Box::new(cons_t),
+                })
+            }
+            Head::Inl | Head::Inr => {
+                let inl_var = self.fresh();
+                let inl_t = self.specialize(occs, &rows, col, Head::Inl, vec![inl_var.clone()])?;
+                let inr_var = self.fresh();
+                let inr_t = self.specialize(occs, &rows, col, Head::Inr, vec![inr_var.clone()])?;
+                Ok(Case {
+                    t: Box::new(occ),
+                    inl_var,
+                    inl_t: Box::new(inl_t),
+                    inr_var,
+                    inr_t: Box::new(inr_t),
+                })
+            }
+            Head::Int(_) => {
+                // Test the literals in order, falling back to the rows matching any integer
+                let mut tree = self.default(occs, &rows, col, &heads)?;
+                for head in heads.iter().rev() {
+                    let Head::Int(n) = head else {
+                        unreachable!("integer patterns to only have integer heads")
+                    };
+                    tree = Ite {
+                        cond: Box::new(Eq(Box::new(occ.clone()), Box::new(Int(n.clone())))),
+                        if_true: Box::new(self.specialize(occs, &rows, col, head.clone(), vec![])?),
+                        if_false: Box::new(tree),
+                    };
+                }
+                Ok(tree)
+            }
+        }
+    }
+
+    /// Compiles the rows which match `head` at `col`, with the arguments of the constructor bound
+    /// to the variables `args`.
+    fn specialize(
+        &mut self,
+        occs: &[String],
+        rows: &[Row],
+        col: usize,
+        head: Head,
+        args: Vec<String>,
+    ) -> Result<Term, Missing> {
+        let rows = rows
+            .iter()
+            .filter_map(|row| {
+                let mut row = row.clone();
+                let p = row.pats.remove(col);
+                let sub = match p.head() {
+                    Some((other, sub)) if other == head => sub,
+                    Some(_) => return None,
+                    None => {
+                        if let Pattern::Var(x) = p {
+                            row.bindings.push((x, occs[col].clone()));
+                        }
+                        vec![Pattern::Wildcard; head.arity()]
+                    }
+                };
+                row.pats.splice(col..col, sub);
+                Some(row)
+            })
+            .collect();
+        let mut occs = occs.to_vec();
+        occs.splice(col..=col, args.iter().cloned());
+        self.compile(&occs, rows).map_err(|err| match err {
+            Missing::Witness(mut witness) => {
+                let sub: Vec<Pattern> = witness.drain(col..col + args.len()).collect();
+                witness.insert(col, head.into_pattern(sub));
+                Missing::Witness(witness)
+            }
+            err => err,
+        })
+    }
+
+    /// Compiles the rows which match anything at `col`, for the values not matching any of the
+    /// integer literals `heads`.
+    fn default(
+        &mut self,
+        occs: &[String],
+        rows: &[Row],
+        col: usize,
+        heads: &[Head],
+    ) -> Result<Term, Missing> {
+        let rows = rows
+            .iter()
+            .filter_map(|row| {
+                let mut row = row.clone();
+                match row.pats.remove(col) {
+                    Pattern::Wildcard => Some(row),
+                    Pattern::Var(x) => {
+                        row.bindings.push((x, occs[col].clone()));
+                        Some(row)
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        let mut rest = occs.to_vec();
+        rest.remove(col);
+        self.compile(&rest, rows).map_err(|err| match err {
+            Missing::Witness(mut witness) => {
+                let literals: HashSet<Integer> = heads
+                    .iter()
+                    .filter_map(|head| match head {
+                        Head::Int(n) => Some(n.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                let n = (0..)
+                    .map(Integer::from)
+                    .find(|n| !literals.contains(n))
+                    .expect("some integer not to be a literal");
+                witness.insert(col, Pattern::Int(n));
+                Missing::Witness(witness)
+            }
+            err => err,
+        })
+    }
+}
@@ -1,56 +1,488 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::EvalError::{self, *};
 use super::Term::{self, *};
+use super::StuckError;
+use crate::arith::{checked, Arithmetic, Integer};
+use crate::r#type::Type;
+
+type StepResult = Result<Term, EvalError>;
+
+/// A budget for [`Term::try_multistep_fuel`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fuel {
+    /// The maximum number of steps taken.
+    pub steps: usize,
+    /// The maximum size of the term, see [`Term::size`], between steps.
+    pub max_size: Option<usize>,
+}
+
+impl Fuel {
+    /// Returns a budget of `steps` steps with no limit on the size of the term.
+    pub fn new(steps: usize) -> Self {
+        Fuel {
+            steps,
+            max_size: None,
+        }
+    }
+
+    /// Limits the size of the term to `max_size`.
+    pub fn with_max_size(self, max_size: usize) -> Self {
+        Fuel {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+}
+
+/// The result of a successful [`Term::try_multistep_fuel`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The term was evaluated to a value.
+    Value(Term),
+    /// The budget ran out, holds the partially reduced term.
+    OutOfFuel(Term),
+}
 
 fn step_op1(
     ctor: impl FnOnce(Box<Term>) -> Term,
-    eval: impl FnOnce(Box<Term>) -> Term,
+    eval: impl FnOnce(Box<Term>) -> StepResult,
     t: Box<Term>,
-) -> Term {
+) -> StepResult {
     if t.is_value() {
         eval(t)
     } else {
-        ctor(Box::new(t.step()))
+        Ok(ctor(Box::new(t.try_step()?)))
     }
 }
 
 fn step_op2(
     ctor: impl FnOnce(Box<Term>, Box<Term>) -> Term,
-    eval: impl FnOnce(Box<Term>, Box<Term>) -> Term,
+    eval: impl FnOnce(Box<Term>, Box<Term>) -> StepResult,
     t1: Box<Term>,
     t2: Box<Term>,
-) -> Term {
+) -> StepResult {
     match (t1.is_value(), t2.is_value()) {
-        (false, _) => ctor(Box::new(t1.step()), t2).into(),
-        (true, false) => ctor(t1, Box::new(t2.step())).into(),
+        (false, _) => Ok(ctor(Box::new(t1.try_step()?), t2)),
+        (true, false) => Ok(ctor(t1, Box::new(t2.try_step()?))),
         (true, true) => eval(t1, t2),
     }
 }
 
-fn eval_app(t1: Box<Term>, t2: Box<Term>) -> Term {
-    if let Abs { var, ty: _, body } = *t1 {
-        body.subst(&var, *t2)
+fn boolean(b: bool) -> Term {
+    if b {
+        True
     } else {
-        panic!("attempted to apply non abstraction to a value");
+        False
     }
 }
 
-fn eval_let(val_t: Box<Term>, var: impl AsRef<str>, body: Box<Term>) -> Term {
-    body.subst(var.as_ref(), *val_t)
+/// Evaluates a checked arithmetic operator `ctor(t1, t2)` on two integer values.
+///
+/// If either of the operands is not an integer, the redex is rebuilt with `ctor` for the error.
+/// If the result overflows with the current [`Arithmetic`](crate::arith::Arithmetic),
+/// [`Overflow`] is returned.
+fn eval_arith(
+    ctor: fn(Box<Term>, Box<Term>) -> Term,
+    op: fn(&Integer, &Integer) -> Integer,
+    t1: Term,
+    t2: Term,
+) -> StepResult {
+    match (t1, t2) {
+        (Int(i1), Int(i2)) => checked(op, &i1, &i2).map(Int).ok_or_else(|| Overflow {
+            redex: Box::new(ctor(Box::new(Int(i1)), Box::new(Int(i2)))),
+        }),
+        (t1, t2) => {
+            let value = if let Int(_) = t1 { t2.clone() } else { t1.clone() };
+            Err(NotAnInteger {
+                redex: Box::new(ctor(Box::new(t1), Box::new(t2))),
+                value: Box::new(value),
+            })
+        }
+    }
 }
 
-fn eval_ite(cond: Box<Term>, if_true: Box<Term>, if_false: Box<Term>) -> Term {
-    match *cond {
-        True => *if_true,
-        False => *if_false,
-        _ => panic!("attempted to if-then-else with non boolean condition"),
+/// Evaluates a division or remainder `ctor(t1, t2)` of two integer values.
+fn eval_div(
+    ctor: fn(Box<Term>, Box<Term>) -> Term,
+    op: fn(&Integer, &Integer) -> Integer,
+    t1: Term,
+    t2: Term,
+) -> StepResult {
+    if let (Int(_), Int(i2)) = (&t1, &t2)
+        && i2.is_zero()
+    {
+        return Err(DivisionByZero {
+            redex: Box::new(ctor(Box::new(t1), Box::new(t2))),
+        });
+    }
+    eval_arith(ctor, op, t1, t2)
+}
+
+/// Evaluates an ordering operator on two integers, characters or strings.
+///
+/// If the operands are not both integers, characters or strings, the redex is rebuilt with
+/// `ctor` for the error.
+fn eval_ord(
+    ctor: fn(Box<Term>, Box<Term>) -> Term,
+    op: impl FnOnce(Ordering) -> bool,
+    t1: Term,
+    t2: Term,
+) -> StepResult {
+    let ordering = match (&t1, &t2) {
+        (Int(i1), Int(i2)) => i1.cmp(i2),
+        (Char(c1), Char(c2)) => c1.cmp(c2),
+        (Str(s1), Str(s2)) => s1.cmp(s2),
+        _ => {
+            // The first operand determines the expected type of the second one
+            let (err, value): (StuckError, _) = match &t1 {
+                Int(_) => (not_an_integer, t2.clone()),
+                Char(_) => (not_a_char, t2.clone()),
+                Str(_) => (not_a_string, t2.clone()),
+                _ => (not_an_integer, t1.clone()),
+            };
+            return Err(err(Box::new(ctor(Box::new(t1), Box::new(t2))), Box::new(value)));
+        }
+    };
+    Ok(boolean(op(ordering)))
+}
+
+fn not_an_integer(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    NotAnInteger { redex, value }
+}
+fn not_a_char(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    NotAChar { redex, value }
+}
+fn not_a_string(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    NotAString { redex, value }
+}
+
+fn eval_concat(t1: Term, t2: Term) -> StepResult {
+    match (t1, t2) {
+        (Str(s1), Str(s2)) => Ok(Str(s1 + &s2)),
+        (t1, t2) => {
+            let value = if let Str(_) = t1 { t2.clone() } else { t1.clone() };
+            Err(NotAString {
+                redex: Box::new(Concat(Box::new(t1), Box::new(t2))),
+                value: Box::new(value),
+            })
+        }
+    }
+}
+
+/// Takes a step of `t1 ++ t2` on two values, moving the head of `t1` out of the concatenation.
+fn eval_append(t1: Term, t2: Term) -> StepResult {
+    match t1 {
+        Nil(_) => Ok(t2),
+        Cons(h, t) => Ok(Cons(h, Box::new(Append(t, Box::new(t2))))),
+        t1 => Err(NotAList {
+            redex: Box::new(Append(Box::new(t1.clone()), Box::new(t2))),
+            value: Box::new(t1),
+        }),
+    }
+}
+
+fn eval_strlen(t: Term) -> StepResult {
+    match t {
+        Str(s) => Ok(Int(s.chars().count().into())),
+        t => Err(NotAString {
+            redex: Box::new(StrLen(Box::new(t.clone()))),
+            value: Box::new(t),
+        }),
+    }
+}
+
+fn eval_char_at(t1: Term, t2: Term) -> StepResult {
+    let redex = || Box::new(CharAt(Box::new(t1.clone()), Box::new(t2.clone())));
+    match (&t1, &t2) {
+        (Str(s), Int(i)) => i
+            .to_usize()
+            .and_then(|i| s.chars().nth(i))
+            .map(Char)
+            .ok_or_else(|| IndexOutOfBounds {
+                redex: redex(),
+                value: Box::new(t2.clone()),
+            }),
+        (Str(_), _) => Err(NotAnInteger {
+            redex: redex(),
+            value: Box::new(t2.clone()),
+        }),
+        _ => Err(NotAString {
+            redex: redex(),
+            value: Box::new(t1.clone()),
+        }),
+    }
+}
+
+fn eval_int_to_string(t: Term) -> StepResult {
+    match t {
+        Int(i) => Ok(Str(i.to_string())),
+        t => Err(NotAnInteger {
+            redex: Box::new(IntToString(Box::new(t.clone()))),
+            value: Box::new(t),
+        }),
+    }
+}
+
+fn eval_proj(t: Term, i: usize) -> StepResult {
+    match (t, i) {
+        (Pair(v1, _), 0) => Ok(*v1),
+        (Pair(_, v2), 1) => Ok(*v2),
+        (Tuple(mut vs), i) if i < vs.len() => Ok(vs.swap_remove(i)),
+        (t, i) => Err(NotATuple {
+            redex: Box::new(Proj(Box::new(t.clone()), i)),
+            value: Box::new(t),
+        }),
+    }
+}
+
+fn eval_field(t: Term, l: &str) -> StepResult {
+    if let Record(fields) = &t
+        && let Some((_, v)) = fields.iter().find(|(l2, _)| l2 == l)
+    {
+        return Ok(v.clone());
+    }
+    Err(NotARecord {
+        redex: Box::new(Field(Box::new(t.clone()), l.to_string())),
+        value: Box::new(t),
+    })
+}
+
+fn eval_update(t: Term, updates: Vec<(String, Term)>) -> StepResult {
+    match t {
+        Record(mut fields) if updates.iter().all(|(l, _)| fields.iter().any(|(l2, _)| l2 == l)) => {
+            for (l, v) in updates {
+                let field = fields.iter_mut().find(|(l2, _)| *l2 == l).expect("the field to exist");
+                field.1 = v;
+            }
+            Ok(Record(fields))
+        }
+        t => Err(NotARecord {
+            redex: Box::new(Update(Box::new(t.clone()), updates)),
+            value: Box::new(t),
+        }),
+    }
+}
+
+/// Steps the first field of `fields` which is not a value, if any.
+fn step_fields(fields: &mut [(String, Term)]) -> Result<bool, EvalError> {
+    match fields.iter_mut().find(|(_, t)| !t.is_value()) {
+        Some((_, t)) => {
+            *t = t.clone().try_step()?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Compares two values structurally.
+///
+/// Returns the offending value if either of the values contains an abstraction.
+fn values_equal<'a>(t1: &'a Term, t2: &'a Term) -> Result<bool, &'a Term> {
+    match (t1, t2) {
+        (Abs { .. } | TAbs { .. }, _) => Err(t1),
+        (_, Abs { .. } | TAbs { .. }) => Err(t2),
+        (Int(i1), Int(i2)) => Ok(i1 == i2),
+        (Unit, Unit) | (True, True) | (False, False) => Ok(true),
+        (Str(s1), Str(s2)) => Ok(s1 == s2),
+        (Char(c1), Char(c2)) => Ok(c1 == c2),
+        (Nil(_), Nil(_)) => Ok(true),
+        (Pair(a1, b1), Pair(a2, b2)) | (Cons(a1, b1), Cons(a2, b2)) => {
+            // Both sides are compared to find functions even if the first ones differ
+            let first = values_equal(a1, a2)?;
+            Ok(values_equal(b1, b2)? && first)
+        }
+        (Tuple(vs1), Tuple(vs2)) => {
+            let mut equal = vs1.len() == vs2.len();
+            for (v1, v2) in vs1.iter().zip(vs2) {
+                equal = values_equal(v1, v2)? && equal;
+            }
+            Ok(equal)
+        }
+        (Record(fields1), Record(fields2)) => {
+            let mut equal = fields1.len() == fields2.len();
+            for (l, v1) in fields1 {
+                equal = match fields2.iter().find(|(l2, _)| l2 == l) {
+                    Some((_, v2)) => values_equal(v1, v2)? && equal,
+                    None => false,
+                };
+            }
+            Ok(equal)
+        }
+        (Inl(v1, _), Inl(v2, _)) | (Inr(v1, _), Inr(v2, _)) => values_equal(v1, v2),
+        (Tag(l1, v1, _), Tag(l2, v2, _)) => Ok(values_equal(v1, v2)? && l1 == l2),
+        (Fold(v1, _), Fold(v2, _)) => values_equal(v1, v2),
+        _ => Ok(false),
+    }
+}
+
+/// Evaluates structural equality of two values, resulting in `True` if the equality equals `expected`.
+fn eval_eq(
+    ctor: fn(Box<Term>, Box<Term>) -> Term,
+    expected: bool,
+    t1: Term,
+    t2: Term,
+) -> StepResult {
+    match values_equal(&t1, &t2) {
+        Ok(equal) => Ok(boolean(equal == expected)),
+        Err(value) => Err(NotComparable {
+            value: Box::new(value.clone()),
+            redex: Box::new(ctor(Box::new(t1), Box::new(t2))),
+        }),
+    }
+}
+
+fn eval_app(t1: Term, t2: Term) -> StepResult {
+    if let Abs { var, ty: _, body } = t1 {
+        Ok(body.subst(&var, t2))
+    } else {
+        Err(NotAFunction {
+            redex: Box::new(App(Box::new(t1.clone()), Box::new(t2))),
+            value: Box::new(t1),
+        })
+    }
+}
+
+fn eval_let(val_t: Term, var: impl AsRef<str>, body: Term) -> StepResult {
+    Ok(body.subst(var.as_ref(), val_t))
+}
+
+fn eval_letrec(bindings: Vec<(String, Type, Term)>, body: Term) -> StepResult {
+    // Each name unfolds to its own binding under the whole group, like `fix`
+    let unfoldings: Vec<_> = bindings
+        .iter()
+        .map(|(var, _, t)| {
+            let t = LetRec {
+                bindings: bindings.clone(),
+                body: Box::new(t.clone()),
+            };
+            (var, t)
+        })
+        .collect();
+    Ok(unfoldings.into_iter().fold(body, |body, (var, t)| body.subst(var, t)))
+}
+
+fn eval_ite(cond: Term, if_true: Term, if_false: Term) -> StepResult {
+    match cond {
+        True => Ok(if_true),
+        False => Ok(if_false),
+        _ => Err(NotABoolean {
+            redex: Box::new(Ite {
+                cond: Box::new(cond.clone()),
+                if_true: Box::new(if_true),
+                if_false: Box::new(if_false),
+            }),
+            value: Box::new(cond),
+        }),
+    }
+}
+
+/// Evaluates a conjunction or disjunction `ctor(t1, t2)` once `t1` is a value.
+///
+/// Results in `t2` if `t1` equals `proceed`, otherwise in `t1` without evaluating `t2`.
+fn eval_connective(
+    ctor: fn(Box<Term>, Box<Term>) -> Term,
+    proceed: Term,
+    t1: Term,
+    t2: Term,
+) -> StepResult {
+    match t1 {
+        True | False if t1 == proceed => Ok(t2),
+        True | False => Ok(t1),
+        _ => Err(NotABoolean {
+            redex: Box::new(ctor(Box::new(t1.clone()), Box::new(t2))),
+            value: Box::new(t1),
+        }),
     }
 }
 
 impl Term {
+    /// Performs a single step of small-step evaluation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the term is a value or is stuck. See [`Term::try_step`] for a non-panicking version.
     pub fn step(self) -> Self {
+        self.try_step().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Performs a single step of small-step evaluation.
+    ///
+    /// # Errors
+    ///
+    /// - If a free variable is reached, returns [`FreeVariable`] with its name.
+    /// - If the term is already a value, returns [`IsValue`] with the term inside.
+    /// - If a redex is stuck because one of its operands is a value of the wrong shape, e.g.
+    ///   `fst 5`, returns the corresponding error with the stuck redex and the offending value.
+    /// - If the divisor of a division or remainder is zero, returns [`DivisionByZero`] with the redex.
+    /// - If the result of arithmetic overflows with the current [`Arithmetic`], returns [`Overflow`]
+    ///   with the redex.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::{util::*, EvalError};
+    /// assert_eq!(add(1, 2).try_step(), Ok(Int(3.into())));
+    /// assert_eq!(
+    ///     app(5, True).try_step(),
+    ///     Err(EvalError::NotAFunction { redex: app(5, True).into(), value: 5.into() })
+    /// );
+    /// assert_eq!(
+    ///     fst(add(1, 2)).try_step(),
+    ///     Ok(fst(3))
+    /// );
+    /// assert_eq!(
+    ///     fst(3).try_step(),
+    ///     Err(EvalError::NotAPair { redex: fst(3).into(), value: 3.into() })
+    /// );
+    /// assert_eq!(var("x").try_step(), Err(EvalError::FreeVariable("x".to_string())));
+    /// assert_eq!(and(False, var("loop")).try_step(), Ok(False));
+    /// assert_eq!(or(False, var("x")).try_step(), Ok(var("x")));
+    /// assert_eq!(div(-7, 2).try_step(), Ok(Int((-3).into())));
+    /// assert_eq!(
+    ///     rem(1, 0).try_step(),
+    ///     Err(EvalError::DivisionByZero { redex: rem(1, 0).into() })
+    /// );
+    /// assert_eq!(
+    ///     add(i32::MAX, 1).try_step(),
+    ///     Err(EvalError::Overflow { redex: add(i32::MAX, 1).into() })
+    /// );
+    /// ```
+    ///
+    /// Equality is structural on integers, booleans, pairs, lists and sums:
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// assert_eq!(eq(True, True).try_step(), Ok(True));
+    /// assert_eq!(eq(pair(1, 2), pair(1, 2)).try_step(), Ok(True));
+    /// assert_eq!(ne(cons(1, Nil(Integer)), Nil(Integer)).try_step(), Ok(True));
+    /// assert_eq!(eq(inl(1, Integer), inr(1, Integer)).try_step(), Ok(False));
+    /// assert!(eq(id2(), id2()).try_step().is_err());
+    /// ```
+    ///
+    /// A `match` of a variant steps to the arm of its label:
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// let option = variant([("some", Integer), ("none", Type::Unit)]);
+    /// let t = match_variant(tag("some", 1, option), [("none", "u", Int(0.into())), ("some", "x", add("x", 1))]);
+    /// assert_eq!(t.try_step(), Ok(add(1, 1)));
+    /// ```
+    ///
+    /// Ordering works on integers, characters and strings:
+    /// ```rust
+    /// # use stlc_project::term::{util::*, EvalError};
+    /// assert_eq!(lt(str("abc"), str("abd")).try_step(), Ok(True));
+    /// assert_eq!(ge(Char('a'), Char('b')).try_step(), Ok(False));
+    /// assert_eq!(charat(str("hey"), 1).try_step(), Ok(Char('e')));
+    /// assert_eq!(
+    ///     charat(str("hey"), 3).try_step(),
+    ///     Err(EvalError::IndexOutOfBounds { redex: charat(str("hey"), 3).into(), value: 3.into() })
+    /// );
+    /// ```
+    pub fn try_step(self) -> StepResult {
         match self {
-            
-            Var(y) => panic!("cannot evaluate a variable: {y}"),
-            App(t1, t2) => step_op2(App, eval_app, t1, t2),
+            Var(y) => Err(FreeVariable(y)),
+            App(t1, t2) => step_op2(App, |t1, t2| eval_app(*t1, *t2), t1, t2),
 
             Let { var, val_t, body } => step_op1(
                 |val_t| Let {
@@ -58,10 +490,12 @@ impl Term {
                     val_t,
                     body: body.clone(),
                 },
-                |val_t| eval_let(val_t, &var, body.clone()),
+                |val_t| eval_let(*val_t, &var, *body.clone()),
                 val_t,
             ),
 
+            LetRec { bindings, body } => eval_letrec(bindings, *body),
+
             Ite {
                 cond,
                 if_true,
@@ -72,207 +506,231 @@ impl Term {
                     if_true: if_true.clone(),
                     if_false: if_false.clone(),
                 },
-                |cond| eval_ite(cond, if_true.clone(), if_false.clone()),
+                |cond| eval_ite(*cond, *if_true.clone(), *if_false.clone()),
                 cond,
             ),
-            /*
-
-            Add(t1, t2) => todo!(),
-            Sub(t1, t2) => todo!(),
-            Mul(t1, t2) => todo!(),
 
-            Eq(t1, t2) => todo!(),
-            Ne(t1, t2) => todo!(),
-            Lt(t1, t2) => todo!(),
-            Le(t1, t2) => todo!(),
-            Gt(t1, t2) => todo!(),
-            Ge(t1, t2) => todo!(),
+            And(t1, t2) => step_op1(
+                |t1| And(t1, t2.clone()),
+                |t1| eval_connective(And, True, *t1, *t2.clone()),
+                t1,
+            ),
+            Or(t1, t2) => step_op1(
+                |t1| Or(t1, t2.clone()),
+                |t1| eval_connective(Or, False, *t1, *t2.clone()),
+                t1,
+            ),
+            Not(t1) => step_op1(
+                Not,
+                |t1| match *t1 {
+                    True => Ok(False),
+                    False => Ok(True),
+                    t1 => Err(NotABoolean {
+                        redex: Box::new(Not(Box::new(t1.clone()))),
+                        value: Box::new(t1),
+                    }),
+                },
+                t1,
+            ),
 
-            */
             Add(t1, t2) => step_op2(
                 Add,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => Int(i1 + i2),
-                    (other1, other2) => Add(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_arith(Add, Integer::add, *t1, *t2),
                 t1,
                 t2,
             ),
             Sub(t1, t2) => step_op2(
                 Sub,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => Int(i1 - i2),
-                    (other1, other2) => Sub(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_arith(Sub, Integer::sub, *t1, *t2),
                 t1,
                 t2,
             ),
             Mul(t1, t2) => step_op2(
                 Mul,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => Int(i1 * i2),
-                    (other1, other2) => Mul(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_arith(Mul, Integer::mul, *t1, *t2),
+                t1,
+                t2,
+            ),
+            Div(t1, t2) => step_op2(
+                Div,
+                |t1, t2| eval_div(Div, Integer::div, *t1, *t2),
+                t1,
+                t2,
+            ),
+            Mod(t1, t2) => step_op2(
+                Mod,
+                |t1, t2| eval_div(Mod, Integer::rem, *t1, *t2),
                 t1,
                 t2,
             ),
+            Neg(t1) => step_op1(
+                Neg,
+                |t1| match *t1 {
+                    Int(i) => Arithmetic::current().check(i.neg()).map(Int).ok_or_else(|| Overflow {
+                        redex: Box::new(Neg(Box::new(Int(i)))),
+                    }),
+                    t1 => Err(NotAnInteger {
+                        redex: Box::new(Neg(Box::new(t1.clone()))),
+                        value: Box::new(t1),
+                    }),
+                },
+                t1,
+            ),
             Eq(t1, t2) => step_op2(
                 Eq,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => {
-                        if i1 == i2 {
-                            True
-                        } else {
-                            False
-                        }
-                    }
-                    (other1, other2) => Eq(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_eq(Eq, true, *t1, *t2),
                 t1,
                 t2,
             ),
             Ne(t1, t2) => step_op2(
                 Ne,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => {
-                        if i1 != i2 {
-                            True
-                        } else {
-                            False
-                        }
-                    }
-                    (other1, other2) => Ne(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_eq(Ne, false, *t1, *t2),
                 t1,
                 t2,
             ),
             Lt(t1, t2) => step_op2(
                 Lt,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => {
-                        if i1 < i2 {
-                            True
-                        } else {
-                            False
-                        }
-                    }
-                    (other1, other2) => Lt(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_ord(Lt, Ordering::is_lt, *t1, *t2),
                 t1,
                 t2,
             ),
             Le(t1, t2) => step_op2(
                 Le,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => {
-                        if i1 <= i2 {
-                            True
-                        } else {
-                            False
-                        }
-                    }
-                    (other1, other2) => Le(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_ord(Le, Ordering::is_le, *t1, *t2),
                 t1,
                 t2,
             ),
             Gt(t1, t2) => step_op2(
                 Gt,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => {
-                        if i1 > i2 {
-                            True
-                        } else {
-                            False
-                        }
-                    }
-                    (other1, other2) => Gt(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_ord(Gt, Ordering::is_gt, *t1, *t2),
                 t1,
                 t2,
             ),
             Ge(t1, t2) => step_op2(
                 Ge,
-                |t1, t2| match (*t1, *t2) {
-                    (Int(i1), Int(i2)) => {
-                        if i1 >= i2 {
-                            True
-                        } else {
-                            False
-                        }
-                    }
-                    (other1, other2) => Ge(Box::new(other1), Box::new(other2)),
-                },
+                |t1, t2| eval_ord(Ge, Ordering::is_ge, *t1, *t2),
                 t1,
                 t2,
             ),
 
+            // ===========================String stuff===========================
+
+            Concat(t1, t2) => step_op2(Concat, |t1, t2| eval_concat(*t1, *t2), t1, t2),
+            StrLen(t1) => step_op1(StrLen, |t1| eval_strlen(*t1), t1),
+            CharAt(t1, t2) => step_op2(CharAt, |t1, t2| eval_char_at(*t1, *t2), t1, t2),
+            IntToString(t1) => step_op1(IntToString, |t1| eval_int_to_string(*t1), t1),
+
             // ============================Pair stuff============================
 
             Pair(t1, t2) => {
                 if !t1.is_value() {
-                    Pair(Box::new(t1.step()), t2)
+                    Ok(Pair(Box::new(t1.try_step()?), t2))
                 } else if !t2.is_value() {
-                    Pair(t1, Box::new(t2.step()))
+                    Ok(Pair(t1, Box::new(t2.try_step()?)))
                 } else {
-                    panic!("attempted to step pair of values")
+                    Err(IsValue(Box::new(Pair(t1, t2))))
                 }
             }
             Fst(t1) => {
                 let inner = *t1;
                 if !inner.is_value() {
-                    Fst(Box::new(inner.step()))
+                    Ok(Fst(Box::new(inner.try_step()?)))
                 } else {
                     match inner {
-                        Pair(v1, v2) if v1.is_value() && v2.is_value() => *v1,
-                        Pair(_, _) => panic!("fst applied to pair with non-value elements"),
-                        _ => panic!("fst applied to non-pair value"),
+                        Pair(v1, _) => Ok(*v1),
+                        _ => Err(NotAPair {
+                            redex: Box::new(Fst(Box::new(inner.clone()))),
+                            value: Box::new(inner),
+                        }),
                     }
                 }
             }
             Snd(t1) => {
                 let inner = *t1;
                 if !inner.is_value() {
-                    Snd(Box::new(inner.step()))
+                    Ok(Snd(Box::new(inner.try_step()?)))
                 } else {
                     match inner {
-                        Pair(v1, v2) if v1.is_value() && v2.is_value() => *v2,
-                        Pair(_, _) => panic!("snd applied to pair with non-value elements"),
-                        _ => panic!("snd applied to non-pair value"),
+                        Pair(_, v2) => Ok(*v2),
+                        _ => Err(NotAPair {
+                            redex: Box::new(Snd(Box::new(inner.clone()))),
+                            value: Box::new(inner),
+                        }),
                     }
                 }
             }
 
+            Tuple(mut ts) => match ts.iter().position(|t| !t.is_value()) {
+                Some(i) => {
+                    ts[i] = ts[i].clone().try_step()?;
+                    Ok(Tuple(ts))
+                }
+                None => Err(IsValue(Box::new(Tuple(ts)))),
+            },
+            Proj(t1, i) => step_op1(|t1| Proj(t1, i), |t1| eval_proj(*t1, i), t1),
+
+            // ===========================Record stuff===========================
+
+            Record(mut fields) => {
+                if step_fields(&mut fields)? {
+                    Ok(Record(fields))
+                } else {
+                    Err(IsValue(Box::new(Record(fields))))
+                }
+            }
+            Field(t1, l) => step_op1(|t1| Field(t1, l.clone()), |t1| eval_field(*t1, &l), t1),
+            Update(t1, mut fields) => {
+                if !t1.is_value() {
+                    Ok(Update(Box::new(t1.try_step()?), fields))
+                } else if step_fields(&mut fields)? {
+                    Ok(Update(t1, fields))
+                } else {
+                    eval_update(*t1, fields)
+                }
+            }
+
             // ============================List stuff============================
 
             Cons(h, t) => {
                 if !h.is_value() {
-                    Cons(Box::new(h.step()), t)
+                    Ok(Cons(Box::new(h.try_step()?), t))
                 } else if !t.is_value() {
-                    Cons(h, Box::new(t.step()))
+                    Ok(Cons(h, Box::new(t.try_step()?)))
                 } else {
-                    panic!("attempted to step cons of values")
+                    Err(IsValue(Box::new(Cons(h, t))))
                 }
             }
+            Append(t1, t2) => step_op2(Append, |t1, t2| eval_append(*t1, *t2), t1, t2),
 
             LCase { t, nil_t, head_var, tail_var, cons_t } => {
                 let t = *t;
                 if !t.is_value() {
-                    LCase {
-                        t: Box::new(t.step()),
+                    Ok(LCase {
+                        t: Box::new(t.try_step()?),
                         nil_t,
                         head_var,
                         tail_var,
                         cons_t,
-                    }
+                    })
                 } else {
                     match t {
-                        Nil(_) => *nil_t,
-                        Cons(h, t) if h.is_value() && t.is_value() => {
-                            cons_t.subst(&head_var, *h).subst(&tail_var, *t)
+                        Nil(_) => Ok(*nil_t),
+                        Cons(h, t) => {
+                            // Substituting one after the other would also replace the tail's
+                            // variable inside the head
+                            let s = HashMap::from([(head_var, *h), (tail_var, *t)]);
+                            Ok(cons_t.subst_vars(&s))
                         }
-                        Cons(_, _) => panic!("lcase on cons with non-value parts"),
-                        _ => panic!("lcase on non-list"),
+                        _ => Err(NotAList {
+                            redex: Box::new(LCase {
+                                t: Box::new(t.clone()),
+                                nil_t,
+                                head_var,
+                                tail_var,
+                                cons_t,
+                            }),
+                            value: Box::new(t),
+                        }),
                     }
                 }
             }
@@ -281,18 +739,18 @@ impl Term {
             // ===== Inl Evaluation Rule =====
             Inl(t, ty) => {
                 if !t.is_value() {
-                    Inl(Box::new(t.step()), ty)
+                    Ok(Inl(Box::new(t.try_step()?), ty))
                 } else {
-                    panic!("attempted to step inl of value")
+                    Err(IsValue(Box::new(Inl(t, ty))))
                 }
             }
 
             // ===== Inr Evaluation Rule =====
             Inr(t, ty) => {
                 if !t.is_value() {
-                    Inr(Box::new(t.step()), ty)
+                    Ok(Inr(Box::new(t.try_step()?), ty))
                 } else {
-                    panic!("attempted to step inr of value")
+                    Err(IsValue(Box::new(Inr(t, ty))))
                 }
             }
 
@@ -300,29 +758,69 @@ impl Term {
             Case { t, inl_var, inl_t, inr_var, inr_t } => {
                 let t = *t;
                 if !t.is_value() {
-                    Case {
-                        t: Box::new(t.step()),
+                    Ok(Case {
+                        t: Box::new(t.try_step()?),
                         inl_var,
                         inl_t,
                         inr_var,
                         inr_t,
-                    }
+                    })
                 } else {
                     match t {
-                        Inl(v, _) if v.is_value() => inl_t.subst(&inl_var, *v),
-                        Inr(v, _) if v.is_value() => inr_t.subst(&inr_var, *v),
-                        Inl(_, _) | Inr(_, _) => panic!("case on non-value inl/inr"),
-                        _ => panic!("case on non-sum value"),
+                        Inl(v, _) => Ok(inl_t.subst(&inl_var, *v)),
+                        Inr(v, _) => Ok(inr_t.subst(&inr_var, *v)),
+                        _ => Err(NotASum {
+                            redex: Box::new(Case {
+                                t: Box::new(t.clone()),
+                                inl_var,
+                                inl_t,
+                                inr_var,
+                                inr_t,
+                            }),
+                            value: Box::new(t),
+                        }),
                     }
                 }
             }
 
+            // ===========================Variant stuff===========================
+
+            Tag(l, t, ty) => {
+                if !t.is_value() {
+                    Ok(Tag(l, Box::new(t.try_step()?), ty))
+                } else {
+                    Err(IsValue(Box::new(Tag(l, t, ty))))
+                }
+            }
+
+            Match { t, arms } => {
+                let t = *t;
+                if !t.is_value() {
+                    return Ok(Match {
+                        t: Box::new(t.try_step()?),
+                        arms,
+                    });
+                }
+                if let Tag(l, v, _) = &t
+                    && let Some((_, var, arm)) = arms.iter().find(|(l2, _, _)| l2 == l)
+                {
+                    return Ok(arm.clone().subst(var, (**v).clone()));
+                }
+                Err(NotAVariant {
+                    redex: Box::new(Match {
+                        t: Box::new(t.clone()),
+                        arms,
+                    }),
+                    value: Box::new(t),
+                })
+            }
+
             // ============================Fix stuff============================
 
             Fix(inner) => {
                 match *inner {
                     // Fix1: Reduce the inner term first if it's not a value
-                    ref t if !t.is_value() => Fix(Box::new(t.clone().step())),
+                    t if !t.is_value() => Ok(Fix(Box::new(t.try_step()?))),
 
                     // Fix2: fix (\x:T. t) => [x -> fix (\x:T. t)] t
                     Abs { var, ty, body } => {
@@ -331,21 +829,141 @@ impl Term {
                             ty: ty.clone(),
                             body: body.clone(),
                         }));
-                        body.subst(&var, clone)
+                        Ok(body.subst(&var, clone))
                     }
-                    _ => panic!("fix applied to non-abstraction value"),
+                    t => Err(NotAFunction {
+                        redex: Box::new(Fix(Box::new(t.clone()))),
+                        value: Box::new(t),
+                    }),
+                }
+            }
+
+            // ========================Polymorphism stuff========================
+
+            TApp(t1, ty) => {
+                match *t1 {
+                    // TApp1: Reduce the type abstraction first
+                    t if !t.is_value() => Ok(TApp(Box::new(t.try_step()?), ty)),
+
+                    // TApp2: (Fun a, t) [T] => [a -> T] t
+                    TAbs { var, body } => Ok(body.subst_type(&var, &ty)),
+                    t => Err(NotATypeAbstraction {
+                        redex: Box::new(TApp(Box::new(t.clone()), ty)),
+                        value: Box::new(t),
+                    }),
                 }
             }
 
+            // ==========================Data type stuff==========================
+
+            // Types are erased, so the declaration is dropped
+            Data { body, .. } => Ok(*body),
 
-            _ => panic!("cannot step a value"),
+            // =======================Recursive type stuff=======================
+
+            Fold(t, ty) => {
+                if !t.is_value() {
+                    Ok(Fold(Box::new(t.try_step()?), ty))
+                } else {
+                    Err(IsValue(Box::new(Fold(t, ty))))
+                }
+            }
+
+            Unfold(t) => {
+                match *t {
+                    // Unfold1: Reduce the inner term first
+                    t if !t.is_value() => Ok(Unfold(Box::new(t.try_step()?))),
+
+                    // Unfold2: unfold (fold v T) => v
+                    Fold(v, _) => Ok(*v),
+                    t => Err(NotAFold {
+                        redex: Box::new(Unfold(Box::new(t.clone()))),
+                        value: Box::new(t),
+                    }),
+                }
+            }
+
+            _ => Err(IsValue(Box::new(self))),
         }
     }
 
-    pub fn multistep(mut self) -> Self {
+    /// Evaluates the term to a value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the evaluation gets stuck. See [`Term::try_multistep`] for a non-panicking version.
+    pub fn multistep(self) -> Self {
+        self.try_multistep().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Evaluates the term to a value by repeatedly calling [`Term::try_step`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first step that fails, see [`Term::try_step`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::{util::*, EvalError};
+    /// assert_eq!(app(abs("x", Integer, add("x", 1)), 2).try_multistep(), Ok(Int(3.into())));
+    /// assert_eq!(
+    ///     add(1, app(abs("x", Integer, "y"), 2)).try_multistep(),
+    ///     Err(EvalError::FreeVariable("y".to_string()))
+    /// );
+    /// ```
+    pub fn try_multistep(mut self) -> StepResult {
+        Arithmetic::current().check_literals(&self)?;
         while !self.is_value() {
-            self = self.step()
+            self = self.try_step()?
+        }
+        Ok(self)
+    }
+
+    /// Evaluates the term to a value within the budget `fuel`.
+    ///
+    /// Evaluation stops with [`Outcome::OutOfFuel`] holding the partially reduced term if the term
+    /// is not a value after `fuel.steps` steps, or if it grows larger than `fuel.max_size`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first step that fails, see [`Term::try_step`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::{step::{Fuel, Outcome}, util::*};
+    /// let t = add(1, add(2, 3));
+    /// assert_eq!(t.clone().try_multistep_fuel(Fuel::new(2)), Ok(Outcome::Value(Int(6.into()))));
+    /// assert_eq!(t.try_multistep_fuel(Fuel::new(1)), Ok(Outcome::OutOfFuel(add(1, 5))));
+    ///
+    /// // fix (fun x : Integer, x) diverges
+    /// let t = fix(abs("x", Integer, "x"));
+    /// assert!(matches!(t.try_multistep_fuel(Fuel::new(100)), Ok(Outcome::OutOfFuel(_))));
+    ///
+    /// // fix (fun x : [Integer], cons 1 x) grows without bound
+    /// let t = fix(abs("x", list(Integer), cons(1, "x")));
+    /// let Ok(Outcome::OutOfFuel(t)) = t.try_multistep_fuel(Fuel::new(1000).with_max_size(20))
+    /// else {
+    ///     panic!("expected to run out of fuel")
+    /// };
+    /// assert!(t.size() > 20);
+    /// ```
+    pub fn try_multistep_fuel(mut self, fuel: Fuel) -> Result<Outcome, EvalError> {
+        Arithmetic::current().check_literals(&self)?;
+        for _ in 0..fuel.steps {
+            if self.is_value() {
+                return Ok(Outcome::Value(self));
+            }
+            if fuel.max_size.is_some_and(|max_size| self.size() > max_size) {
+                return Ok(Outcome::OutOfFuel(self));
+            }
+            self = self.try_step()?;
+        }
+        if self.is_value() {
+            Ok(Outcome::Value(self))
+        } else {
+            Ok(Outcome::OutOfFuel(self))
         }
-        self
     }
 }
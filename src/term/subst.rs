@@ -1,25 +1,236 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
 use super::Term::{self, *};
+use crate::r#type::Type;
+
+/// Generates a variable name based on `base` that does not occur in `avoid`.
+///
+/// Trailing digits of `base` are stripped and replaced with the smallest number that makes the
+/// name fresh, so renaming `x` repeatedly yields `x1`, `x2`, ... rather than `x11`, `x111`, ...
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::collections::HashSet;
+/// # use stlc_project::term::subst::fresh_name;
+/// let avoid = HashSet::from(["x".to_string(), "x1".to_string()]);
+/// assert_eq!(fresh_name("x", &avoid), "x2");
+/// assert_eq!(fresh_name("x1", &avoid), "x2");
+/// assert_eq!(fresh_name("y", &avoid), "y1");
+/// ```
+pub fn fresh_name(base: &str, avoid: &HashSet<String>) -> String {
+    let stem = base.trim_end_matches(|c: char| c.is_ascii_digit());
+    let stem = if stem.is_empty() { "x" } else { stem };
+    (1..)
+        .map(|i| format!("{stem}{i}"))
+        .find(|name| !avoid.contains(name))
+        .expect("there are infinitely many candidate names")
+}
+
+/// Terms substituted for variables.
+type Substitution = HashMap<String, Term>;
+
+/// The substitution `s` with the free variables `fv` of its terms, restricted to the body of
+/// binders of `vars`.
+///
+/// Binders capturing a free variable of `s` are renamed in `vars`, to names outside `avoid` which
+/// holds every variable free or bound above the body. Returns `None` if nothing is substituted in
+/// the body, which is then left untouched.
+fn under_binders<'a>(
+    vars: &mut [String],
+    s: &'a Substitution,
+    fv: &'a HashSet<String>,
+    avoid: &mut HashSet<String>,
+) -> Option<(Cow<'a, Substitution>, Cow<'a, HashSet<String>>)> {
+    avoid.extend(vars.iter().cloned());
+    let mut s = Cow::Borrowed(s);
+    for var in vars.iter() {
+        if s.contains_key(var) {
+            s.to_mut().remove(var);
+        }
+    }
+    if s.is_empty() {
+        return None;
+    }
+    let mut fv = Cow::Borrowed(fv);
+    for var in vars.iter_mut() {
+        if fv.contains(var) {
+            let fresh = fresh_name(var, avoid);
+            avoid.insert(fresh.clone());
+            fv.to_mut().insert(fresh.clone());
+            s.to_mut().insert(std::mem::replace(var, fresh.clone()), Var(fresh));
+        }
+    }
+    Some((s, fv))
+}
+
+/// Substitutes `s` in the `body` of a binder of `var`, see [`under_binders`].
+fn subst_under(
+    var: String,
+    body: Term,
+    s: &Substitution,
+    fv: &HashSet<String>,
+    avoid: &mut HashSet<String>,
+) -> (String, Term) {
+    let mut vars = [var];
+    let body = match under_binders(&mut vars, s, fv, avoid) {
+        Some((s, fv)) => body.subst_map(&s, &fv, avoid),
+        None => body,
+    };
+    let [var] = vars;
+    (var, body)
+}
 
 impl Term {
+    /// Returns the set of variables occurring free in the term.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::collections::HashSet;
+    /// # use stlc_project::term::util::*;
+    /// let t = abs("x", Integer, add("x", "y"));
+    /// assert_eq!(t.free_vars(), HashSet::from(["y".to_string()]));
+    ///
+    /// let t = lcase("xs", "z", "h", "t", cons("h", "t"));
+    /// assert_eq!(t.free_vars(), HashSet::from(["xs".to_string(), "z".to_string()]));
+    /// ```
+    pub fn free_vars(&self) -> HashSet<String> {
+        let mut fv = HashSet::new();
+        self.collect_free_vars(&mut Vec::new(), &mut fv);
+        fv
+    }
+
+    fn collect_free_vars<'a>(&'a self, bound: &mut Vec<&'a str>, fv: &mut HashSet<String>) {
+        // Collects the free variables of `t` with the extra variables `vars` bound
+        fn under<'a>(
+            t: &'a Term,
+            vars: &[&'a str],
+            bound: &mut Vec<&'a str>,
+            fv: &mut HashSet<String>,
+        ) {
+            bound.extend_from_slice(vars);
+            t.collect_free_vars(bound, fv);
+            bound.truncate(bound.len() - vars.len());
+        }
+
+        match self {
+            Var(x) => {
+                if !bound.contains(&x.as_str()) {
+                    fv.insert(x.clone());
+                }
+            }
+            Abs { var, body, .. } => under(body, &[var], bound, fv),
+            TAbs { body, .. } | Data { body, .. } => body.collect_free_vars(bound, fv),
+            Let { var, val_t, body } => {
+                val_t.collect_free_vars(bound, fv);
+                under(body, &[var], bound, fv);
+            }
+            LetRec { bindings, body } => {
+                let vars: Vec<&str> = bindings.iter().map(|(var, ..)| var.as_str()).collect();
+                for (_, _, t) in bindings {
+                    under(t, &vars, bound, fv);
+                }
+                under(body, &vars, bound, fv);
+            }
+            Unit | True | False | Int(_) | Str(_) | Char(_) | Nil(_) => {}
+            Ite {
+                cond,
+                if_true,
+                if_false,
+            } => {
+                cond.collect_free_vars(bound, fv);
+                if_true.collect_free_vars(bound, fv);
+                if_false.collect_free_vars(bound, fv);
+            }
+            App(t1, t2)
+            | Add(t1, t2)
+            | Sub(t1, t2)
+            | Mul(t1, t2)
+            | Div(t1, t2)
+            | Mod(t1, t2)
+            | Eq(t1, t2)
+            | Ne(t1, t2)
+            | Lt(t1, t2)
+            | Le(t1, t2)
+            | Gt(t1, t2)
+            | Ge(t1, t2)
+            | And(t1, t2)
+            | Or(t1, t2)
+            | Pair(t1, t2)
+            | Cons(t1, t2)
+            | Append(t1, t2)
+            | Concat(t1, t2)
+            | CharAt(t1, t2) => {
+                t1.collect_free_vars(bound, fv);
+                t2.collect_free_vars(bound, fv);
+            }
+            Fst(t) | Snd(t) | Inl(t, _) | Inr(t, _) | Fix(t) | TApp(t, _) | StrLen(t)
+            | IntToString(t) | Neg(t) | Not(t) | Proj(t, _) | Fold(t, _) | Unfold(t) => {
+                t.collect_free_vars(bound, fv)
+            }
+            Tuple(ts) => ts.iter().for_each(|t| t.collect_free_vars(bound, fv)),
+            Record(fields) => fields
+                .iter()
+                .for_each(|(_, t)| t.collect_free_vars(bound, fv)),
+            Field(t, _) => t.collect_free_vars(bound, fv),
+            Update(t, fields) => {
+                t.collect_free_vars(bound, fv);
+                fields
+                    .iter()
+                    .for_each(|(_, t)| t.collect_free_vars(bound, fv));
+            }
+            LCase {
+                t,
+                nil_t,
+                head_var,
+                tail_var,
+                cons_t,
+            } => {
+                t.collect_free_vars(bound, fv);
+                nil_t.collect_free_vars(bound, fv);
+                under(cons_t, &[head_var, tail_var], bound, fv);
+            }
+            Case {
+                t,
+                inl_var,
+                inl_t,
+                inr_var,
+                inr_t,
+            } => {
+                t.collect_free_vars(bound, fv);
+                under(inl_t, &[inl_var], bound, fv);
+                under(inr_t, &[inr_var], bound, fv);
+            }
+            Tag(_, t, _) => t.collect_free_vars(bound, fv),
+            Match { t, arms } => {
+                t.collect_free_vars(bound, fv);
+                for (_, var, arm) in arms {
+                    under(arm, &[var], bound, fv);
+                }
+            }
+        }
+    }
+
     /// Performs substitution of a variable `x` with a given term `v`.
     ///
     /// # Incomplete Terms
     ///
-    /// If `v` contains free variables, the function's behavior remains well-defined
-    /// but the correctness of the result is not guaranteed (i.e. this function assumes that `v`
-    /// is complete), though it must not panic even if it is not.
+    /// If `v` contains free variables, binders that would capture them are renamed using
+    /// [`fresh_name`], so the result is correct for open terms as well.
     ///
     /// # Examples
     ///
     /// **Variable substitution:**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert_eq!(var("x").subst("x", var("y")), var("y"));
     /// ```
     ///
     /// **Inside an abstraction (bound variable remains unchanged):**
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert_eq!(abs("x", Boolean, var("x")).subst("x", var("y")), abs("x", Boolean, var("x")));
     /// ```
     ///
@@ -27,7 +238,7 @@ impl Term {
     /// In the let expression below, the bound variable is `"y"`. Substituting `"x"` will
     /// affect both the value part and the body.
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// let let_expr = letin("y", var("x"), app(var("x"), var("y")));
     /// let expected  = letin("y", var("z"), app(var("z"), var("y")));
     /// assert_eq!(let_expr.subst("x", var("z")), expected);
@@ -38,7 +249,7 @@ impl Term {
     /// - `[x ↦ id](let x = x in x) = let x = id in x`, i.e. `val_t` is substituted, as it's not quantified by the let.
     ///
     /// ```rust
-    /// # use application::term::util::*;
+    /// # use stlc_project::term::util::*;
     /// assert_eq!(
     ///     letin("x", id2(), var("x")).subst("x", id2()),
     ///     letin("x", id2(), var("x"))
@@ -48,122 +259,536 @@ impl Term {
     ///     letin("x", id2(), var("x"))
     /// );
     /// ```
+    ///
+    /// **Capture avoidance:**
+    /// Binders that would capture a free variable of `v` are renamed before substituting.
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// // [x ↦ y](fun y : Integer, x + y) = fun y1 : Integer, y + y1
+    /// assert_eq!(
+    ///     abs("y", Integer, add("x", "y")).subst("x", var("y")),
+    ///     abs("y1", Integer, add("y", "y1"))
+    /// );
+    /// // [x ↦ y](let y = 1 in x) = let y1 = 1 in y
+    /// assert_eq!(
+    ///     letin("y", 1, "x").subst("x", var("y")),
+    ///     letin("y1", 1, "y")
+    /// );
+    /// // Both binders of an lcase are renamed if needed, avoiding each other
+    /// assert_eq!(
+    ///     lcase("l", "x", "h", "h1", cons("x", "h1")).subst("x", var("h")),
+    ///     lcase("l", "h", "h2", "h1", cons("h", "h1"))
+    /// );
+    /// assert_eq!(
+    ///     lcase("l", 0, "h", "t", add("x", "t")).subst("x", var("t")),
+    ///     lcase("l", 0, "h", "t1", add("t", "t1"))
+    /// );
+    /// // Each arm of a case is renamed independently
+    /// assert_eq!(
+    ///     case("s", "a", add("x", "a"), "b", "b").subst("x", var("a")),
+    ///     case("s", "a1", add("a", "a1"), "b", "b")
+    /// );
+    /// assert_eq!(
+    ///     case("s", "a", "a", "b", add("x", "b")).subst("x", var("b")),
+    ///     case("s", "a", "a", "b1", add("b", "b1"))
+    /// );
+    /// // Binders that do not capture anything are left alone
+    /// assert_eq!(
+    ///     abs("y", Integer, "y").subst("x", var("y")),
+    ///     abs("y", Integer, "y")
+    /// );
+    /// ```
     pub fn subst(self, x: &str, v: Self) -> Self {
+        self.subst_vars(&HashMap::from([(x.to_string(), v)]))
+    }
+
+    /// Substitutes the terms of `s` for their variables simultaneously, like [`Term::subst`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use stlc_project::term::util::*;
+    /// // [x ↦ y, y ↦ x](x, y) = (y, x)
+    /// let s = HashMap::from([("x".to_string(), var("y")), ("y".to_string(), var("x"))]);
+    /// assert_eq!(pair("x", "y").subst_vars(&s), pair("y", "x"));
+    /// ```
+    pub fn subst_vars(self, s: &HashMap<String, Term>) -> Self {
+        let mut avoid = self.free_vars();
+        if s.keys().all(|x| !avoid.contains(x)) {
+            return self;
+        }
+        let fv: HashSet<String> = s.values().flat_map(Term::free_vars).collect();
+        avoid.extend(fv.iter().cloned());
+        avoid.extend(s.keys().cloned());
+        self.subst_map(s, &fv, &mut avoid)
+    }
+
+    /// Returns the set of type variables occurring free in the type annotations of the term.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::collections::HashSet;
+    /// # use stlc_project::term::util::*;
+    /// let t = tabs("a", abs("x", tvar("a"), tapp("y", tvar("b"))));
+    /// assert_eq!(t.free_type_vars(), HashSet::from(["b".to_string()]));
+    /// ```
+    pub fn free_type_vars(&self) -> HashSet<String> {
+        let mut ftv = HashSet::new();
+        self.collect_free_type_vars(&mut Vec::new(), &mut ftv);
+        ftv
+    }
+
+    fn collect_free_type_vars(&self, bound: &mut Vec<String>, ftv: &mut HashSet<String>) {
+        let mut annotation = |ty: &Type, bound: &Vec<String>| {
+            ftv.extend(ty.free_type_vars().into_iter().filter(|a| !bound.contains(a)))
+        };
         match self {
-            Var(y) if y == x => v,
-            Abs { var, ty, body } if var != x => Abs {
+            Abs { ty, body, .. } | Data { ty, body, .. } => {
+                annotation(ty, bound);
+                body.collect_free_type_vars(bound, ftv);
+            }
+            Nil(ty) => annotation(ty, bound),
+            Inl(t, ty) | Inr(t, ty) | Tag(_, t, ty) | TApp(t, ty) | Fold(t, ty) => {
+                annotation(ty, bound);
+                t.collect_free_type_vars(bound, ftv);
+            }
+            TAbs { var, body } => {
+                bound.push(var.clone());
+                body.collect_free_type_vars(bound, ftv);
+                bound.pop();
+            }
+            LetRec { bindings, body } => {
+                for (_, ty, _) in bindings {
+                    annotation(ty, bound);
+                }
+                for (_, _, t) in bindings {
+                    t.collect_free_type_vars(bound, ftv);
+                }
+                body.collect_free_type_vars(bound, ftv);
+            }
+            Var(_) | Unit | True | False | Int(_) | Str(_) | Char(_) => {}
+            Let { val_t: t1, body: t2, .. }
+            | App(t1, t2)
+            | Add(t1, t2)
+            | Sub(t1, t2)
+            | Mul(t1, t2)
+            | Div(t1, t2)
+            | Mod(t1, t2)
+            | Eq(t1, t2)
+            | Ne(t1, t2)
+            | Lt(t1, t2)
+            | Le(t1, t2)
+            | Gt(t1, t2)
+            | Ge(t1, t2)
+            | And(t1, t2)
+            | Or(t1, t2)
+            | Pair(t1, t2)
+            | Cons(t1, t2)
+            | Append(t1, t2)
+            | Concat(t1, t2)
+            | CharAt(t1, t2) => {
+                t1.collect_free_type_vars(bound, ftv);
+                t2.collect_free_type_vars(bound, ftv);
+            }
+            Ite {
+                cond: t1,
+                if_true: t2,
+                if_false: t3,
+            }
+            | LCase {
+                t: t1,
+                nil_t: t2,
+                cons_t: t3,
+                ..
+            }
+            | Case {
+                t: t1,
+                inl_t: t2,
+                inr_t: t3,
+                ..
+            } => {
+                t1.collect_free_type_vars(bound, ftv);
+                t2.collect_free_type_vars(bound, ftv);
+                t3.collect_free_type_vars(bound, ftv);
+            }
+            Fst(t) | Snd(t) | Fix(t) | StrLen(t) | IntToString(t) | Neg(t) | Not(t) | Proj(t, _)
+            | Unfold(t) => t.collect_free_type_vars(bound, ftv),
+            Tuple(ts) => ts.iter().for_each(|t| t.collect_free_type_vars(bound, ftv)),
+            Record(fields) => fields
+                .iter()
+                .for_each(|(_, t)| t.collect_free_type_vars(bound, ftv)),
+            Field(t, _) => t.collect_free_type_vars(bound, ftv),
+            Update(t, fields) => {
+                t.collect_free_type_vars(bound, ftv);
+                fields
+                    .iter()
+                    .for_each(|(_, t)| t.collect_free_type_vars(bound, ftv));
+            }
+            Match { t, arms } => {
+                t.collect_free_type_vars(bound, ftv);
+                arms.iter()
+                    .for_each(|(_, _, t)| t.collect_free_type_vars(bound, ftv));
+            }
+        }
+    }
+
+    /// Substitutes the type `ty` for the type variable `a` in the type annotations of the term.
+    ///
+    /// Type abstractions are renamed if they would capture a type variable of `ty`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// // [a ↦ b](Fun b, fun x : a, x [b]) = Fun b1, fun x : b, x [b1]
+    /// assert_eq!(
+    ///     tabs("b", abs("x", tvar("a"), tapp("x", tvar("b")))).subst_type("a", &tvar("b")),
+    ///     tabs("b1", abs("x", tvar("b"), tapp("x", tvar("b1"))))
+    /// );
+    /// ```
+    pub fn subst_type(self, a: &str, ty: &Type) -> Self {
+        let substitution = HashMap::from([(a.to_string(), ty.clone())]);
+        self.subst_type_vars(&substitution)
+    }
+
+    /// Substitutes the types of `s` for their type variables, like [`Term::subst_type`].
+    pub(crate) fn subst_type_vars(self, s: &HashMap<String, Type>) -> Self {
+        let go = |t: Box<Term>| Box::new(t.subst_type_vars(s));
+        match self {
+            Abs { var, ty, body } => Abs {
                 var,
-                ty,
-                body: Box::new(body.subst(x, v)),
+                ty: ty.subst_type_vars(s),
+                body: go(body),
             },
-            App(t1, t2) => App(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-
-            Let { var, val_t, body } => Let {
-                body: if &var != x {
-                    Box::new(body.subst(x, v.clone()))
+            Data { name, ty, body } => Data {
+                name,
+                ty: ty.subst_type_vars(s),
+                body: go(body),
+            },
+            Nil(ty) => Nil(ty.subst_type_vars(s)),
+            Inl(t, ty) => Inl(go(t), ty.subst_type_vars(s)),
+            Inr(t, ty) => Inr(go(t), ty.subst_type_vars(s)),
+            Tag(l, t, ty) => Tag(l, go(t), ty.subst_type_vars(s)),
+            Fold(t, ty) => Fold(go(t), ty.subst_type_vars(s)),
+            TApp(t, ty) => TApp(go(t), ty.subst_type_vars(s)),
+            TAbs { var, body } => {
+                let mut s = s.clone();
+                s.remove(&var);
+                let captured: HashSet<String> =
+                    s.values().flat_map(Type::free_type_vars).collect();
+                let (var, body) = if captured.contains(&var) && !s.is_empty() {
+                    let mut avoid = body.free_type_vars();
+                    avoid.extend(captured);
+                    let fresh = fresh_name(&var, &avoid);
+                    s.insert(var, Type::Var(fresh.clone()));
+                    (fresh, body)
                 } else {
-                    body
-                },
+                    (var, body)
+                };
+                TAbs {
+                    var,
+                    body: Box::new(body.subst_type_vars(&s)),
+                }
+            }
+            Var(_) | Unit | True | False | Int(_) | Str(_) | Char(_) => self,
+            Let { var, val_t, body } => Let {
                 var,
-                val_t: Box::new(val_t.subst(x, v)),
+                val_t: go(val_t),
+                body: go(body),
             },
-
+            LetRec { bindings, body } => LetRec {
+                bindings: bindings
+                    .into_iter()
+                    .map(|(var, ty, t)| (var, ty.subst_type_vars(s), t.subst_type_vars(s)))
+                    .collect(),
+                body: go(body),
+            },
+            App(t1, t2) => App(go(t1), go(t2)),
+            Add(t1, t2) => Add(go(t1), go(t2)),
+            Sub(t1, t2) => Sub(go(t1), go(t2)),
+            Mul(t1, t2) => Mul(go(t1), go(t2)),
+            Div(t1, t2) => Div(go(t1), go(t2)),
+            Mod(t1, t2) => Mod(go(t1), go(t2)),
+            Neg(t) => Neg(go(t)),
+            Eq(t1, t2) => Eq(go(t1), go(t2)),
+            Ne(t1, t2) => Ne(go(t1), go(t2)),
+            Lt(t1, t2) => Lt(go(t1), go(t2)),
+            Le(t1, t2) => Le(go(t1), go(t2)),
+            Gt(t1, t2) => Gt(go(t1), go(t2)),
+            Ge(t1, t2) => Ge(go(t1), go(t2)),
+            And(t1, t2) => And(go(t1), go(t2)),
+            Or(t1, t2) => Or(go(t1), go(t2)),
+            Not(t) => Not(go(t)),
+            Pair(t1, t2) => Pair(go(t1), go(t2)),
+            Cons(t1, t2) => Cons(go(t1), go(t2)),
+            Append(t1, t2) => Append(go(t1), go(t2)),
+            Fst(t) => Fst(go(t)),
+            Snd(t) => Snd(go(t)),
+            Tuple(ts) => Tuple(ts.into_iter().map(|t| t.subst_type_vars(s)).collect()),
+            Proj(t, i) => Proj(go(t), i),
+            Unfold(t) => Unfold(go(t)),
+            Record(fields) => Record(
+                fields
+                    .into_iter()
+                    .map(|(l, t)| (l, t.subst_type_vars(s)))
+                    .collect(),
+            ),
+            Field(t, l) => Field(go(t), l),
+            Update(t, fields) => Update(
+                go(t),
+                fields
+                    .into_iter()
+                    .map(|(l, t)| (l, t.subst_type_vars(s)))
+                    .collect(),
+            ),
+            Fix(t) => Fix(go(t)),
+            Concat(t1, t2) => Concat(go(t1), go(t2)),
+            StrLen(t) => StrLen(go(t)),
+            CharAt(t1, t2) => CharAt(go(t1), go(t2)),
+            IntToString(t) => IntToString(go(t)),
             Ite {
                 cond,
                 if_true,
                 if_false,
             } => Ite {
-                cond: Box::new(cond.subst(x, v.clone())),
-                if_true: Box::new(if_true.subst(x, v.clone())),
-                if_false: Box::new(if_false.subst(x, v)),
+                cond: go(cond),
+                if_true: go(if_true),
+                if_false: go(if_false),
+            },
+            LCase {
+                t,
+                nil_t,
+                head_var,
+                tail_var,
+                cons_t,
+            } => LCase {
+                t: go(t),
+                nil_t: go(nil_t),
+                head_var,
+                tail_var,
+                cons_t: go(cons_t),
+            },
+            Case {
+                t,
+                inl_var,
+                inl_t,
+                inr_var,
+                inr_t,
+            } => Case {
+                t: go(t),
+                inl_var,
+                inl_t: go(inl_t),
+                inr_var,
+                inr_t: go(inr_t),
+            },
+            Match { t, arms } => Match {
+                t: go(t),
+                arms: arms
+                    .into_iter()
+                    .map(|(l, var, t)| (l, var, t.subst_type_vars(s)))
+                    .collect(),
             },
-            /*
+        }
+    }
+
+    /// Substitutes the terms of `s` whose free variables are `fv`, renaming binders to names
+    /// outside `avoid`, see [`under_binders`].
+    fn subst_map(
+        self,
+        s: &Substitution,
+        fv: &HashSet<String>,
+        avoid: &mut HashSet<String>,
+    ) -> Self {
+        let mut go = |t: Box<Term>| Box::new(t.subst_map(s, fv, avoid));
+        match self {
+            Var(y) => s.get(&y).cloned().unwrap_or(Var(y)),
+            Abs { var, ty, body } => {
+                let (var, body) = subst_under(var, *body, s, fv, avoid);
+                Abs {
+                    var,
+                    ty,
+                    body: Box::new(body),
+                }
+            }
+            App(t1, t2) => App(go(t1), go(t2)),
+
+            Let { var, val_t, body } => {
+                let val_t = go(val_t);
+                let (var, body) = subst_under(var, *body, s, fv, avoid);
+                Let {
+                    var,
+                    val_t,
+                    body: Box::new(body),
+                }
+            }
+            LetRec { bindings, body } => {
+                // The names scope over every binding and the body, so a name capturing a free
+                // variable of `s` is renamed in all of them at once
+                let mut vars: Vec<String> = bindings.iter().map(|(var, ..)| var.clone()).collect();
+                let Some((s, fv)) = under_binders(&mut vars, s, fv, avoid) else {
+                    return LetRec { bindings, body };
+                };
+                LetRec {
+                    bindings: bindings
+                        .into_iter()
+                        .zip(vars)
+                        .map(|((_, ty, t), var)| (var, ty, t.subst_map(&s, &fv, avoid)))
+                        .collect(),
+                    body: Box::new(body.subst_map(&s, &fv, avoid)),
+                }
+            }
 
-            Add(t1, t2) => todo!(),
-            Sub(t1, t2) => todo!(),
-            Mul(t1, t2) => todo!(),
+            Ite {
+                cond,
+                if_true,
+                if_false,
+            } => Ite {
+                cond: go(cond),
+                if_true: go(if_true),
+                if_false: go(if_false),
+            },
 
-            Eq(t1, t2) => todo!(),
-            Ne(t1, t2) => todo!(),
-            Lt(t1, t2) => todo!(),
-            Le(t1, t2) => todo!(),
-            Gt(t1, t2) => todo!(),
-            Ge(t1, t2) => todo!(),
+            Add(t1, t2) => Add(go(t1), go(t2)),
+            Sub(t1, t2) => Sub(go(t1), go(t2)),
+            Mul(t1, t2) => Mul(go(t1), go(t2)),
+            Div(t1, t2) => Div(go(t1), go(t2)),
+            Mod(t1, t2) => Mod(go(t1), go(t2)),
+            Neg(t) => Neg(go(t)),
 
-            */
-            Add(t1, t2) => Add(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Sub(t1, t2) => Sub(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Mul(t1, t2) => Mul(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
+            Eq(t1, t2) => Eq(go(t1), go(t2)),
+            Ne(t1, t2) => Ne(go(t1), go(t2)),
+            Lt(t1, t2) => Lt(go(t1), go(t2)),
+            Le(t1, t2) => Le(go(t1), go(t2)),
+            Gt(t1, t2) => Gt(go(t1), go(t2)),
+            Ge(t1, t2) => Ge(go(t1), go(t2)),
+            And(t1, t2) => And(go(t1), go(t2)),
+            Or(t1, t2) => Or(go(t1), go(t2)),
+            Not(t) => Not(go(t)),
 
-            Eq(t1, t2) => Eq(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Ne(t1, t2) => Ne(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Lt(t1, t2) => Lt(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Le(t1, t2) => Le(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Gt(t1, t2) => Gt(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Ge(t1, t2) => Ge(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
+            // ===========================String stuff===========================
 
-             // ============================Pair stuff============================
+            Concat(t1, t2) => Concat(go(t1), go(t2)),
+            StrLen(t) => StrLen(go(t)),
+            CharAt(t1, t2) => CharAt(go(t1), go(t2)),
+            IntToString(t) => IntToString(go(t)),
 
+            // ============================Pair stuff============================
 
-            Pair(t1, t2) => Pair(Box::new(t1.subst(x, v.clone())), Box::new(t2.subst(x, v))),
-            Fst(t) => Fst(Box::new(t.subst(x, v))),
-            Snd(t) => Snd(Box::new(t.subst(x, v))),
+            Pair(t1, t2) => Pair(go(t1), go(t2)),
+            Fst(t) => Fst(go(t)),
+            Snd(t) => Snd(go(t)),
+            Tuple(ts) => Tuple(ts.into_iter().map(|t| t.subst_map(s, fv, avoid)).collect()),
+            Proj(t, i) => Proj(go(t), i),
+            Record(fields) => Record(
+                fields
+                    .into_iter()
+                    .map(|(l, t)| (l, t.subst_map(s, fv, avoid)))
+                    .collect(),
+            ),
+            Field(t, l) => Field(go(t), l),
+            Update(t, fields) => Update(
+                go(t),
+                fields
+                    .into_iter()
+                    .map(|(l, t)| (l, t.subst_map(s, fv, avoid)))
+                    .collect(),
+            ),
 
             // ============================List stuff============================
 
             Nil(_) => self,
-            Cons(h, t) => Cons(Box::new(h.subst(x, v.clone())), Box::new(t.subst(x, v))),
+            Cons(h, t) => Cons(go(h), go(t)),
+            Append(t1, t2) => Append(go(t1), go(t2)),
 
             LCase { t, nil_t, head_var, tail_var, cons_t } => {
-                let new_t = Box::new(t.subst(x, v.clone()));
-                let new_nil_t = Box::new(nil_t.subst(x, v.clone()));
-                let new_cons_t = if x != head_var && x != tail_var {
-                    Box::new(cons_t.subst(x, v))
-                } else {
-                    cons_t
+                let t = go(t);
+                let nil_t = go(nil_t);
+                let mut vars = [head_var, tail_var];
+                let cons_t = match under_binders(&mut vars, s, fv, avoid) {
+                    Some((s, fv)) => Box::new(cons_t.subst_map(&s, &fv, avoid)),
+                    None => cons_t,
                 };
-
+                let [head_var, tail_var] = vars;
                 LCase {
-                    t: new_t,
-                    nil_t: new_nil_t,
+                    t,
+                    nil_t,
                     head_var,
                     tail_var,
-                    cons_t: new_cons_t,
+                    cons_t,
                 }
             }
-            
+
              // ============================Sum stuff============================
 
-            Inl(t, ty) => Inl(Box::new(t.subst(x, v)), ty),
-            Inr(t, ty) => Inr(Box::new(t.subst(x, v)), ty),
+            Inl(t, ty) => Inl(go(t), ty),
+            Inr(t, ty) => Inr(go(t), ty),
 
             Case { t, inl_var, inl_t, inr_var, inr_t } => {
-                let new_t = Box::new(t.subst(x, v.clone()));
-                let new_inl_t = if x != inl_var {
-                    Box::new(inl_t.subst(x, v.clone()))
-                } else {
-                    inl_t
-                };
-                let new_inr_t = if x != inr_var {
-                    Box::new(inr_t.subst(x, v))
-                } else {
-                    inr_t
-                };
-
+                let t = go(t);
+                let (inl_var, inl_t) = subst_under(inl_var, *inl_t, s, fv, avoid);
+                let (inr_var, inr_t) = subst_under(inr_var, *inr_t, s, fv, avoid);
                 Case {
-                    t: new_t,
+                    t,
                     inl_var,
-                    inl_t: new_inl_t,
+                    inl_t: Box::new(inl_t),
                     inr_var,
-                    inr_t: new_inr_t,
+                    inr_t: Box::new(inr_t),
                 }
             }
 
+            // ===========================Variant stuff===========================
+
+            Tag(l, t, ty) => Tag(l, go(t), ty),
+            Match { t, arms } => Match {
+                t: go(t),
+                arms: arms
+                    .into_iter()
+                    .map(|(l, var, arm)| {
+                        let (var, arm) = subst_under(var, arm, s, fv, avoid);
+                        (l, var, arm)
+                    })
+                    .collect(),
+            },
+
             // ============================Fix stuff============================
 
-            Fix(inner) => Fix(Box::new(inner.subst(x, v))),
+            Fix(inner) => Fix(go(inner)),
+
+            // ========================Polymorphism stuff========================
+
+            TAbs { var, body } => {
+                // Type variables of `s` must not be captured by the type abstraction either
+                let captured: HashSet<String> = s.values().flat_map(Term::free_type_vars).collect();
+                let (var, body) = if captured.contains(&var) {
+                    let mut avoid = body.free_type_vars();
+                    avoid.extend(captured);
+                    let fresh = fresh_name(&var, &avoid);
+                    let body = body.subst_type(&var, &Type::Var(fresh.clone()));
+                    (fresh, body)
+                } else {
+                    (var, *body)
+                };
+                TAbs {
+                    var,
+                    body: Box::new(body.subst_map(s, fv, avoid)),
+                }
+            }
+            TApp(t, ty) => TApp(go(t), ty),
+
+            // ==========================Data type stuff==========================
+
+            Data { name, ty, body } => Data {
+                name,
+                ty,
+                body: go(body),
+            },
 
+            // =======================Recursive type stuff=======================
 
+            Fold(t, ty) => Fold(go(t), ty),
+            Unfold(t) => Unfold(go(t)),
 
             _ => self,
         }
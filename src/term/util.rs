@@ -1,5 +1,7 @@
 pub use super::Term::{self, *};
 pub use crate::r#type::util::*;
+// Term constructors take precedence over type constructors of the same name
+pub use super::Term::{Char, Record, Tuple, Unit, Var};
 
 pub fn var(name: impl ToString) -> Term {
     Var(name.to_string())
@@ -15,7 +17,7 @@ impl From<&str> for Box<Term> {
 // Implicitly converts integers to integer terms
 impl From<i32> for Box<Term> {
     fn from(int: i32) -> Self {
-        Box::new(Int(int))
+        Box::new(Int(int.into()))
     }
 }
 
@@ -36,6 +38,27 @@ pub fn letin(var: impl ToString, val_t: impl Into<Box<Term>>, body: impl Into<Bo
         body: body.into(),
     }
 }
+pub fn ite(
+    cond: impl Into<Box<Term>>,
+    if_true: impl Into<Box<Term>>,
+    if_false: impl Into<Box<Term>>,
+) -> Term {
+    Ite {
+        cond: cond.into(),
+        if_true: if_true.into(),
+        if_false: if_false.into(),
+    }
+}
+
+pub fn and(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    And(t1.into(), t2.into())
+}
+pub fn or(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    Or(t1.into(), t2.into())
+}
+pub fn not(t: impl Into<Box<Term>>) -> Term {
+    Not(t.into())
+}
 
 pub fn add(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
     Add(t1.into(), t2.into())
@@ -46,6 +69,15 @@ pub fn sub(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
 pub fn mul(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
     Mul(t1.into(), t2.into())
 }
+pub fn div(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    Div(t1.into(), t2.into())
+}
+pub fn rem(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    Mod(t1.into(), t2.into())
+}
+pub fn neg(t: impl Into<Box<Term>>) -> Term {
+    Neg(t.into())
+}
 pub fn eq(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
     Eq(t1.into(), t2.into())
 }
@@ -68,6 +100,24 @@ pub fn ge(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
 pub fn pair(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
     Pair(t1.into(), t2.into())
 }
+pub fn tuple<T: Into<Term>>(ts: impl IntoIterator<Item = T>) -> Term {
+    Tuple(ts.into_iter().map(Into::into).collect())
+}
+pub fn proj(t: impl Into<Box<Term>>, i: usize) -> Term {
+    Proj(t.into(), i)
+}
+pub fn record<S: ToString, T: Into<Term>>(fields: impl IntoIterator<Item = (S, T)>) -> Term {
+    Record(fields.into_iter().map(|(l, t)| (l.to_string(), t.into())).collect())
+}
+pub fn field(t: impl Into<Box<Term>>, l: impl ToString) -> Term {
+    Field(t.into(), l.to_string())
+}
+pub fn update<S: ToString, T: Into<Term>>(
+    t: impl Into<Box<Term>>,
+    fields: impl IntoIterator<Item = (S, T)>,
+) -> Term {
+    Update(t.into(), fields.into_iter().map(|(l, t)| (l.to_string(), t.into())).collect())
+}
 pub fn fst(t: impl Into<Box<Term>>) -> Term {
     Fst(t.into())
 }
@@ -78,6 +128,9 @@ pub fn snd(t: impl Into<Box<Term>>) -> Term {
 pub fn cons(head: impl Into<Box<Term>>, tail: impl Into<Box<Term>>) -> Term {
     Cons(head.into(), tail.into())
 }
+pub fn append(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    Append(t1.into(), t2.into())
+}
 pub fn lcase(
     t: impl Into<Box<Term>>,
     nil_t: impl Into<Box<Term>>,
@@ -117,10 +170,104 @@ pub fn case(
     }
 }
 
+pub fn tag(l: impl ToString, t: impl Into<Box<Term>>, ty: impl Into<Type>) -> Term {
+    Tag(l.to_string(), t.into(), ty.into())
+}
+/// Case analysis `match t of | l1 var1 => t1 | ...` of a variant, with the arms `(l1, var1, t1)`.
+pub fn match_variant<L: ToString, V: ToString, T: Into<Term>>(
+    t: impl Into<Box<Term>>,
+    arms: impl IntoIterator<Item = (L, V, T)>,
+) -> Term {
+    Match {
+        t: t.into(),
+        arms: arms
+            .into_iter()
+            .map(|(l, var, t)| (l.to_string(), var.to_string(), t.into()))
+            .collect(),
+    }
+}
+
+/// Sequencing `t1; t2`, which is sugar for `(fun _ : Unit, t2) t1`.
+pub fn seq(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    App(
+        Box::new(Abs {
+            var: "_".to_string(),
+            ty: Type::Unit,
+            body: t2.into(),
+        }),
+        t1.into(),
+    )
+}
+
+/// Recursive bindings `let rec f : T = t and g : U = u in body`, which may refer to each other.
+///
+/// ```rust
+/// # use stlc_project::term::util::*;
+/// let even = abs("n", Integer, ite(eq("n", 0), True, app("odd", sub("n", 1))));
+/// let odd = abs("n", Integer, ite(eq("n", 0), False, app("even", sub("n", 1))));
+/// let ty = arrow(Integer, Boolean);
+/// let t = letrec([("even", ty.clone(), even), ("odd", ty, odd)], app("even", 4));
+/// assert_eq!(t.type_check(), Ok(Boolean));
+/// assert_eq!(t.multistep(), True);
+/// ```
+pub fn letrec<V: ToString, T: Into<Term>>(
+    bindings: impl IntoIterator<Item = (V, Type, T)>,
+    body: impl Into<Box<Term>>,
+) -> Term {
+    LetRec {
+        bindings: bindings
+            .into_iter()
+            .map(|(var, ty, t)| (var.to_string(), ty, t.into()))
+            .collect(),
+        body: body.into(),
+    }
+}
+
+pub fn str(s: impl ToString) -> Term {
+    Str(s.to_string())
+}
+pub fn concat(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    Concat(t1.into(), t2.into())
+}
+pub fn strlen(t: impl Into<Box<Term>>) -> Term {
+    StrLen(t.into())
+}
+pub fn charat(t1: impl Into<Box<Term>>, t2: impl Into<Box<Term>>) -> Term {
+    CharAt(t1.into(), t2.into())
+}
+pub fn itos(t: impl Into<Box<Term>>) -> Term {
+    IntToString(t.into())
+}
+
 pub fn fix(t: impl Into<Box<Term>>) -> Term {
     Fix(t.into())
 }
 
+pub fn tabs(var: impl ToString, body: impl Into<Box<Term>>) -> Term {
+    TAbs {
+        var: var.to_string(),
+        body: body.into(),
+    }
+}
+pub fn tapp(t: impl Into<Box<Term>>, ty: impl Into<Type>) -> Term {
+    TApp(t.into(), ty.into())
+}
+
+pub fn data(name: impl ToString, ty: impl Into<Type>, body: impl Into<Box<Term>>) -> Term {
+    Data {
+        name: name.to_string(),
+        ty: ty.into(),
+        body: body.into(),
+    }
+}
+
+pub fn fold(t: impl Into<Box<Term>>, ty: impl Into<Type>) -> Term {
+    Fold(t.into(), ty.into())
+}
+pub fn unfold(t: impl Into<Box<Term>>) -> Term {
+    Unfold(t.into())
+}
+
 pub fn id2() -> Term {
     abs("x", Boolean, "x")
 }
@@ -1,30 +1,425 @@
 pub mod check;
 mod display;
 pub mod parse;
+mod unify;
 pub mod util;
 
+use std::collections::{HashMap, HashSet};
+
+use crate::term::{subst::fresh_name, Term};
+
 #[non_exhaustive]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
+    Unit,
     Boolean,
     Integer,
+    String,
+    Char,
 
     /// Type of abstractions
     Arrow(Box<Type>, Box<Type>),
     /// Product type (Pair)
     Prod(Box<Type>, Box<Type>),
+    /// Type of tuples of three or more components
+    Tuple(Vec<Type>),
+    /// Type of records, with the fields sorted by their labels
+    Record(Vec<(String, Type)>),
     /// Type of lists
     List(Box<Type>),
     /// Type of sums
     Sum(Box<Type>, Box<Type>),
+    /// Type of labelled variants, with the labels sorted
+    Variant(Vec<(String, Type)>),
+
+    /// A missing annotation, written `_` or left out, whose type is inferred
+    Hole,
+    /// A type variable introduced by inference, standing for a yet unknown type
+    Meta(usize),
+    /// A named type variable, written in lowercase
+    Var(String),
+    /// A polymorphic type `forall a b. T`, quantifying over the type variables of `T`
+    Forall(Vec<String>, Box<Type>),
+    /// A data type declared with `data`, written with a capitalized name, see [`Term::Data`]
+    Named(String),
+    /// An iso-recursive type `μa. T`, in which `a` stands for the whole type,
+    /// see [`Term::Fold`] and [`Term::Unfold`]
+    Rec(String, Box<Type>),
+}
+
+impl Type {
+    /// Determines whether values of the type can be compared with `==` and `!=`.
+    ///
+    /// Unit, booleans, integers, strings, characters, and pairs, tuples, records, lists, sums,
+    /// variants and recursive types of comparable types are comparable,
+    /// abstractions and type abstractions are not. Neither are type variables, which could stand
+    /// for an abstraction, except the ones standing for a recursive type.
+    /// Types which are not known yet and data types are assumed to be comparable,
+    /// see [`Type::is_comparable_in`] to look the data types up.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::r#type::util::*;
+    /// assert!(prod(Integer, list(sum(Boolean, Integer))).is_comparable());
+    /// assert!(!list(arrow(Integer, Integer)).is_comparable());
+    /// assert!(!tvar("a").is_comparable());
+    /// ```
+    pub fn is_comparable(&self) -> bool {
+        self.is_comparable_in(&[])
+    }
+
+    /// Determines whether values of the type can be compared like [`Type::is_comparable`], with
+    /// the definitions of the data types in scope in `data`, innermost last.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::r#type::util::*;
+    /// let data = [("F".to_string(), variant([("f", arrow(Integer, Integer))]))];
+    /// assert!(named("F").is_comparable());
+    /// assert!(!named("F").is_comparable_in(&data));
+    /// ```
+    pub fn is_comparable_in(&self, data: &[(String, Type)]) -> bool {
+        fn go(ty: &Type, data: &[(String, Type)], bound: &mut Vec<String>) -> bool {
+            match ty {
+                Type::Arrow(..) | Type::Forall(..) => false,
+                Type::Var(a) => bound.contains(a),
+                // The definition is checked with the data types in scope where it is declared,
+                // and its recursive occurrences are assumed to be comparable
+                Type::Named(name) => match data.iter().rposition(|(other, _)| other == name) {
+                    Some(i) => go(&data[i].1, &data[..i], &mut Vec::new()),
+                    None => true,
+                },
+                Type::Rec(a, ty) => {
+                    bound.push(a.clone());
+                    let comparable = go(ty, data, bound);
+                    bound.pop();
+                    comparable
+                }
+                ty => ty.children().into_iter().all(|ty| go(ty, data, bound)),
+            }
+        }
+        go(self, data, &mut Vec::new())
+    }
+
+    /// Returns the types directly inside `self`.
+    pub(crate) fn children(&self) -> Vec<&Type> {
+        match self {
+            Type::Unit
+            | Type::Boolean
+            | Type::Integer
+            | Type::String
+            | Type::Char
+            | Type::Hole | Type::Meta(_) | Type::Var(_) | Type::Named(_) => vec![],
+            Type::List(ty) | Type::Forall(_, ty) | Type::Rec(_, ty) => vec![ty],
+            Type::Arrow(ty1, ty2) | Type::Prod(ty1, ty2) | Type::Sum(ty1, ty2) => vec![ty1, ty2],
+            Type::Tuple(tys) => tys.iter().collect(),
+            Type::Record(fields) | Type::Variant(fields) => fields.iter().map(|(_, ty)| ty).collect(),
+        }
+    }
+
+    /// Rebuilds `self` with `f` applied to the types directly inside it.
+    pub(crate) fn map_children(&self, mut f: impl FnMut(&Type) -> Type) -> Type {
+        match self {
+            Type::Unit
+            | Type::Boolean
+            | Type::Integer
+            | Type::String
+            | Type::Char
+            | Type::Hole | Type::Meta(_) | Type::Var(_) | Type::Named(_) => {
+                self.clone()
+            }
+            Type::Arrow(ty1, ty2) => Type::Arrow(Box::new(f(ty1)), Box::new(f(ty2))),
+            Type::Prod(ty1, ty2) => Type::Prod(Box::new(f(ty1)), Box::new(f(ty2))),
+            Type::Tuple(tys) => Type::Tuple(tys.iter().map(f).collect()),
+            Type::Record(fields) => {
+                Type::Record(fields.iter().map(|(l, ty)| (l.clone(), f(ty))).collect())
+            }
+            Type::Variant(fields) => {
+                Type::Variant(fields.iter().map(|(l, ty)| (l.clone(), f(ty))).collect())
+            }
+            Type::List(ty) => Type::List(Box::new(f(ty))),
+            Type::Sum(ty1, ty2) => Type::Sum(Box::new(f(ty1)), Box::new(f(ty2))),
+            Type::Forall(vars, ty) => Type::Forall(vars.clone(), Box::new(f(ty))),
+            Type::Rec(var, ty) => Type::Rec(var.clone(), Box::new(f(ty))),
+        }
+    }
+
+    /// Returns the type variables of `self` not bound by a [`Type::Forall`] or [`Type::Rec`],
+    /// in the order they appear.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::r#type::util::*;
+    /// let ty = arrow(tvar("a"), forall(["b"], prod(tvar("b"), tvar("c"))));
+    /// assert_eq!(ty.free_type_vars(), ["a", "c"]);
+    /// ```
+    pub fn free_type_vars(&self) -> Vec<String> {
+        fn go(ty: &Type, bound: &mut Vec<String>, free: &mut Vec<String>) {
+            match ty {
+                Type::Var(a) => {
+                    if !bound.contains(a) && !free.contains(a) {
+                        free.push(a.clone());
+                    }
+                }
+                Type::Forall(vars, ty) => {
+                    bound.extend(vars.iter().cloned());
+                    go(ty, bound, free);
+                    bound.truncate(bound.len() - vars.len());
+                }
+                Type::Rec(var, ty) => {
+                    bound.push(var.clone());
+                    go(ty, bound, free);
+                    bound.pop();
+                }
+                ty => ty.children().into_iter().for_each(|ty| go(ty, bound, free)),
+            }
+        }
+        let mut free = Vec::new();
+        go(self, &mut Vec::new(), &mut free);
+        free
+    }
+
+    /// Returns the names of the data types [`Type::Named`] mentioned in `self`.
+    pub(crate) fn names(&self) -> Vec<&str> {
+        match self {
+            Type::Named(name) => vec![name],
+            ty => ty.children().into_iter().flat_map(Type::names).collect(),
+        }
+    }
+
+    /// Returns a label which occurs twice in a record or variant type inside `self`, if any.
+    pub(crate) fn duplicate_label(&self) -> Option<&str> {
+        match self {
+            Type::Record(fields) | Type::Variant(fields) => fields
+                .iter()
+                .enumerate()
+                .find(|(i, (l, _))| fields[..*i].iter().any(|(other, _)| other == l))
+                .map(|(_, (l, _))| l.as_str())
+                .or_else(|| fields.iter().find_map(|(_, ty)| ty.duplicate_label())),
+            ty => ty.children().into_iter().find_map(Type::duplicate_label),
+        }
+    }
+
+    /// Substitutes the types of `substitution` for the free type variables of `self`.
+    ///
+    /// Type variables bound by a [`Type::Forall`] or [`Type::Rec`] are renamed if they would capture a type
+    /// variable of a substituted type.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use stlc_project::r#type::util::*;
+    /// let ty = forall(["b"], arrow(tvar("a"), tvar("b")));
+    /// let substitution = HashMap::from([("a".to_string(), list(tvar("b")))]);
+    /// assert_eq!(
+    ///     ty.subst_type_vars(&substitution),
+    ///     forall(["b1"], arrow(list(tvar("b")), tvar("b1")))
+    /// );
+    /// ```
+    pub fn subst_type_vars(&self, substitution: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Var(a) => substitution.get(a).cloned().unwrap_or_else(|| self.clone()),
+            Type::Forall(vars, ty) => {
+                let (vars, ty) = subst_binder(vars, ty, substitution);
+                Type::Forall(vars, Box::new(ty))
+            }
+            Type::Rec(var, ty) => {
+                let (mut vars, ty) = subst_binder(std::slice::from_ref(var), ty, substitution);
+                Type::Rec(vars.remove(0), Box::new(ty))
+            }
+            ty => ty.map_children(|ty| ty.subst_type_vars(substitution)),
+        }
+    }
+
+    /// Unrolls the recursive type `μa. T` once, to `T` with `μa. T` substituted for `a`.
+    ///
+    /// Returns `None` if `self` is not a [`Type::Rec`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::r#type::util::*;
+    /// let nat = rec("a", sum(Unit, tvar("a")));
+    /// assert_eq!(nat.unroll(), Some(sum(Unit, nat)));
+    /// ```
+    pub fn unroll(&self) -> Option<Type> {
+        match self {
+            Type::Rec(var, ty) => {
+                Some(ty.subst_type_vars(&HashMap::from([(var.clone(), self.clone())])))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Substitutes into `ty` under the binders `vars`, renaming the binders which would capture a
+/// type variable of a substituted type.
+fn subst_binder(
+    vars: &[String],
+    ty: &Type,
+    substitution: &HashMap<String, Type>,
+) -> (Vec<String>, Type) {
+    let mut substitution = substitution.clone();
+    substitution.retain(|a, _| !vars.contains(a));
+    let captured: HashSet<String> = substitution
+        .values()
+        .flat_map(Type::free_type_vars)
+        .collect();
+    let mut avoid: HashSet<String> = captured.clone();
+    avoid.extend(ty.free_type_vars());
+    avoid.extend(vars.iter().cloned());
+    let vars = vars
+        .iter()
+        .map(|a| {
+            if captured.contains(a) {
+                let fresh = fresh_name(a, &avoid);
+                avoid.insert(fresh.clone());
+                substitution.insert(a.clone(), Type::Var(fresh.clone()));
+                fresh
+            } else {
+                a.clone()
+            }
+        })
+        .collect();
+    (vars, ty.subst_type_vars(&substitution))
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeError {
     UndefinedVariable(String),
-    WrongAppTypeRight(Type),
-    WrongAppTypeLeft(Type),
-    Fail,
+    /// An annotation mentions a type variable which is not bound by a type abstraction.
+    UndefinedTypeVariable(String),
+    /// An annotation mentions a data type which is not declared.
+    UndefinedType(String),
+    /// An annotation has a record or variant type with two fields of the same label.
+    DuplicateLabel { label: String, ty: Type },
+    /// The left hand side of an application is not a function.
+    WrongAppTypeLeft {
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// The argument of an application does not have the type of the domain of the function.
+    ArgumentMismatch {
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// Inference would need an infinite type, where the metavariable `var` equals `ty` containing it.
+    OccursCheck {
+        var: Type,
+        ty: Type,
+        term: Box<Term>,
+    },
+    /// Equality on a type whose values cannot be compared, holds the type of the operands.
+    NotComparable { actual: Type, term: Box<Term> },
+    /// The branches of an if-then-else, `lcase` or `case` have different types.
+    BranchMismatch {
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// The condition of an if-then-else or an operand of a boolean connective is not a boolean.
+    ExpectedBoolean { actual: Type, term: Box<Term> },
+    /// An operand of an arithmetic operator is not an integer.
+    ExpectedInteger { actual: Type, term: Box<Term> },
+    /// An operand of a string operation is not a string.
+    ExpectedString { actual: Type, term: Box<Term> },
+    /// An operand of an ordering operator is not an integer, a character or a string.
+    NotOrdered { actual: Type, term: Box<Term> },
+    /// The operands of a binary operator such as `==` or `++` have different types.
+    OperandMismatch {
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// `fst` or `snd` of a term that is not a product.
+    NotAProduct { actual: Type, term: Box<Term> },
+    /// A record or record update with two fields of the same label.
+    DuplicateField { field: String, term: Box<Term> },
+    /// Field access or update of a term that is not a record with the field.
+    NoSuchField {
+        field: String,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// A record update with a value whose type differs from the type of the field.
+    FieldMismatch {
+        field: String,
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// Projection of a term that is not a tuple or product with a component at `index`.
+    NotATuple {
+        index: usize,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// `lcase` of a term that is not a list.
+    NotAList { actual: Type, term: Box<Term> },
+    /// `case` of a term that is not a sum.
+    NotASum { actual: Type, term: Box<Term> },
+    /// `match` of a term that is not a variant, or `tag` annotated with a type that is not a variant.
+    NotAVariant { actual: Type, term: Box<Term> },
+    /// `tag` with a label which is not one of the annotated variant type.
+    NoSuchLabel {
+        label: String,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// A `match` without an arm for a label of the variant type.
+    MissingArm {
+        label: String,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// A `match` with a second arm for a label, or with an arm for a label not in the variant type.
+    RedundantArm {
+        label: String,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// The tail of a `cons` is not a list of the type of the head.
+    ConsMismatch {
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// `fix` of a term that is not an abstraction.
+    FixNotAFunction { actual: Type, term: Box<Term> },
+    /// `fix` of an abstraction whose domain and codomain differ.
+    FixMismatch {
+        domain: Type,
+        codomain: Type,
+        term: Box<Term>,
+    },
+    /// The body of a recursive binding of a `let rec` or of a module declaration does not have the
+    /// annotated type of the binding.
+    LetRecMismatch {
+        var: String,
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
+    /// A `let rec` binding the same variable twice.
+    DuplicateBinding { var: String, term: Box<Term> },
+    /// Type application of a term whose type is not polymorphic.
+    NotAForall { actual: Type, term: Box<Term> },
+    /// The type variable of a type abstraction would leak into the type of a variable bound outside it.
+    TypeVariableEscape { var: String, term: Box<Term> },
+    /// `fold` annotated with, or `unfold` of a term of, a type that is not recursive.
+    NotARecursiveType { actual: Type, term: Box<Term> },
+    /// The term of a `fold` does not have the unrolled annotated type.
+    FoldMismatch {
+        expected: Type,
+        actual: Type,
+        term: Box<Term>,
+    },
 }
 
 use TypeError::*;
@@ -33,9 +428,164 @@ impl std::fmt::Display for TypeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UndefinedVariable(x) => write!(f, "undefined variable: {x}"),
-            WrongAppTypeRight(ty) => write!(f, "wrong app type right: {ty:?}"),
-            WrongAppTypeLeft(ty) => write!(f, "wrong app type left: {ty:?}"),
-            Fail => write!(f, "type error"),
+            UndefinedTypeVariable(a) => write!(f, "undefined type variable: {a}"),
+            UndefinedType(name) => write!(f, "undefined type: {name}"),
+            DuplicateLabel { label, ty } => write!(f, "duplicate label {label} in {ty}"),
+            WrongAppTypeLeft {
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected a function {expected}, found {actual} in {term}"
+            ),
+            ArgumentMismatch {
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "wrong argument type: expected {expected}, found {actual} in {term}"
+            ),
+            OccursCheck { var, ty, term } => {
+                write!(f, "cannot construct the infinite type {var} = {ty} in {term}")
+            }
+            NotComparable { actual, term } => {
+                write!(f, "cannot compare values of type {actual} in {term}")
+            }
+            BranchMismatch {
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "branches have different types: expected {expected}, found {actual} in {term}"
+            ),
+            ExpectedBoolean { actual, term } => {
+                write!(
+                    f,
+                    "expected condition or operand of type {}, found {actual} in {term}",
+                    Type::Boolean
+                )
+            }
+            ExpectedInteger { actual, term } => {
+                write!(f, "expected operand of type {}, found {actual} in {term}", Type::Integer)
+            }
+            ExpectedString { actual, term } => {
+                write!(f, "expected operand of type {}, found {actual} in {term}", Type::String)
+            }
+            NotOrdered { actual, term } => write!(
+                f,
+                "expected operand of type {}, {} or {}, found {actual} in {term}",
+                Type::Integer,
+                Type::Char,
+                Type::String
+            ),
+            OperandMismatch {
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "operands have different types: expected {expected}, found {actual} in {term}"
+            ),
+            NotAProduct { actual, term } => {
+                write!(f, "expected a product, found {actual} in {term}")
+            }
+            DuplicateField { field, term } => write!(f, "duplicate field {field} in {term}"),
+            NoSuchField {
+                field,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected a record with a field {field}, found {actual} in {term}"
+            ),
+            FieldMismatch {
+                field,
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "wrong type of field {field}: expected {expected}, found {actual} in {term}"
+            ),
+            NotATuple {
+                index,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected a tuple with a component {index}, found {actual} in {term}"
+            ),
+            NotAList { actual, term } => write!(f, "expected a list, found {actual} in {term}"),
+            NotASum { actual, term } => write!(f, "expected a sum, found {actual} in {term}"),
+            NotAVariant { actual, term } => write!(f, "expected a variant, found {actual} in {term}"),
+            NoSuchLabel {
+                label,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected a variant with a label {label}, found {actual} in {term}"
+            ),
+            MissingArm {
+                label,
+                actual,
+                term,
+            } => write!(f, "missing arm for label {label} of {actual} in {term}"),
+            RedundantArm {
+                label,
+                actual,
+                term,
+            } => write!(f, "redundant arm for label {label} of {actual} in {term}"),
+            ConsMismatch {
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected tail of type {expected}, found {actual} in {term}"
+            ),
+            FixNotAFunction { actual, term } => {
+                write!(f, "expected fix of a function, found {actual} in {term}")
+            }
+            FixMismatch {
+                domain,
+                codomain,
+                term,
+            } => write!(
+                f,
+                "expected fix of a function with equal domain and codomain, found {} in {term}",
+                Type::Arrow(Box::new(domain.clone()), Box::new(codomain.clone()))
+            ),
+            LetRecMismatch {
+                var,
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected {var} to have type {expected} as annotated, found {actual} in {term}"
+            ),
+            DuplicateBinding { var, term } => write!(f, "duplicate binding {var} in {term}"),
+            NotAForall { actual, term } => {
+                write!(f, "expected a polymorphic type, found {actual} in {term}")
+            }
+            TypeVariableEscape { var, term } => {
+                write!(f, "type variable {var} escapes its scope in {term}")
+            }
+            NotARecursiveType { actual, term } => {
+                write!(f, "expected a recursive type, found {actual} in {term}")
+            }
+            FoldMismatch {
+                expected,
+                actual,
+                term,
+            } => write!(
+                f,
+                "expected fold of type {expected}, found {actual} in {term}"
+            ),
         }
     }
 }
@@ -1,235 +1,1240 @@
+use std::collections::{HashMap, HashSet};
+
+use super::unify::{normalize, Unifier, UnifyError};
 use super::Type::{self, *};
 use super::{
     Context,
     TypeError::{self, *},
 };
+use crate::span::Located;
 use crate::term::Term::{self, *};
+// Term variables and values take precedence over the types of the same name
+use crate::term::Term::{Char, Record, Tuple, Unit, Var};
+// The standard string takes precedence over the string type
+use std::string::String;
 
-impl Term {
-    /// Infers the type of the term `self`.
+/// The type of a variable in scope, quantified over the type variables `vars` left undetermined
+/// by its `let` binding.
+///
+/// Unlike a [`Forall`], which is only instantiated by applying the variable to types, a scheme is
+/// instantiated with fresh metavariables wherever the variable is used. The type variables in
+/// `comparable` stand for types compared with `==` or `!=`, so they must be instantiated with
+/// comparable types.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<String>,
+    comparable: Vec<String>,
+    ty: Type,
+}
+
+impl From<Type> for Scheme {
+    fn from(ty: Type) -> Self {
+        Scheme {
+            vars: vec![],
+            comparable: vec![],
+            ty,
+        }
+    }
+}
+
+/// The schemes of the variables in scope.
+type Env = HashMap<String, Scheme>;
+
+/// The state of type inference of a term: the metavariables solved so far, the types compared with
+/// `==` and `!=` which must turn out to be comparable along with their comparison, and the type
+/// variables and data types in scope.
+#[derive(Debug, Default)]
+struct Inference<'t> {
+    unifier: Unifier,
+    comparisons: Vec<(Type, &'t Term)>,
+    type_vars: Vec<String>,
+    data: Vec<(String, Type)>,
+    /// The innermost node of the term whose type could not be inferred, once inference failed
+    at: Option<&'t Term>,
+}
+
+/// Checks that the labels of `fields` of the record or record update `term` are distinct.
+fn distinct_fields(fields: &[(String, Term)], term: &Term) -> Result<(), TypeError> {
+    for (i, (l, _)) in fields.iter().enumerate() {
+        if fields[..i].iter().any(|(l2, _)| l2 == l) {
+            return Err(DuplicateField {
+                field: l.clone(),
+                term: Box::new(term.clone()),
+            });
+        }
+    }
+    Ok(())
+}
+
+impl<'t> Inference<'t> {
+    /// Unifies the type `actual` of `term` with `expected`.
     ///
-    /// # Errors
+    /// On mismatch, `mismatch` builds the error from both types with the metavariables solved so far
+    /// substituted.
+    fn expect(
+        &mut self,
+        expected: &Type,
+        actual: &Type,
+        term: &Term,
+        mismatch: impl FnOnce(Type, Type) -> TypeError,
+    ) -> Result<(), TypeError> {
+        match self.unifier.unify(expected, actual) {
+            Ok(()) => Ok(()),
+            Err(UnifyError::Mismatch) => Err(mismatch(
+                self.unifier.resolve(expected),
+                self.unifier.resolve(actual),
+            )),
+            Err(UnifyError::Occurs(m, ty)) => Err(OccursCheck {
+                var: Meta(m),
+                ty,
+                term: Box::new(term.clone()),
+            }),
+        }
+    }
+
+    /// Infers the type of `term` and checks that it is [`Integer`].
+    fn expect_integer(&mut self, term: &'t Term, ctx: Env) -> Result<(), TypeError> {
+        let actual = self.infer(term, ctx)?;
+        self.expect(&Integer, &actual, term, |_, actual| ExpectedInteger {
+            actual,
+            term: Box::new(term.clone()),
+        })
+    }
+
+    /// Returns the type of the field `l` of the record type `ty` of `term`.
+    fn field(&self, ty: &Type, l: &str, term: &Term) -> Result<Type, TypeError> {
+        match self.unifier.shallow(ty) {
+            Type::Record(fields) => fields.iter().find(|(l2, _)| l2 == l).map(|(_, ty)| ty.clone()),
+            _ => None,
+        }
+        .ok_or_else(|| NoSuchField {
+            field: l.to_string(),
+            actual: self.unifier.resolve(ty),
+            term: Box::new(term.clone()),
+        })
+    }
+
+    /// Returns the labels and types of the variant type `ty` of the scrutinee of the `match` `term`,
+    /// checking that `arms` has exactly one arm for each of them.
     ///
-    /// - If the context doesn't contain the required variable, returns an [`UndefinedVariable`] error with the name of the variable inside.
-    /// - If the left hand side of an application is not an arrow type, returns the [`WrongAppTypeLeft`] error with the actual type inside.
-    /// - If the right hand side of an application is not of the expected type, returns the [`WrongAppTypeRight`] error with the actual type inside.
-    /// - In other typing failures, the error [`Fail`] is returned.
-    pub fn infer_type(&self, mut ctx: Context) -> Result<Type, TypeError> {
-        match self {
-            Var(x) => ctx.get(x).cloned().ok_or(UndefinedVariable(x.clone())),
+    /// A scrutinee of a type which is not known yet has the data type in scope with a constructor
+    /// named like the first arm, if any, otherwise the variant type of the labels of the arms.
+    fn variant_arms(
+        &mut self,
+        ty: &Type,
+        arms: &[(String, String, Term)],
+        term: &Term,
+    ) -> Result<Vec<(String, Type)>, TypeError> {
+        let redundant = |label: &String, actual: Type| RedundantArm {
+            label: label.clone(),
+            actual,
+            term: Box::new(term.clone()),
+        };
+        for (i, (l, _, _)) in arms.iter().enumerate() {
+            if arms[..i].iter().any(|(l2, _, _)| l2 == l) {
+                return Err(redundant(l, self.unifier.resolve(ty)));
+            }
+        }
+        // The data type with a constructor named like the first arm
+        let data = arms.first().and_then(|(l, _, _)| {
+            self.data.iter().rev().find(|(_, ty)| {
+                matches!(self.unifier.shallow(ty), Variant(fields) if fields.iter().any(|(l2, _)| l2 == l))
+            })
+        });
+        let fields = match self.unfold(ty) {
+            Variant(fields) => fields,
+            Meta(_) if let Some((name, _)) = data => {
+                let name = Named(name.clone());
+                self.unifier
+                    .unify(&name, ty)
+                    .expect("an unknown type to unify with any type");
+                let Variant(fields) = self.unfold(ty) else {
+                    return Err(NotAVariant {
+                        actual: name,
+                        term: Box::new(term.clone()),
+                    });
+                };
+                fields
+            }
+            Meta(_) => {
+                let mut fields: Vec<_> = arms
+                    .iter()
+                    .map(|(l, _, _)| (l.clone(), self.unifier.fresh()))
+                    .collect();
+                fields.sort_by(|(l1, _), (l2, _)| l1.cmp(l2));
+                self.unifier
+                    .unify(&Variant(fields.clone()), ty)
+                    .expect("an unknown type to unify with any type");
+                fields
+            }
+            _ => {
+                return Err(NotAVariant {
+                    actual: self.unifier.resolve(ty),
+                    term: Box::new(term.clone()),
+                })
+            }
+        };
+        if let Some((l, _, _)) = arms.iter().find(|(l, _, _)| fields.iter().all(|(l2, _)| l2 != l)) {
+            return Err(redundant(l, self.unifier.resolve(ty)));
+        }
+        if let Some((l, _)) = fields.iter().find(|(l, _)| arms.iter().all(|(l2, _, _)| l2 != l)) {
+            return Err(MissingArm {
+                label: l.clone(),
+                actual: self.unifier.resolve(ty),
+                term: Box::new(term.clone()),
+            });
+        }
+        Ok(fields)
+    }
+
+    /// Infers the type of `term` and checks that it is [`Boolean`].
+    fn expect_boolean(&mut self, term: &'t Term, ctx: Env) -> Result<(), TypeError> {
+        let actual = self.infer(term, ctx)?;
+        self.expect(&Boolean, &actual, term, |_, actual| ExpectedBoolean {
+            actual,
+            term: Box::new(term.clone()),
+        })
+    }
+
+    /// Infers the type of `term` and checks that it is [`Type::String`].
+    fn expect_string(&mut self, term: &'t Term, ctx: Env) -> Result<(), TypeError> {
+        let actual = self.infer(term, ctx)?;
+        self.expect(&Type::String, &actual, term, |_, actual| ExpectedString {
+            actual,
+            term: Box::new(term.clone()),
+        })
+    }
+
+    /// Checks that the type `ty` of the operand `term` of an ordering operator is [`Integer`],
+    /// [`Type::Char`] or [`Type::String`], or not known yet.
+    fn expect_ordered(&self, ty: &Type, term: &Term) -> Result<(), TypeError> {
+        match self.unifier.shallow(ty) {
+            Meta(_) | Integer | Type::Char | Type::String => Ok(()),
+            _ => Err(NotOrdered {
+                actual: self.unifier.resolve(ty),
+                term: Box::new(term.clone()),
+            }),
+        }
+    }
+
+    /// Checks that the type `actual` of the branch `term` equals the type `expected` of the previous branches.
+    fn expect_branch(&mut self, expected: Type, actual: Type, term: &Term) -> Result<Type, TypeError> {
+        self.expect(&expected, &actual, term, |expected, actual| BranchMismatch {
+            expected,
+            actual,
+            term: Box::new(term.clone()),
+        })?;
+        Ok(expected)
+    }
+
+    /// Checks that the type variables of the annotation `ty` are in scope, and replaces its holes
+    /// with fresh metavariables.
+    fn annotation(&mut self, ty: &Type) -> Result<Type, TypeError> {
+        if let Some(name) = ty
+            .names()
+            .into_iter()
+            .find(|name| self.data.iter().all(|(other, _)| other != name))
+        {
+            return Err(UndefinedType(name.to_string()));
+        }
+        if let Some(label) = ty.duplicate_label() {
+            return Err(DuplicateLabel {
+                label: label.to_string(),
+                ty: ty.clone(),
+            });
+        }
+        match ty
+            .free_type_vars()
+            .into_iter()
+            .find(|a| !self.type_vars.contains(a))
+        {
+            Some(a) => Err(UndefinedTypeVariable(a)),
+            None => Ok(self.unifier.instantiate(ty)),
+        }
+    }
+
+    /// Follows the bindings of the metavariable at the root of `ty`, and replaces a data type with
+    /// the type it stands for.
+    fn unfold(&self, ty: &Type) -> Type {
+        match self.unifier.shallow(ty) {
+            Named(name) => self
+                .data
+                .iter()
+                .rev()
+                .find(|(other, _)| other == name)
+                .map(|(_, ty)| self.unifier.shallow(ty).clone())
+                .expect("data types to be checked to be in scope"),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Infers the type of `term` with the type variables `vars` in scope.
+    fn infer_with_type_vars(
+        &mut self,
+        vars: &[String],
+        term: &'t Term,
+        ctx: Env,
+    ) -> Result<Type, TypeError> {
+        self.type_vars.extend(vars.iter().cloned());
+        let ty = self.infer(term, ctx);
+        self.type_vars.truncate(self.type_vars.len() - vars.len());
+        ty
+    }
+
+    /// Infers the types of the recursive `bindings`, i.e. their annotations, checking each of them
+    /// with all of them in scope.
+    fn infer_rec_bindings(
+        &mut self,
+        bindings: &'t [(String, Type, Term)],
+        ctx: Env,
+    ) -> Result<Vec<Type>, TypeError> {
+        // The type variables of the polymorphic annotations scope over all the bindings
+        let len = self.type_vars.len();
+        for (_, ty, _) in bindings {
+            if let Forall(vars, _) = ty {
+                self.type_vars.extend(vars.iter().cloned());
+            }
+        }
+        let doms = self.check_rec_bindings(bindings, ctx);
+        self.type_vars.truncate(len);
+        doms
+    }
+
+    fn check_rec_bindings(
+        &mut self,
+        bindings: &'t [(String, Type, Term)],
+        mut ctx: Env,
+    ) -> Result<Vec<Type>, TypeError> {
+        let mut doms = vec![];
+        for (var, ty, t) in bindings {
+            let dom = self.annotation(ty)?;
+            let scheme = match (&dom, t) {
+                // A binding annotated with a polymorphic type is instantiated implicitly, unless
+                // it is a type abstraction
+                (Forall(vars, ty), term) if !matches!(term, TAbs { .. }) => Scheme {
+                    vars: vars.clone(),
+                    comparable: vec![],
+                    ty: (**ty).clone(),
+                },
+                _ => dom.clone().into(),
+            };
+            ctx.insert(var.clone(), scheme);
+            doms.push(dom);
+        }
+
+        for ((var, _, t), dom) in bindings.iter().zip(&doms) {
+            let actual = self.infer(t, ctx.clone())?;
+            // Unless the binding is a type abstraction, the type variables of a polymorphic
+            // annotation are rigid in it
+            let expected = match (self.unifier.shallow(dom), self.unifier.shallow(&actual)) {
+                (Forall(_, ty), actual) if !matches!(actual, Forall(..)) => (**ty).clone(),
+                _ => dom.clone(),
+            };
+            self.expect(&expected, &actual, t, |expected, actual| LetRecMismatch {
+                var: var.clone(),
+                expected,
+                actual,
+                term: Box::new(t.clone()),
+            })?;
+        }
+        Ok(doms)
+    }
+
+    /// Quantifies `ty` over the metavariables which do not occur in `ctx`.
+    ///
+    /// The metavariables of compared types are quantified as comparable type variables, so that
+    /// the types they are instantiated with are checked to be comparable in turn.
+    fn generalize(&self, ty: &Type, ctx: &Env) -> Scheme {
+        let fixed = ctx
+            .values()
+            .flat_map(|scheme| self.unifier.metas(&scheme.ty))
+            .collect();
+        let (vars, ty) = self.unifier.generalize(ty, &fixed);
+        let compared: HashSet<usize> = self
+            .comparisons
+            .iter()
+            .flat_map(|(ty, _)| self.unifier.metas(ty))
+            .collect();
+        Scheme {
+            comparable: vars
+                .iter()
+                .filter(|(m, _)| compared.contains(m))
+                .map(|(_, a)| a.clone())
+                .collect(),
+            vars: vars.into_iter().map(|(_, a)| a).collect(),
+            ty,
+        }
+    }
+
+    /// Quantifies the type `dom` of the recursive binding of `t` over the metavariables which do
+    /// not occur in `ctx`, along with the type variables of its annotation unless `t` is a type
+    /// abstraction.
+    fn generalize_binding(&self, dom: &Type, t: &Term, ctx: &Env) -> Scheme {
+        match (self.unifier.shallow(dom), t) {
+            (Forall(vars, ty), term) if !matches!(term, TAbs { .. }) => {
+                let mut scheme = self.generalize(ty, ctx);
+                scheme.vars.splice(0..0, vars.iter().cloned());
+                scheme
+            }
+            _ => self.generalize(dom, ctx),
+        }
+    }
+
+    /// Replaces the type variables of `scheme` with fresh metavariables, the comparable ones being
+    /// checked to be comparable like the operands of the comparison `term`.
+    fn instantiate(&mut self, scheme: &Scheme, term: &'t Term) -> Type {
+        let substitution: HashMap<_, _> = scheme
+            .vars
+            .iter()
+            .map(|a| (a.clone(), self.unifier.fresh()))
+            .collect();
+        for a in &scheme.comparable {
+            self.comparisons.push((substitution[a].clone(), term));
+        }
+        scheme.ty.subst_type_vars(&substitution)
+    }
+
+    /// Infers the type of the whole term `term`, checking that the compared types are comparable
+    /// once they are known.
+    fn check(&mut self, term: &'t Term, ctx: Env) -> Result<Type, TypeError> {
+        let ty = self.infer(term, ctx)?;
+        for &(ref compared, term) in &self.comparisons {
+            let actual = self.unifier.resolve(compared);
+            if !actual.is_comparable() {
+                self.at = Some(term);
+                return Err(NotComparable {
+                    actual,
+                    term: Box::new(term.clone()),
+                });
+            }
+        }
+        Ok(normalize(&self.unifier.resolve(&ty)))
+    }
+
+    /// Infers the type of `term`, remembering it as the node where inference failed unless one of
+    /// its subterms already is.
+    fn infer(&mut self, term: &'t Term, ctx: Env) -> Result<Type, TypeError> {
+        self.infer_node(term, ctx).inspect_err(|_| {
+            self.at.get_or_insert(term);
+        })
+    }
+
+    fn infer_node(&mut self, term: &'t Term, mut ctx: Env) -> Result<Type, TypeError> {
+        match term {
+            Var(x) => match ctx.get(x) {
+                Some(scheme) => Ok(self.instantiate(scheme, term)),
+                None => Err(UndefinedVariable(x.clone())),
+            },
             Abs { var, ty, body } => {
-                ctx.insert(var.clone(), ty.clone());
-                Ok(Arrow(
-                    Box::new(ty.clone()),
-                    Box::new(body.as_ref().infer_type(ctx)?),
-                ))
-            }
-            App(term, term1) => {
-                let ty1 = term.infer_type(ctx.clone())?;
-                if let Arrow(dom, cod) = ty1 {
-                    let ty2 = term1.infer_type(ctx)?;
-                    if ty2 == *dom {
-                        return Ok(*(cod));
-                    } else {
-                        return Err(WrongAppTypeRight(ty2));
-                    }
-                }
-                Err(WrongAppTypeLeft(ty1))
+                let ty = self.annotation(ty)?;
+                ctx.insert(var.clone(), ty.clone().into());
+                Ok(Arrow(Box::new(ty), Box::new(self.infer(body, ctx)?)))
+            }
+            App(term1, term2) => {
+                let ty1 = self.infer(term1, ctx.clone())?;
+                let dom = self.unifier.fresh();
+                let cod = self.unifier.fresh();
+                let arrow = Arrow(Box::new(dom.clone()), Box::new(cod.clone()));
+                self.expect(&arrow, &ty1, term1, |expected, actual| WrongAppTypeLeft {
+                    expected,
+                    actual,
+                    term: term1.clone(),
+                })?;
+                let ty2 = self.infer(term2, ctx)?;
+                self.expect(&dom, &ty2, term2, |expected, actual| ArgumentMismatch {
+                    expected,
+                    actual,
+                    term: term2.clone(),
+                })?;
+                Ok(cod)
             }
 
             Let { var, val_t, body } => {
-                ctx.insert(var.clone(), val_t.infer_type(ctx.clone())?);
-                body.infer_type(ctx)
+                let ty = self.infer(val_t, ctx.clone())?;
+                ctx.insert(var.clone(), self.generalize(&ty, &ctx));
+                self.infer(body, ctx)
+            }
+            LetRec { bindings, body } => {
+                if let Some((var, ..)) = bindings.iter().enumerate().find_map(|(i, (var, ..))| {
+                    bindings[..i].iter().find(|(other, ..)| other == var)
+                }) {
+                    return Err(DuplicateBinding {
+                        var: var.clone(),
+                        term: Box::new(term.clone()),
+                    });
+                }
+                let doms = self.infer_rec_bindings(bindings, ctx.clone())?;
+                let schemes: Vec<_> = bindings
+                    .iter()
+                    .zip(&doms)
+                    .map(|((_, _, t), dom)| self.generalize_binding(dom, t, &ctx))
+                    .collect();
+                for ((var, ..), scheme) in bindings.iter().zip(schemes) {
+                    ctx.insert(var.clone(), scheme);
+                }
+                self.infer(body, ctx)
             }
 
+            Unit => Ok(Type::Unit),
             True | False => Ok(Boolean),
             Ite {
                 cond,
                 if_true,
                 if_false,
             } => {
-                if let Boolean = cond.infer_type(ctx.clone())? {
-                    let ty1 = if_true.infer_type(ctx.clone())?;
-                    let ty2 = if_false.infer_type(ctx.clone())?;
-                    if ty1 == ty2 {
-                        return Ok(ty1);
-                    }
-                }
-                Err(Fail)
+                let cond_ty = self.infer(cond, ctx.clone())?;
+                self.expect(&Boolean, &cond_ty, cond, |_, actual| ExpectedBoolean {
+                    actual,
+                    term: cond.clone(),
+                })?;
+                let ty1 = self.infer(if_true, ctx.clone())?;
+                let ty2 = self.infer(if_false, ctx)?;
+                self.expect_branch(ty1, ty2, if_false)
             }
-            Int(_) => Ok(Integer),
-            Add(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Integer);
-                }
-                Err(Fail)
+            And(term1, term2) | Or(term1, term2) => {
+                self.expect_boolean(term1, ctx.clone())?;
+                self.expect_boolean(term2, ctx)?;
+                Ok(Boolean)
             }
-            Sub(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Integer);
-                }
-                Err(Fail)
+            Not(t) => {
+                self.expect_boolean(t, ctx)?;
+                Ok(Boolean)
             }
-            Mul(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Integer);
-                }
-                Err(Fail)
+            Int(_) => Ok(Integer),
+            Add(term1, term2)
+            | Sub(term1, term2)
+            | Mul(term1, term2)
+            | Div(term1, term2)
+            | Mod(term1, term2) => {
+                self.expect_integer(term1, ctx.clone())?;
+                self.expect_integer(term2, ctx)?;
+                Ok(Integer)
             }
-            Eq(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == ty2 {
-                    return Ok(Boolean);
+            Eq(term1, term2) | Ne(term1, term2) => {
+                let ty1 = self.infer(term1, ctx.clone())?;
+                let ty2 = self.infer(term2, ctx)?;
+                self.expect(&ty1, &ty2, term2, |expected, actual| OperandMismatch {
+                    expected,
+                    actual,
+                    term: term2.clone(),
+                })?;
+                let ty = self.unifier.resolve(&ty1);
+                if !ty.is_comparable_in(&self.data) {
+                    return Err(NotComparable {
+                        actual: ty,
+                        term: Box::new(term.clone()),
+                    });
                 }
-                Err(Fail)
+                self.comparisons.push((ty, term));
+                Ok(Boolean)
             }
-            Ne(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == ty2 {
-                    return Ok(Boolean);
+            Lt(term1, term2) | Le(term1, term2) | Gt(term1, term2) | Ge(term1, term2) => {
+                let ty1 = self.infer(term1, ctx.clone())?;
+                self.expect_ordered(&ty1, term1)?;
+                let ty2 = self.infer(term2, ctx)?;
+                self.expect(&ty1, &ty2, term2, |expected, actual| OperandMismatch {
+                    expected,
+                    actual,
+                    term: term2.clone(),
+                })?;
+                self.expect_ordered(&ty1, term2)?;
+                // Operands of a type which is not known yet are integers
+                if let Meta(_) = self.unifier.shallow(&ty1) {
+                    self.unifier
+                        .unify(&Integer, &ty1)
+                        .expect("an unknown type to unify with any type");
                 }
-                Err(Fail)
+                Ok(Boolean)
             }
-            Lt(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Boolean);
-                }
-                Err(Fail)
+
+            // ===========================String stuff===========================
+
+            Neg(t) => {
+                self.expect_integer(t, ctx)?;
+                Ok(Integer)
             }
-            Le(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Boolean);
-                }
-                Err(Fail)
+
+            Str(_) => Ok(Type::String),
+            Char(_) => Ok(Type::Char),
+            Concat(term1, term2) => {
+                self.expect_string(term1, ctx.clone())?;
+                self.expect_string(term2, ctx)?;
+                Ok(Type::String)
             }
-            Gt(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Boolean);
-                }
-                Err(Fail)
+            StrLen(t) => {
+                self.expect_string(t, ctx)?;
+                Ok(Integer)
             }
-            Ge(term1, term2) => {
-                let ty1 = term1.infer_type(ctx.clone())?;
-                let ty2 = term2.infer_type(ctx.clone())?;
-                if ty1 == Integer && ty2 == Integer {
-                    return Ok(Boolean);
-                }
-                Err(Fail)
+            CharAt(term1, term2) => {
+                self.expect_string(term1, ctx.clone())?;
+                self.expect_integer(term2, ctx)?;
+                Ok(Type::Char)
+            }
+            IntToString(t) => {
+                self.expect_integer(t, ctx)?;
+                Ok(Type::String)
             }
             Pair(t1, t2) => {
-                let ty1 = t1.infer_type(ctx.clone())?;
-                let ty2 = t2.infer_type(ctx)?;
+                let ty1 = self.infer(t1, ctx.clone())?;
+                let ty2 = self.infer(t2, ctx)?;
                 Ok(Prod(Box::new(ty1), Box::new(ty2)))
             }
-            Fst(t) => {
-                match t.infer_type(ctx)? {
-                    Prod(ty1, _) => Ok(*ty1),
-                    _ => Err(Fail),
+            Fst(t) | Snd(t) => {
+                let ty = self.infer(t, ctx)?;
+                let ty1 = self.unifier.fresh();
+                let ty2 = self.unifier.fresh();
+                let prod = Prod(Box::new(ty1.clone()), Box::new(ty2.clone()));
+                self.expect(&prod, &ty, t, |_, actual| NotAProduct {
+                    actual,
+                    term: t.clone(),
+                })?;
+                Ok(if matches!(term, Fst(_)) { ty1 } else { ty2 })
+            }
+            Tuple(ts) => {
+                let tys = ts
+                    .iter()
+                    .map(|t| self.infer(t, ctx.clone()))
+                    .collect::<Result<_, _>>()?;
+                Ok(Type::Tuple(tys))
+            }
+            Record(fields) => {
+                distinct_fields(fields, term)?;
+                let mut tys = fields
+                    .iter()
+                    .map(|(l, t)| Ok((l.clone(), self.infer(t, ctx.clone())?)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                tys.sort_by(|(l1, _), (l2, _)| l1.cmp(l2));
+                Ok(Type::Record(tys))
+            }
+            Field(t, l) => {
+                let ty = self.infer(t, ctx)?;
+                self.field(&ty, l, t)
+            }
+            Update(t, fields) => {
+                distinct_fields(fields, term)?;
+                let ty = self.infer(t, ctx.clone())?;
+                for (l, u) in fields {
+                    let field_ty = self.field(&ty, l, t)?;
+                    let actual = self.infer(u, ctx.clone())?;
+                    self.expect(&field_ty, &actual, u, |expected, actual| FieldMismatch {
+                        field: l.clone(),
+                        expected,
+                        actual,
+                        term: Box::new(u.clone()),
+                    })?;
                 }
+                Ok(ty)
             }
-            Snd(t) => {
-                match t.infer_type(ctx)? {
-                    Prod(_, ty2) => Ok(*ty2),
-                    _ => Err(Fail),
+            Proj(t, index) => {
+                let ty = self.infer(t, ctx)?;
+                let not_a_tuple = |actual| NotATuple {
+                    index: *index,
+                    actual,
+                    term: t.clone(),
+                };
+                match self.unifier.shallow(&ty) {
+                    Type::Tuple(tys) => tys
+                        .get(*index)
+                        .cloned()
+                        .ok_or_else(|| not_a_tuple(self.unifier.resolve(&ty))),
+                    // A projection of a type which is not known yet is one of a pair, like `fst` and `snd`
+                    Meta(_) | Prod(..) if *index < 2 => {
+                        let ty1 = self.unifier.fresh();
+                        let ty2 = self.unifier.fresh();
+                        let prod = Prod(Box::new(ty1.clone()), Box::new(ty2.clone()));
+                        self.expect(&prod, &ty, t, |_, actual| not_a_tuple(actual))?;
+                        Ok(if *index == 0 { ty1 } else { ty2 })
+                    }
+                    _ => Err(not_a_tuple(self.unifier.resolve(&ty))),
                 }
             }
 
             // ============================List stuff============================
 
-            Nil(ty) => Ok(List(Box::new(ty.clone()))),
+            Nil(ty) => Ok(List(Box::new(self.annotation(ty)?))),
 
             Cons(head, tail) => {
-                let head_ty = head.infer_type(ctx.clone())?;
-                let tail_ty = tail.infer_type(ctx)?;
-                match tail_ty {
-                    List(inner_ty) if *inner_ty == head_ty => Ok(List(Box::new(head_ty))),
-                    _ => Err(Fail),
-                }
+                let head_ty = self.infer(head, ctx.clone())?;
+                let tail_ty = self.infer(tail, ctx)?;
+                let list_ty = List(Box::new(head_ty));
+                self.expect(&list_ty, &tail_ty, tail, |expected, actual| ConsMismatch {
+                    expected,
+                    actual,
+                    term: tail.clone(),
+                })?;
+                Ok(list_ty)
             }
-    
+
+            Append(term1, term2) => {
+                let ty1 = self.infer(term1, ctx.clone())?;
+                let list_ty = List(Box::new(self.unifier.fresh()));
+                self.expect(&list_ty, &ty1, term1, |_, actual| NotAList {
+                    actual,
+                    term: term1.clone(),
+                })?;
+                let ty2 = self.infer(term2, ctx)?;
+                self.expect(&ty1, &ty2, term2, |expected, actual| OperandMismatch {
+                    expected,
+                    actual,
+                    term: term2.clone(),
+                })?;
+                Ok(ty1)
+            }
+
             LCase { t, nil_t, head_var, tail_var, cons_t } => {
-                let list_ty = t.infer_type(ctx.clone())?;
-                match list_ty {
-                    List(inner_ty) => {
-                        let nil_ty = nil_t.infer_type(ctx.clone())?;
-                        ctx.insert(head_var.clone(), (*inner_ty).clone());
-                        ctx.insert(tail_var.clone(), List(inner_ty));
-                        let cons_ty = cons_t.infer_type(ctx)?;
-                        if nil_ty == cons_ty {
-                            Ok(nil_ty)
-                        } else {
-                            Err(Fail)
-                        }
-                    }
-                    _ => Err(Fail),
-                }
+                let ty = self.infer(t, ctx.clone())?;
+                let inner_ty = self.unifier.fresh();
+                let list_ty = List(Box::new(inner_ty.clone()));
+                self.expect(&list_ty, &ty, t, |_, actual| NotAList {
+                    actual,
+                    term: t.clone(),
+                })?;
+                let nil_ty = self.infer(nil_t, ctx.clone())?;
+                ctx.insert(head_var.clone(), inner_ty.into());
+                ctx.insert(tail_var.clone(), list_ty.into());
+                let cons_ty = self.infer(cons_t, ctx)?;
+                self.expect_branch(nil_ty, cons_ty, cons_t)
             }
 
             // ============================Sum stuff============================
 
             Inl(t, ty_r) => {
-                let ty_l = t.infer_type(ctx)?;
-                Ok(Sum(Box::new(ty_l), Box::new(ty_r.clone())))
+                let ty_l = self.infer(t, ctx)?;
+                Ok(Sum(Box::new(ty_l), Box::new(self.annotation(ty_r)?)))
             }
             Inr(t, ty_l) => {
-                let ty_r = t.infer_type(ctx)?;
-                Ok(Sum(Box::new(ty_l.clone()), Box::new(ty_r)))
+                let ty_r = self.infer(t, ctx)?;
+                Ok(Sum(Box::new(self.annotation(ty_l)?), Box::new(ty_r)))
             }
             Case { t, inl_var, inl_t, inr_var, inr_t } => {
-                let sum_ty = t.infer_type(ctx.clone())?;
-                if let Sum(ty_l, ty_r) = sum_ty {
-                    let mut ctx_l = ctx.clone();
-                    ctx_l.insert(inl_var.clone(), (*ty_l).clone());
-                    let inl_t_ty = inl_t.infer_type(ctx_l)?;
-            
-                    let mut ctx_r = ctx;
-                    ctx_r.insert(inr_var.clone(), (*ty_r).clone());
-                    let inr_t_ty = inr_t.infer_type(ctx_r)?;
-            
-                    if inl_t_ty == inr_t_ty {
-                        Ok(inl_t_ty)
-                    } else {
-                        Err(Fail)
-                    }
-                } else {
-                    Err(Fail)
+                let ty = self.infer(t, ctx.clone())?;
+                let ty_l = self.unifier.fresh();
+                let ty_r = self.unifier.fresh();
+                let sum_ty = Sum(Box::new(ty_l.clone()), Box::new(ty_r.clone()));
+                self.expect(&sum_ty, &ty, t, |_, actual| NotASum {
+                    actual,
+                    term: t.clone(),
+                })?;
+
+                let mut ctx_l = ctx.clone();
+                ctx_l.insert(inl_var.clone(), ty_l.into());
+                let inl_t_ty = self.infer(inl_t, ctx_l)?;
+
+                let mut ctx_r = ctx;
+                ctx_r.insert(inr_var.clone(), ty_r.into());
+                let inr_t_ty = self.infer(inr_t, ctx_r)?;
+
+                self.expect_branch(inl_t_ty, inr_t_ty, inr_t)
+            }
+
+            // ===========================Variant stuff===========================
+
+            Tag(l, t, ty) => {
+                let ty = self.annotation(ty)?;
+                let Variant(fields) = self.unfold(&ty) else {
+                    return Err(NotAVariant {
+                        actual: self.unifier.resolve(&ty),
+                        term: Box::new(term.clone()),
+                    });
+                };
+                let Some((_, field_ty)) = fields.iter().find(|(l2, _)| l2 == l) else {
+                    return Err(NoSuchLabel {
+                        label: l.clone(),
+                        actual: self.unifier.resolve(&ty),
+                        term: Box::new(term.clone()),
+                    });
+                };
+                let field_ty = field_ty.clone();
+                let actual = self.infer(t, ctx)?;
+                self.expect(&field_ty, &actual, t, |expected, actual| FieldMismatch {
+                    field: l.clone(),
+                    expected,
+                    actual,
+                    term: t.clone(),
+                })?;
+                Ok(ty)
+            }
+            Match { t, arms } => {
+                let ty = self.infer(t, ctx.clone())?;
+                let fields = self.variant_arms(&ty, arms, term)?;
+                let mut result: Option<Type> = None;
+                for (l, var, arm) in arms {
+                    let (_, field_ty) = fields
+                        .iter()
+                        .find(|(l2, _)| l2 == l)
+                        .expect("every arm to have a label of the variant");
+                    let mut ctx = ctx.clone();
+                    ctx.insert(var.clone(), field_ty.clone().into());
+                    let arm_ty = self.infer(arm, ctx)?;
+                    result = Some(match result {
+                        Some(expected) => self.expect_branch(expected, arm_ty, arm)?,
+                        None => arm_ty,
+                    });
                 }
+                Ok(result.expect("a match to have at least one arm"))
             }
 
             // ============================Fix stuff============================
 
             Fix(inner) => {
-                let t_ty = inner.infer_type(ctx)?;
-                if let Type::Arrow(dom, cod) = t_ty {
-                    if *dom == *cod {
-                        Ok(*dom)
-                    } else {
-                        Err(Fail)
+                // The type variables of a polymorphic annotation scope over the abstraction
+                let vars = match &**inner {
+                    Abs {
+                        ty: Forall(vars, _),
+                        ..
+                    } => vars.clone(),
+                    _ => vec![],
+                };
+                let ty = self.infer_with_type_vars(&vars, inner, ctx)?;
+                let dom = self.unifier.fresh();
+                let cod = self.unifier.fresh();
+                let arrow = Arrow(Box::new(dom.clone()), Box::new(cod.clone()));
+                self.expect(&arrow, &ty, inner, |_, actual| FixNotAFunction {
+                    actual,
+                    term: inner.clone(),
+                })?;
+                // Unless the body is a type abstraction, the type variables of a polymorphic
+                // domain are rigid in the codomain
+                let (vars, expected) = match (self.unifier.shallow(&dom), self.unifier.shallow(&cod)) {
+                    (Forall(vars, ty), cod) if !matches!(cod, Forall(..)) => {
+                        (Some(vars.clone()), (**ty).clone())
                     }
-                } else {
-                    Err(Fail)
+                    _ => (None, dom.clone()),
+                };
+                self.expect(&expected, &cod, inner, |domain, codomain| FixMismatch {
+                    domain: match vars {
+                        Some(vars) => Forall(vars, Box::new(domain)),
+                        None => domain,
+                    },
+                    codomain,
+                    term: inner.clone(),
+                })?;
+                Ok(dom)
+            }
+
+            // ========================Polymorphism stuff========================
+
+            TAbs { var, body } => {
+                let fixed: Vec<usize> = ctx
+                    .values()
+                    .flat_map(|scheme| self.unifier.metas(&scheme.ty))
+                    .collect();
+                let ty = self.infer_with_type_vars(std::slice::from_ref(var), body, ctx)?;
+                // The type variable must not escape into the types of the variables in scope
+                let escapes = fixed.into_iter().any(|m| {
+                    self.unifier
+                        .resolve(&Meta(m))
+                        .free_type_vars()
+                        .contains(var)
+                });
+                if escapes {
+                    return Err(TypeVariableEscape {
+                        var: var.clone(),
+                        term: Box::new(term.clone()),
+                    });
+                }
+                Ok(match self.unifier.resolve(&ty) {
+                    Forall(mut vars, ty) if !vars.contains(var) => {
+                        vars.insert(0, var.clone());
+                        Forall(vars, ty)
+                    }
+                    ty => Forall(vec![var.clone()], Box::new(ty)),
+                })
+            }
+            TApp(t, ty_arg) => {
+                let ty = self.infer(t, ctx)?;
+                let ty_arg = self.annotation(ty_arg)?;
+                match self.unifier.resolve(&ty) {
+                    Forall(mut vars, ty) => {
+                        let a = vars.remove(0);
+                        let ty = ty.subst_type_vars(&[(a, ty_arg)].into_iter().collect());
+                        Ok(if vars.is_empty() {
+                            ty
+                        } else {
+                            Forall(vars, Box::new(ty))
+                        })
+                    }
+                    actual => Err(NotAForall {
+                        actual,
+                        term: t.clone(),
+                    }),
                 }
             }
 
-            _ => todo!(),
+            // ==========================Data type stuff==========================
+
+            Data { name, ty, body } => {
+                self.data.push((name.clone(), ty.clone()));
+                let start = self.comparisons.len();
+                let ty = self.annotation(ty).and_then(|_| self.infer(body, ctx));
+                // The data type is out of scope afterwards, so the types compared in the body
+                // are checked now that they are known
+                let ty = ty.and_then(|ty| {
+                    for &(ref compared, term) in &self.comparisons[start..] {
+                        let actual = self.unifier.resolve(compared);
+                        if !actual.is_comparable_in(&self.data) {
+                            self.at = Some(term);
+                            return Err(NotComparable {
+                                actual,
+                                term: Box::new(term.clone()),
+                            });
+                        }
+                    }
+                    Ok(ty)
+                });
+                self.data.pop();
+                ty
+            }
+
+            // =======================Recursive type stuff=======================
+
+            Fold(t, ty) => {
+                let ty = self.annotation(ty)?;
+                let Some(unrolled) = self.unifier.resolve(&ty).unroll() else {
+                    return Err(NotARecursiveType {
+                        actual: self.unifier.resolve(&ty),
+                        term: Box::new(term.clone()),
+                    });
+                };
+                let actual = self.infer(t, ctx)?;
+                self.expect(&unrolled, &actual, t, |expected, actual| FoldMismatch {
+                    expected,
+                    actual,
+                    term: t.clone(),
+                })?;
+                Ok(ty)
+            }
+            Unfold(t) => {
+                let ty = self.infer(t, ctx)?;
+                self.unifier
+                    .resolve(&ty)
+                    .unroll()
+                    .ok_or_else(|| NotARecursiveType {
+                        actual: self.unifier.resolve(&ty),
+                        term: Box::new(term.clone()),
+                    })
+            }
         }
     }
+}
+
+impl Term {
+    /// Infers the principal type of the term `self`.
+    ///
+    /// Missing annotations, see [`Type::Hole`], are inferred by unification. The types of `let`
+    /// bindings are generalized over the metavariables not occurring in the context, and
+    /// instantiated wherever the variables are used, as are the polymorphic annotations of
+    /// recursive bindings which are not type abstractions. Other terms of a polymorphic type
+    /// [`Type::Forall`] must be applied to types explicitly.
+    /// Named type variables [`Type::Var`] are rigid, and are brought into scope by type abstractions
+    /// [`Term::TAbs`] and by polymorphic annotations of recursive functions. Types which are not
+    /// determined by the term are left as metavariables [`Type::Meta`], numbered from `0` in the
+    /// order they appear in the result, except for the operands of ordering operators such as `<`,
+    /// which are integers unless they are known to be characters or strings where they are
+    /// compared.
+    ///
+    /// # Errors
+    ///
+    /// - If the context doesn't contain the required variable, returns an [`UndefinedVariable`] error with the name of the variable inside.
+    /// - If the left hand side of an application is not an arrow type, returns the [`WrongAppTypeLeft`] error with the expected arrow type and the actual type inside.
+    /// - If the right hand side of an application is not of the expected type, returns the [`ArgumentMismatch`] error with both types inside.
+    /// - If inference would need an infinite type, returns the [`OccursCheck`] error with the metavariable and the type containing it.
+    /// - If an annotation mentions a type variable not in scope, returns the [`UndefinedTypeVariable`] error with its name inside.
+    /// - If a term applied to a type is not polymorphic, returns the [`NotAForall`] error with its type inside.
+    /// - If the operands of `==` or `!=` are not comparable (see [`Type::is_comparable`]), returns the [`NotComparable`] error with their type and the comparison inside, or the variable standing for the comparison if it was bound by `let`.
+    /// - In other typing failures, an error naming the expected and actual types and the offending subterm is returned, see [`TypeError`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// assert_eq!(
+    ///     add(1, True).type_check(),
+    ///     Err(TypeError::ExpectedInteger { actual: Boolean, term: True.into() })
+    /// );
+    /// assert_eq!(
+    ///     Ite { cond: True.into(), if_true: 1.into(), if_false: False.into() }.type_check(),
+    ///     Err(TypeError::BranchMismatch { expected: Integer, actual: Boolean, term: False.into() })
+    /// );
+    /// assert_eq!(
+    ///     fix(abs("x", Integer, True)).type_check(),
+    ///     Err(TypeError::FixMismatch {
+    ///         domain: Integer,
+    ///         codomain: Boolean,
+    ///         term: abs("x", Integer, True).into(),
+    ///     })
+    /// );
+    /// assert_eq!(
+    ///     app(1, 2).type_check(),
+    ///     Err(TypeError::WrongAppTypeLeft {
+    ///         expected: arrow(Meta(0), Meta(1)),
+    ///         actual: Integer,
+    ///         term: Int(1.into()).into(),
+    ///     })
+    /// );
+    /// ```
+    ///
+    /// **Inference of missing annotations:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// let compose = abs("f", Hole, abs("g", Hole, abs("x", Hole, app("f", app("g", "x")))));
+    /// assert_eq!(
+    ///     compose.type_check(),
+    ///     Ok(arrow(arrow(Meta(0), Meta(1)), arrow(arrow(Meta(2), Meta(0)), arrow(Meta(2), Meta(1)))))
+    /// );
+    /// assert_eq!(abs("x", Hole, add("x", 1)).type_check(), Ok(arrow(Integer, Integer)));
+    /// // Let bindings are generalized
+    /// let t = letin("id", abs("x", Hole, "x"), pair(app("id", 1), app("id", True)));
+    /// assert_eq!(t.type_check(), Ok(prod(Integer, Boolean)));
+    /// assert_eq!(
+    ///     abs("x", Hole, app("x", "x")).type_check(),
+    ///     Err(TypeError::OccursCheck {
+    ///         var: Meta(1),
+    ///         ty: arrow(Meta(1), Meta(2)),
+    ///         term: var("x").into(),
+    ///     })
+    /// );
+    /// ```
+    ///
+    /// **Explicit polymorphism:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// let id = tabs("a", abs("x", tvar("a"), "x"));
+    /// assert_eq!(id.type_check(), Ok(forall(["a"], arrow(tvar("a"), tvar("a")))));
+    /// assert_eq!(tapp(id.clone(), Integer).type_check(), Ok(arrow(Integer, Integer)));
+    /// assert_eq!(
+    ///     tapp(abs("x", Integer, "x"), Integer).type_check(),
+    ///     Err(TypeError::NotAForall {
+    ///         actual: arrow(Integer, Integer),
+    ///         term: abs("x", Integer, "x").into(),
+    ///     })
+    /// );
+    /// assert_eq!(
+    ///     abs("x", tvar("a"), "x").type_check(),
+    ///     Err(TypeError::UndefinedTypeVariable("a".to_string()))
+    /// );
+    /// // Unlike the types of `let` bindings, explicitly polymorphic types are not instantiated
+    /// assert_eq!(letin("id", id.clone(), app(tapp("id", Integer), 1)).type_check(), Ok(Integer));
+    /// assert!(matches!(
+    ///     letin("id", id, app("id", 1)).type_check(),
+    ///     Err(TypeError::WrongAppTypeLeft { .. })
+    /// ));
+    /// ```
+    ///
+    /// **Comparisons:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// let equal = abs("x", Hole, abs("y", Hole, eq("x", "y")));
+    /// let t = |arg: Term| letin("eq", equal.clone(), app(app("eq", arg.clone()), arg));
+    /// assert_eq!(t(Int(1.into())).type_check(), Ok(Boolean));
+    /// let both = pair(app(app("eq", 1), 1), app(app("eq", True), True));
+    /// assert_eq!(letin("eq", equal.clone(), both).type_check(), Ok(prod(Boolean, Boolean)));
+    /// // Compared types can only be instantiated with comparable types
+    /// assert_eq!(
+    ///     t(abs("x", Integer, "x")).type_check(),
+    ///     Err(TypeError::NotComparable {
+    ///         actual: arrow(Integer, Integer),
+    ///         term: var("eq").into(),
+    ///     })
+    /// );
+    /// // Ordered types which are not known are integers
+    /// let less = abs("x", Hole, abs("y", Hole, lt("x", "y")));
+    /// assert_eq!(less.type_check(), Ok(arrow(Integer, arrow(Integer, Boolean))));
+    /// assert_eq!(
+    ///     tabs("a", abs("x", tvar("a"), eq("x", "x"))).type_check(),
+    ///     Err(TypeError::NotComparable { actual: tvar("a"), term: eq("x", "x").into() })
+    /// );
+    /// let f = variant([("f", arrow(Integer, Integer))]);
+    /// let t = data("F", f, abs("x", named("F"), eq("x", "x")));
+    /// assert_eq!(
+    ///     t.type_check(),
+    ///     Err(TypeError::NotComparable { actual: named("F"), term: eq("x", "x").into() })
+    /// );
+    /// ```
+    ///
+    /// **Records:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::{util::record as record_type, TypeError};
+    /// let r = record([("y", True), ("x", Int(1.into()))]);
+    /// assert_eq!(r.type_check(), Ok(record_type([("x", Integer), ("y", Boolean)])));
+    /// assert_eq!(update(r.clone(), [("x", Int(2.into()))]).type_check(), r.type_check());
+    /// assert_eq!(
+    ///     field(r.clone(), "z").type_check(),
+    ///     Err(TypeError::NoSuchField {
+    ///         field: "z".to_string(),
+    ///         actual: record_type([("x", Integer), ("y", Boolean)]),
+    ///         term: r.into(),
+    ///     })
+    /// );
+    /// assert_eq!(
+    ///     record([("x", Int(1.into())), ("x", Int(2.into()))]).type_check(),
+    ///     Err(TypeError::DuplicateField {
+    ///         field: "x".to_string(),
+    ///         term: record([("x", Int(1.into())), ("x", Int(2.into()))]).into(),
+    ///     })
+    /// );
+    /// let ty = record_type([("x", Integer), ("x", Boolean)]);
+    /// assert_eq!(
+    ///     abs("r", ty.clone(), var("r")).type_check(),
+    ///     Err(TypeError::DuplicateLabel { label: "x".to_string(), ty })
+    /// );
+    /// ```
+    ///
+    /// **Variants:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// let option = variant([("some", Integer), ("none", Type::Unit)]);
+    /// let t = tag("some", 1, option.clone());
+    /// assert_eq!(t.type_check(), Ok(option.clone()));
+    /// let get = |arms: Vec<(&str, &str, Term)>| match_variant(t.clone(), arms);
+    /// assert_eq!(get(vec![("none", "u", Int(0.into())), ("some", "x", var("x"))]).type_check(), Ok(Integer));
+    /// assert_eq!(
+    ///     get(vec![("some", "x", var("x"))]).type_check(),
+    ///     Err(TypeError::MissingArm {
+    ///         label: "none".to_string(),
+    ///         actual: option.clone(),
+    ///         term: get(vec![("some", "x", var("x"))]).into(),
+    ///     })
+    /// );
+    /// let arms = vec![("none", "u", Int(0.into())), ("some", "x", var("x")), ("some", "y", Int(1.into()))];
+    /// assert_eq!(
+    ///     get(arms.clone()).type_check(),
+    ///     Err(TypeError::RedundantArm {
+    ///         label: "some".to_string(),
+    ///         actual: option.clone(),
+    ///         term: get(arms).into(),
+    ///     })
+    /// );
+    /// assert!(matches!(tag("other", 1, option).type_check(), Err(TypeError::NoSuchLabel { .. })));
+    /// let twice = variant([("x", Integer), ("x", Boolean)]);
+    /// assert!(matches!(tag("x", 1, twice).type_check(), Err(TypeError::DuplicateLabel { .. })));
+    /// ```
+    ///
+    /// **Recursive types:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// let nat = rec("n", sum(Type::Unit, tvar("n")));
+    /// let zero = fold(inl(Unit, Hole), nat.clone());
+    /// let one = fold(inr(zero.clone(), Hole), nat.clone());
+    /// assert_eq!(one.type_check(), Ok(nat.clone()));
+    /// assert_eq!(unfold(one).type_check(), Ok(sum(Type::Unit, nat.clone())));
+    /// assert_eq!(
+    ///     fold(1, nat.clone()).type_check(),
+    ///     Err(TypeError::FoldMismatch {
+    ///         expected: sum(Type::Unit, nat),
+    ///         actual: Integer,
+    ///         term: 1.into(),
+    ///     })
+    /// );
+    /// assert!(matches!(unfold(1).type_check(), Err(TypeError::NotARecursiveType { .. })));
+    /// ```
+    ///
+    /// **Recursive bindings:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::TypeError;
+    /// let f = abs("n", Integer, app("g", "n"));
+    /// let g = abs("n", Integer, eq("n", 0));
+    /// let ty = arrow(Integer, Integer);
+    /// let t = letrec([("f", ty.clone(), f.clone()), ("g", ty.clone(), g.clone())], app("f", 1));
+    /// assert_eq!(
+    ///     t.type_check(),
+    ///     Err(TypeError::LetRecMismatch {
+    ///         var: "g".to_string(),
+    ///         expected: ty.clone(),
+    ///         actual: arrow(Integer, Boolean),
+    ///         term: g.into(),
+    ///     })
+    /// );
+    /// let t = letrec([("g", ty.clone(), abs("n", Integer, True))], app("g", 1));
+    /// assert_eq!(
+    ///     t.type_check(),
+    ///     Err(TypeError::LetRecMismatch {
+    ///         var: "g".to_string(),
+    ///         expected: ty.clone(),
+    ///         actual: arrow(Integer, Boolean),
+    ///         term: abs("n", Integer, True).into(),
+    ///     })
+    /// );
+    /// let t = letrec([("f", ty.clone(), f.clone()), ("f", ty.clone(), f)], app("f", 1));
+    /// assert_eq!(
+    ///     t.type_check(),
+    ///     Err(TypeError::DuplicateBinding { var: "f".to_string(), term: t.into() })
+    /// );
+    /// // A `fix` written out by hand is not a recursive binding
+    /// let inner = abs("g", ty.clone(), abs("n", Integer, True));
+    /// let t = letin("g", fix(inner.clone()), app("g", 1));
+    /// assert_eq!(
+    ///     t.type_check(),
+    ///     Err(TypeError::FixMismatch {
+    ///         domain: ty,
+    ///         codomain: arrow(Integer, Boolean),
+    ///         term: inner.into(),
+    ///     })
+    /// );
+    /// ```
+    ///
+    /// **Tuples:**
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::r#type::{util::tuple as tuple_type, TypeError};
+    /// let t = tuple([Int(1.into()), True, Int(2.into())]);
+    /// assert_eq!(t.type_check(), Ok(tuple_type([Integer, Boolean, Integer])));
+    /// assert_eq!(proj(t.clone(), 1).type_check(), Ok(Boolean));
+    /// assert_eq!(
+    ///     proj(t.clone(), 3).type_check(),
+    ///     Err(TypeError::NotATuple {
+    ///         index: 3,
+    ///         actual: tuple_type([Integer, Boolean, Integer]),
+    ///         term: t.into(),
+    ///     })
+    /// );
+    /// ```
+    pub fn infer_type(&self, ctx: Context) -> Result<Type, TypeError> {
+        self.infer_type_located(ctx).map_err(|e| e.error)
+    }
+
+    /// Like [`Term::infer_type`], but also returns the innermost node of `self` whose type could
+    /// not be inferred on error.
+    ///
+    /// ```rust
+    /// # use stlc_project::r#type::{Context, TypeError};
+    /// # use stlc_project::term::util::*;
+    /// let t = add(1, ite(True, 1, False));
+    /// let err = t.infer_type_located(Context::new()).unwrap_err();
+    /// assert!(matches!(err.error, TypeError::BranchMismatch { .. }));
+    /// // The `if` is the node where inference failed, rather than the addition containing it
+    /// let Add(_, ite) = &t else { unreachable!() };
+    /// assert!(std::ptr::eq(err.node, &**ite));
+    /// ```
+    pub fn infer_type_located(&self, ctx: Context) -> Result<Type, Box<Located<'_, TypeError>>> {
+        let mut inference = Inference::default();
+        let ctx = ctx
+            .into_iter()
+            .map(|(x, ty)| (x, inference.unifier.instantiate(&ty).into()))
+            .collect();
+        inference.check(self, ctx).map_err(|error| {
+            Box::new(Located {
+                error,
+                node: inference.at.unwrap_or(self),
+            })
+        })
+    }
 
     pub fn type_check(&self) -> Result<Type, TypeError> {
         self.infer_type(Context::new())
     }
+
+    /// Like [`Term::type_check`], but also returns the node of `self` where type checking failed,
+    /// see [`Term::infer_type_located`].
+    pub fn type_check_located(&self) -> Result<Type, Box<Located<'_, TypeError>>> {
+        self.infer_type_located(Context::new())
+    }
 }
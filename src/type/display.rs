@@ -1,12 +1,30 @@
 use super::Type::{self, *};
 
+/// Types are displayed with the parentheses needed to parse them back.
+///
+/// ```rust
+/// # use nom::{combinator::all_consuming, Parser};
+/// # use stlc_project::r#type::{parse::parse_type, util::*};
+/// let ty = sum(rec("a", list(tvar("a"))), named("T"));
+/// let code = ty.to_string();
+/// assert_eq!(code, "(μa. [a]) + T");
+/// assert_eq!(all_consuming(parse_type).parse(&code), Ok(("", ty)));
+/// ```
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Unit => write!(f, "𝟙"),
             Boolean => write!(f, "𝟚"),
             Integer => write!(f, "ℤ"),
+            String => write!(f, "String"),
+            Char => write!(f, "Char"),
+            Hole => write!(f, "_"),
+            Meta(m) => write!(f, "?{m}"),
+            Var(a) | Named(a) => write!(f, "{a}"),
+            Forall(vars, ty) => write!(f, "∀{}. {ty}", vars.join(" ")),
+            Rec(var, ty) => write!(f, "μ{var}. {ty}"),
             Arrow(ty1, ty2) => match (&**ty1, &**ty2) {
-                (ty1 @ Arrow(..), ty2) => {
+                (ty1 @ (Arrow(..) | Forall(..) | Rec(..)), ty2) => {
                     // If the left hand side is an arrow, it needs parentheses
                     write!(f, "({ty1}) → {ty2}")
                 }
@@ -14,28 +32,47 @@ impl std::fmt::Display for Type {
                 _ => write!(f, "{ty1} → {ty2}"),
             },
             Prod(ty1, ty2) => match (&**ty1, &**ty2) {
-                (ty1 @ (Arrow(..) | Sum(..)), ty2 @ (Arrow(..) | Sum(..))) => {
+                (
+                    ty1 @ (Arrow(..) | Sum(..) | Forall(..) | Rec(..)),
+                    ty2 @ (Arrow(..) | Sum(..) | Forall(..) | Rec(..)),
+                ) => {
                     write!(f, "({ty1}) × ({ty2})")
                 }
-                (ty1 @ (Arrow(..) | Sum(..)), ty2) => {
+                (ty1 @ (Arrow(..) | Sum(..) | Forall(..) | Rec(..)), ty2) => {
                     write!(f, "({ty1}) × {ty2}")
                 }
-                (ty1, ty2 @ (Arrow(..) | Prod(..) | Sum(..))) => {
+                (ty1, ty2 @ (Arrow(..) | Prod(..) | Sum(..) | Forall(..) | Rec(..))) => {
                     // Prod associates left
                     write!(f, "{ty1} × ({ty2})")
                 }
                 // Otherwise no parens
                 _ => write!(f, "{ty1} × {ty2}"),
             },
+            Tuple(tys) => {
+                let tys: Vec<_> = tys.iter().map(Type::to_string).collect();
+                write!(f, "({})", tys.join(", "))
+            }
+            Record(fields) => {
+                let fields: Vec<_> = fields.iter().map(|(l, ty)| format!("{l} : {ty}")).collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Variant(fields) => {
+                let fields: Vec<_> = fields.iter().map(|(l, ty)| format!("{l} : {ty}")).collect();
+                write!(f, "<{}>", fields.join(", "))
+            }
             List(ty) => write!(f, "[{ty}]"),
             Sum(ty1, ty2) => match (&**ty1, &**ty2) {
-                (ty1 @ Arrow(..), ty2 @ Arrow(..)) => {
+                // Binders extend as far to the right as possible, so they need parentheses too
+                (
+                    ty1 @ (Arrow(..) | Forall(..) | Rec(..)),
+                    ty2 @ (Arrow(..) | Sum(..) | Forall(..) | Rec(..)),
+                ) => {
                     write!(f, "({ty1}) + ({ty2})")
                 }
-                (ty1 @ Arrow(..), ty2) => {
+                (ty1 @ (Arrow(..) | Forall(..) | Rec(..)), ty2) => {
                     write!(f, "({ty1}) + {ty2}")
                 }
-                (ty1, ty2 @ (Arrow(..) | Sum(..))) => {
+                (ty1, ty2 @ (Arrow(..) | Sum(..) | Forall(..) | Rec(..))) => {
                     // Sum associates left
                     write!(f, "{ty1} + ({ty2})")
                 }
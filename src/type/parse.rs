@@ -1,17 +1,78 @@
-use nom::character::complete::multispace1;
-use nom::multi::fold_many0;
+use nom::character::complete::{multispace0, multispace1};
 use nom::{
-    branch::alt, bytes::complete::tag, character::complete::char, combinator::value,
-    sequence::delimited, IResult, Parser,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{value, verify},
+    multi::{many1, separated_list0},
+    sequence::{delimited, preceded},
+    Parser,
 };
 
 use super::super::parse::*;
 use super::Type::{self, *};
+use crate::span::record_type;
+use crate::term::parse::parse_variable_name;
+
+/// Parses the name of a type variable, which starts with a lowercase letter.
+pub fn parse_type_var_name(input: &str) -> IResult<&str, std::string::String> {
+    verify(parse_variable_name, |name: &str| {
+        name.starts_with(|c: char| c.is_lowercase())
+    })
+    .parse(input)
+}
+
+fn parse_type_var(input: &str) -> IResult<&str, Type> {
+    parse_type_var_name.map(Var).parse(input)
+}
+
+/// Parses the name of a data type, which starts with an uppercase letter.
+pub fn parse_type_name(input: &str) -> IResult<&str, std::string::String> {
+    verify(parse_variable_name, |name: &str| {
+        name.starts_with(|c: char| c.is_uppercase())
+    })
+    .parse(input)
+}
+
+/// Parses a polymorphic type `forall a b. T`, which extends as far to the right as possible.
+fn parse_forall_type(input: &str) -> IResult<&str, Type> {
+    (
+        alt((tag("forall"), tag("∀"))),
+        many1(preceded(multispace1, parse_type_var_name)),
+        multispace0,
+        char('.'),
+        multispace0,
+        parse_type,
+    )
+        .map(|(_, vars, _, _, _, ty)| Forall(vars, Box::new(ty)))
+        .parse(input)
+}
+
+/// Parses a recursive type `μa. T` or `mu a. T`, which extends as far to the right as possible.
+fn parse_rec_type(input: &str) -> IResult<&str, Type> {
+    (
+        alt(((tag("mu"), multispace1), (tag("μ"), multispace0))),
+        parse_type_var_name,
+        multispace0,
+        char('.'),
+        multispace0,
+        parse_type,
+    )
+        .map(|(_, var, _, _, _, ty)| Rec(var, Box::new(ty)))
+        .parse(input)
+}
 
 fn parse_base_type(input: &str) -> IResult<&str, Type> {
     alt((
+        // Names of data types may start with the name of a base type, e.g. `Units`
+        parse_type_name.map(Named),
+        value(Unit, tag("Unit")),
         value(Boolean, tag("Boolean")),
         value(Integer, tag("Integer")),
+        value(String, tag("String")),
+        value(Char, tag("Char")),
+        value(Hole, tag("_")),
+        parse_type_var,
     ))
     .parse(input)
 }
@@ -39,46 +100,101 @@ fn parse_arrow_type(input: &str) -> IResult<&str, Type> {
     let (rest, ty1) = parse_sum_type.parse(input)?;
 
     if let Ok((rest, (_, ty2))) = (ws0(tag("->")), parse_arrow_type).parse(rest) {
-        Ok((rest, Arrow(ty1.into(), ty2.into())))
+        let ty = Arrow(ty1.into(), ty2.into());
+        record_type(input, rest, &ty);
+        Ok((rest, ty))
     } else {
         Ok((rest, ty1))
     }
 }
 
+/// Parses a product type `(A, B)` or a tuple type `(A, B, C)` of three or more components.
 fn parse_prod_type(input: &str) -> IResult<&str, Type> {
     delimited(
         char('('),
-        (ws0(parse_type), char(','), ws0(parse_type)),
+        (ws0(parse_type), many1(preceded(char(','), ws0(parse_type)))),
         char(')'),
     )
-    .map(|(ty1, _, ty2)| Prod(ty1.into(), ty2.into()))
+    .map(|(ty1, mut tys)| {
+        if tys.len() == 1 {
+            Prod(ty1.into(), tys.remove(0).into())
+        } else {
+            tys.insert(0, ty1);
+            Tuple(tys)
+        }
+    })
     .parse(input)
 }
 
+/// Parses the fields `x : A, y : B` of a record or variant type delimited by `open` and `close`,
+/// sorted by their labels.
+fn parse_labelled_types(
+    open: char,
+    close: char,
+) -> impl FnMut(&str) -> IResult<&str, Vec<(std::string::String, Type)>> {
+    move |input| {
+        let field = (parse_variable_name, ws0(char(':')), parse_type).map(|(l, _, ty)| (l, ty));
+        delimited(
+            (char(open), multispace0),
+            separated_list0(ws0(char(',')), field),
+            (multispace0, char(close)),
+        )
+        .map(|mut fields| {
+            fields.sort_by(|(l1, _), (l2, _)| l1.cmp(l2));
+            fields
+        })
+        .parse(input)
+    }
+}
+
+/// Parses a record type `{x : A, y : B}`.
+///
+/// Duplicate labels are reported by the type checker, see [`TypeError::DuplicateLabel`].
+///
+/// [`TypeError::DuplicateLabel`]: crate::r#type::TypeError::DuplicateLabel
+fn parse_record_type(input: &str) -> IResult<&str, Type> {
+    parse_labelled_types('{', '}').map(Record).parse(input)
+}
+
+/// Parses a variant type `<some : A, none : B>`.
+///
+/// Duplicate labels are reported by the type checker, see [`TypeError::DuplicateLabel`].
+///
+/// [`TypeError::DuplicateLabel`]: crate::r#type::TypeError::DuplicateLabel
+fn parse_variant_type(input: &str) -> IResult<&str, Type> {
+    parse_labelled_types('<', '>').map(Variant).parse(input)
+}
+
 /// Parses a sum type associating to the left.
 ///
 /// `A + B + C` is parsed as `(A + B) + C`
 fn parse_sum_type(input: &str) -> IResult<&str, Type> {
-    let (rest, ty1) = parse_type_primary.parse(input)?;
+    let (mut rest, mut ty) = parse_type_primary.parse(input)?;
 
-    fold_many0(
-        (ws0(char('+')), parse_type_primary),
-        move || ty1.clone(),
-        |lhs, (_, rhs)| Sum(Box::new(lhs), Box::new(rhs)),
-    )
-    .parse(rest)
+    while let Ok((after, (_, rhs))) = (ws0(char('+')), parse_type_primary).parse(rest) {
+        ty = Sum(Box::new(ty), Box::new(rhs));
+        record_type(input, after, &ty);
+        rest = after;
+    }
+    Ok((rest, ty))
 }
 
 pub fn parse_type_primary(input: &str) -> IResult<&str, Type> {
-    alt((
+    let (rest, ty) = alt((
         parse_base_type,
         parse_prod_type,
         parse_paren_type,
         parse_list_type_parens,
+        parse_record_type,
+        parse_variant_type,
     ))
-    .parse(input)
+    .parse(input)?;
+    record_type(input, rest, &ty);
+    Ok((rest, ty))
 }
 
 pub fn parse_type(input: &str) -> IResult<&str, Type> {
-    alt((parse_arrow_type, parse_list_type)).parse(input)
+    let (rest, ty) = alt((parse_forall_type, parse_rec_type, parse_arrow_type, parse_list_type)).parse(input)?;
+    record_type(input, rest, &ty);
+    Ok((rest, ty))
 }
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+
+use super::Type::{self, *};
+// The standard string takes precedence over the string type
+use std::string::String;
+
+/// A failed [`Unifier::unify`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnifyError {
+    /// The types have different shapes.
+    Mismatch,
+    /// The metavariable would have to be bound to a type containing itself.
+    Occurs(usize, Type),
+}
+
+/// A substitution of metavariables, see [`Type::Meta`], built up by unification.
+///
+/// Named type variables [`Type::Var`] are rigid, they only unify with themselves, and so are data
+/// types [`Type::Named`].
+#[derive(Debug, Clone, Default)]
+pub struct Unifier {
+    bindings: Vec<Option<Type>>,
+}
+
+impl Unifier {
+    /// Returns a new unbound metavariable.
+    pub fn fresh(&mut self) -> Type {
+        self.bindings.push(None);
+        Meta(self.bindings.len() - 1)
+    }
+
+    /// Replaces every [`Type::Hole`] in `ty` with a fresh metavariable.
+    pub fn instantiate(&mut self, ty: &Type) -> Type {
+        match ty {
+            Hole => self.fresh(),
+            ty => ty.map_children(|ty| self.instantiate(ty)),
+        }
+    }
+
+    /// Follows the bindings of the metavariable at the root of `ty`.
+    pub fn shallow<'t>(&'t self, mut ty: &'t Type) -> &'t Type {
+        while let Meta(m) = ty {
+            match &self.bindings[*m] {
+                Some(bound) => ty = bound,
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// Applies the substitution to `ty`, leaving only unbound metavariables.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        self.shallow(ty).map_children(|ty| self.resolve(ty))
+    }
+
+    fn occurs(&self, m: usize, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Meta(n) => m == *n,
+            ty => ty.children().into_iter().any(|ty| self.occurs(m, ty)),
+        }
+    }
+
+    /// Unifies `ty1` and `ty2`, binding metavariables so that both resolve to the same type.
+    ///
+    /// Polymorphic and recursive types unify if they are equal up to the names of their type variables.
+    /// Recursive types are iso-recursive, so `μa. T` does not unify with its unrolling.
+    /// On failure, the bindings made before the conflict was found are kept.
+    pub fn unify(&mut self, ty1: &Type, ty2: &Type) -> Result<(), UnifyError> {
+        let ty1 = self.shallow(ty1).clone();
+        let ty2 = self.shallow(ty2).clone();
+        match (ty1, ty2) {
+            (Meta(m), Meta(n)) if m == n => Ok(()),
+            (Meta(m), ty) | (ty, Meta(m)) => {
+                if self.occurs(m, &ty) {
+                    Err(UnifyError::Occurs(m, self.resolve(&ty)))
+                } else {
+                    self.bindings[m] = Some(ty);
+                    Ok(())
+                }
+            }
+            (Unit, Unit)
+            | (Boolean, Boolean)
+            | (Integer, Integer)
+            | (Type::String, Type::String)
+            | (Char, Char) => Ok(()),
+            (Var(a), Var(b)) | (Named(a), Named(b)) if a == b => Ok(()),
+            (List(ty1), List(ty2)) => self.unify(&ty1, &ty2),
+            (Record(fields1), Record(fields2)) | (Variant(fields1), Variant(fields2))
+                if fields1.iter().map(|(l, _)| l).eq(fields2.iter().map(|(l, _)| l)) =>
+            {
+                fields1
+                    .iter()
+                    .zip(&fields2)
+                    .try_for_each(|((_, ty1), (_, ty2))| self.unify(ty1, ty2))
+            }
+            (Tuple(tys1), Tuple(tys2)) if tys1.len() == tys2.len() => tys1
+                .iter()
+                .zip(&tys2)
+                .try_for_each(|(ty1, ty2)| self.unify(ty1, ty2)),
+            (Arrow(a1, b1), Arrow(a2, b2))
+            | (Prod(a1, b1), Prod(a2, b2))
+            | (Sum(a1, b1), Sum(a2, b2)) => {
+                self.unify(&a1, &a2)?;
+                self.unify(&b1, &b2)
+            }
+            (Forall(vars1, ty1), Forall(vars2, ty2)) if vars1.len() == vars2.len() => {
+                let renaming = vars2
+                    .into_iter()
+                    .zip(vars1.iter().map(|a| Var(a.clone())))
+                    .collect();
+                self.unify(&ty1, &ty2.subst_type_vars(&renaming))
+            }
+            (Rec(a, ty1), Rec(b, ty2)) => {
+                let renaming = HashMap::from([(b, Var(a))]);
+                self.unify(&ty1, &ty2.subst_type_vars(&renaming))
+            }
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    /// Returns the unbound metavariables of `ty`, in the order they appear.
+    pub fn metas(&self, ty: &Type) -> Vec<usize> {
+        fn go(unifier: &Unifier, ty: &Type, metas: &mut Vec<usize>) {
+            match unifier.shallow(ty) {
+                Meta(m) => {
+                    if !metas.contains(m) {
+                        metas.push(*m)
+                    }
+                }
+                ty => ty.children().into_iter().for_each(|ty| go(unifier, ty, metas)),
+            }
+        }
+        let mut metas = Vec::new();
+        go(self, ty, &mut metas);
+        metas
+    }
+
+    /// Replaces the unbound metavariables of `ty` which are not in `fixed` with type variables,
+    /// returning them along with the names they were given.
+    ///
+    /// The metavariables are named `a`, `b`, ... avoiding the type variables occurring in `ty`.
+    pub fn generalize(&self, ty: &Type, fixed: &HashSet<usize>) -> (Vec<(usize, String)>, Type) {
+        let ty = self.resolve(ty);
+        let mut avoid: HashSet<String> = ty.free_type_vars().into_iter().collect();
+        if let Forall(vars, _) = &ty {
+            avoid.extend(vars.iter().cloned());
+        }
+        let mut names = HashMap::new();
+        let mut vars = Vec::new();
+        for m in self.metas(&ty).into_iter().filter(|m| !fixed.contains(m)) {
+            let name = type_var_name(&avoid);
+            avoid.insert(name.clone());
+            names.insert(m, Var(name.clone()));
+            vars.push((m, name));
+        }
+        (vars, replace_metas(&ty, &names))
+    }
+}
+
+/// Returns the first of `a`, `b`, ..., `z`, `a1`, `a2`, ... not in `avoid`.
+fn type_var_name(avoid: &HashSet<String>) -> String {
+    ('a'..='z')
+        .map(String::from)
+        .find(|name| !avoid.contains(name))
+        .unwrap_or_else(|| crate::term::subst::fresh_name("a", avoid))
+}
+
+fn replace_metas(ty: &Type, names: &HashMap<usize, Type>) -> Type {
+    match ty {
+        Meta(m) => names.get(m).cloned().unwrap_or_else(|| ty.clone()),
+        ty => ty.map_children(|ty| replace_metas(ty, names)),
+    }
+}
+
+/// Renumbers the metavariables of `ty` from `0` in the order they appear.
+pub fn normalize(ty: &Type) -> Type {
+    fn go(ty: &Type, names: &mut HashMap<usize, usize>) -> Type {
+        match ty {
+            Meta(m) => {
+                let next = names.len();
+                Meta(*names.entry(*m).or_insert(next))
+            }
+            ty => ty.map_children(|ty| go(ty, names)),
+        }
+    }
+    go(ty, &mut HashMap::new())
+}
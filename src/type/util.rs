@@ -6,9 +6,37 @@ pub fn arrow(ty1: impl Into<Box<Type>>, ty2: impl Into<Box<Type>>) -> Type {
 pub fn prod(ty1: impl Into<Box<Type>>, ty2: impl Into<Box<Type>>) -> Type {
     Prod(ty1.into(), ty2.into())
 }
+pub fn tuple(tys: impl IntoIterator<Item = Type>) -> Type {
+    Tuple(tys.into_iter().collect())
+}
+/// Builds a record type, sorting the fields by their labels.
+pub fn record<S: ToString>(fields: impl IntoIterator<Item = (S, Type)>) -> Type {
+    let mut fields: Vec<_> = fields.into_iter().map(|(l, ty)| (l.to_string(), ty)).collect();
+    fields.sort_by(|(l1, _), (l2, _)| l1.cmp(l2));
+    Record(fields)
+}
+/// Builds a variant type, sorting the labels.
+pub fn variant<S: ToString>(fields: impl IntoIterator<Item = (S, Type)>) -> Type {
+    let mut fields: Vec<_> = fields.into_iter().map(|(l, ty)| (l.to_string(), ty)).collect();
+    fields.sort_by(|(l1, _), (l2, _)| l1.cmp(l2));
+    Variant(fields)
+}
 pub fn list(ty: impl Into<Box<Type>>) -> Type {
     List(ty.into())
 }
 pub fn sum(ty1: impl Into<Box<Type>>, ty2: impl Into<Box<Type>>) -> Type {
     Sum(ty1.into(), ty2.into())
 }
+pub fn tvar(name: impl ToString) -> Type {
+    Var(name.to_string())
+}
+pub fn named(name: impl ToString) -> Type {
+    Named(name.to_string())
+}
+pub fn forall<S: ToString>(vars: impl IntoIterator<Item = S>, ty: impl Into<Box<Type>>) -> Type {
+    Forall(vars.into_iter().map(|a| a.to_string()).collect(), ty.into())
+}
+
+pub fn rec(var: impl ToString, ty: impl Into<Box<Type>>) -> Type {
+    Rec(var.to_string(), ty.into())
+}
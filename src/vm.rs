@@ -0,0 +1,858 @@
+//! # Bytecode VM
+//!
+//! A compiler from [`Term`]s to a compact bytecode, see [`compile`], and a stack machine running
+//! it, see [`Program::run`].
+//!
+//! Each call to an abstraction gets a frame of slots on the stack, holding the argument, the
+//! variables bound by `let` and the case analyses, and the intermediate results. Variables are
+//! resolved to their slot at compile time, abstractions capture the values of their free
+//! variables into a closure when they are evaluated. A type abstraction is compiled to a closure
+//! without an argument, which is run with its type variable bound to the type it is applied to.
+//! The bound types are only used to read the annotations of values back.
+//!
+//! ```rust
+//! # use stlc_project::term::util::*;
+//! # use stlc_project::vm;
+//! let t = app(abs("x", Integer, add("x", 1)), 2);
+//! let program = vm::compile(&t);
+//! assert_eq!(program.run().unwrap().to_term(), t.multistep());
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::r#type::Type;
+use crate::arith::{checked, Arithmetic, Integer};
+use crate::eval::TypeEnv;
+use crate::span::Located;
+use crate::term::{EvalError, StuckError, Term};
+
+pub mod compile;
+
+pub use compile::compile;
+
+/// Where the value of a variable is found at runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    /// A slot of the current frame, counted from its base
+    Local(usize),
+    /// A value captured by the current closure
+    Capture(usize),
+}
+
+/// A compiled abstraction or type abstraction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function<'t> {
+    /// Address of the first instruction of the body
+    pub entry: usize,
+    /// The source [`Term::Abs`], [`Term::TAbs`] or [`Term::LetRec`], used to convert closures back
+    /// to terms
+    pub source: &'t Term,
+    /// The free variables of the abstraction and where to find them when creating a closure
+    pub captures: Vec<(String, Slot)>,
+}
+
+/// A bytecode instruction.
+///
+/// Instructions pop their operands from the stack and push their result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr<'t> {
+    Unit,
+    Int(Integer),
+    Str(Rc<str>),
+    Char(char),
+    Bool(bool),
+    Nil(Rc<Type>),
+    /// Pushes the value of a slot
+    Load(Slot),
+    /// Fails with [`EvalError::FreeVariable`]
+    Free(String),
+    /// Pops the result, drops the `n` slots below it and pushes the result back
+    Slide(usize),
+    /// Pushes a closure of the function
+    Closure(Rc<Function<'t>>),
+    /// Pops an argument and a closure, and calls the closure
+    Call,
+    /// Like [`Instr::Call`], but replaces the current frame
+    TailCall,
+    /// Pops the result and returns it to the caller
+    Return,
+    /// Pops a closure and calls it with its own fixed point
+    Fix,
+    /// Pushes the recursive bindings of a group of closures of the functions, see [`Value::Rec`]
+    LetRec(Rc<[Rc<Function<'t>>]>),
+    /// Pops a closure of a type abstraction and calls it without an argument, binding its type
+    /// variable to the type
+    TApp(Rc<Type>),
+    Jump(usize),
+    /// Pops a boolean and jumps if it is false
+    JumpIfFalse(usize),
+    Not,
+    Add,
+    Sub,
+    Mul,
+    /// Pops the divisor and the dividend, failing with [`EvalError::DivisionByZero`] if the divisor is zero
+    Div,
+    Mod,
+    Neg,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Concat,
+    StrLen,
+    CharAt,
+    IntToString,
+    Pair,
+    Fst,
+    Snd,
+    /// Pops the given number of values and pushes them as a tuple
+    Tuple(usize),
+    /// Pops a tuple or pair and pushes its component at the given index
+    Proj(usize),
+    /// Pops a value for each of the labels and pushes them as a record
+    Record(Rc<[Rc<str>]>),
+    /// Pops a record and pushes its field with the label
+    Field(Rc<str>),
+    /// Pops a value for each of the labels and a record, and pushes the record with the fields replaced
+    Update(Rc<[Rc<str>]>),
+    Cons,
+    /// Pops two lists and pushes their concatenation
+    Append,
+    Inl(Rc<Type>),
+    Inr(Rc<Type>),
+    /// Pops a list, falls through if it is empty, otherwise pushes its head and tail and jumps
+    ListCase(usize),
+    /// Pops a sum and pushes its content, falls through if it is a left injection, otherwise jumps
+    SumCase(usize),
+    Tag(Rc<str>, Rc<Type>),
+    /// Pops a variant, pushes its content and jumps to the target of its label
+    VariantCase(Rc<[(Rc<str>, usize)]>),
+    Fold(Rc<Type>),
+    /// Pops a fold and pushes its content
+    Unfold,
+}
+
+/// A compiled term, see [`compile`], which borrows the subterms of the term it was compiled from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<'t> {
+    /// The compiled term
+    pub term: &'t Term,
+    pub code: Vec<Instr<'t>>,
+    /// The source terms of the instructions which may fail, used for errors
+    pub redexes: HashMap<usize, &'t Term>,
+}
+
+/// A value computed by the VM.
+#[derive(Debug, Clone)]
+pub enum Value<'t> {
+    Unit,
+    Bool(bool),
+    Int(Integer),
+    Str(Rc<str>),
+    Char(char),
+    Closure(Rc<Closure<'t>>),
+    Pair(Rc<Value<'t>>, Rc<Value<'t>>),
+    Tuple(Rc<[Value<'t>]>),
+    Record(Rc<[(Rc<str>, Value<'t>)]>),
+    Nil(Rc<Type>),
+    Cons(Rc<Value<'t>>, Rc<Value<'t>>),
+    Inl(Rc<Value<'t>>, Rc<Type>),
+    Inr(Rc<Value<'t>>, Rc<Type>),
+    Tag(Rc<str>, Rc<Value<'t>>, Rc<Type>),
+    Fold(Rc<Value<'t>>, Rc<Type>),
+    /// `fix` of the closure, only stored in slots as it is unfolded whenever it is loaded
+    Fix(Rc<Closure<'t>>),
+    /// The recursive binding at the index in a group of closures, each of them computing the value
+    /// of its binding from all the bindings of the group. Only stored in slots as it is unfolded
+    /// whenever it is loaded
+    Rec(Rc<[Rc<Closure<'t>>]>, usize),
+}
+
+/// A function along with the values of its free variables.
+#[derive(Debug, Clone)]
+pub struct Closure<'t> {
+    pub function: Rc<Function<'t>>,
+    pub captures: Vec<Value<'t>>,
+    /// The types bound to the type variables of the enclosing type abstractions
+    types: TypeEnv,
+}
+
+impl Closure<'_> {
+    /// Converts the closure to an abstraction, substituting the bound types and the captured
+    /// values into its body.
+    pub fn to_term(&self) -> Term {
+        self.close(self.function.source.clone())
+    }
+
+    /// Substitutes the bound types and the captured values into `t`.
+    fn close(&self, t: Term) -> Term {
+        self.function
+            .captures
+            .iter()
+            .zip(&self.captures)
+            .fold(self.types.close(t), |t, ((x, _), v)| t.subst(x, v.to_term()))
+    }
+
+    fn is_abs(&self) -> bool {
+        matches!(self.function.source, Term::Abs { .. })
+    }
+}
+
+impl Value<'_> {
+    /// Converts the value back to a term, which is a value in the sense of [`Term::is_value`].
+    pub fn to_term(&self) -> Term {
+        match self {
+            Value::Unit => Term::Unit,
+            Value::Bool(true) => Term::True,
+            Value::Bool(false) => Term::False,
+            Value::Int(i) => Term::Int(i.clone()),
+            Value::Str(s) => Term::Str(s.to_string()),
+            Value::Char(c) => Term::Char(*c),
+            Value::Closure(c) => c.to_term(),
+            Value::Pair(v1, v2) => Term::Pair(Box::new(v1.to_term()), Box::new(v2.to_term())),
+            Value::Tuple(vs) => Term::Tuple(vs.iter().map(Value::to_term).collect()),
+            Value::Record(fields) => Term::Record(
+                fields
+                    .iter()
+                    .map(|(l, v)| (l.to_string(), v.to_term()))
+                    .collect(),
+            ),
+            Value::Nil(ty) => Term::Nil((**ty).clone()),
+            Value::Cons(v1, v2) => Term::Cons(Box::new(v1.to_term()), Box::new(v2.to_term())),
+            Value::Inl(v, ty) => Term::Inl(Box::new(v.to_term()), (**ty).clone()),
+            Value::Inr(v, ty) => Term::Inr(Box::new(v.to_term()), (**ty).clone()),
+            Value::Tag(l, v, ty) => Term::Tag(l.to_string(), Box::new(v.to_term()), (**ty).clone()),
+            Value::Fold(v, ty) => Term::Fold(Box::new(v.to_term()), (**ty).clone()),
+            Value::Fix(c) => Term::Fix(Box::new(c.to_term())),
+            Value::Rec(group, i) => {
+                let Term::LetRec { bindings, .. } = group[*i].function.source else {
+                    unreachable!("closures of recursive bindings to be of a let rec")
+                };
+                group[*i].close(Term::LetRec {
+                    bindings: bindings.clone(),
+                    body: Box::new(bindings[*i].2.clone()),
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_term())
+    }
+}
+
+/// Compares two values structurally.
+///
+/// Returns the offending value if either of the values contains a closure.
+fn values_equal<'v, 't>(
+    v1: &'v Value<'t>,
+    v2: &'v Value<'t>,
+) -> Result<bool, &'v Value<'t>> {
+    match (v1, v2) {
+        (Value::Closure(_) | Value::Fix(_) | Value::Rec(..), _) => Err(v1),
+        (_, Value::Closure(_) | Value::Fix(_) | Value::Rec(..)) => Err(v2),
+        (Value::Unit, Value::Unit) => Ok(true),
+        (Value::Int(i1), Value::Int(i2)) => Ok(i1 == i2),
+        (Value::Str(s1), Value::Str(s2)) => Ok(s1 == s2),
+        (Value::Char(c1), Value::Char(c2)) => Ok(c1 == c2),
+        (Value::Bool(b1), Value::Bool(b2)) => Ok(b1 == b2),
+        (Value::Nil(_), Value::Nil(_)) => Ok(true),
+        (Value::Pair(a1, b1), Value::Pair(a2, b2)) | (Value::Cons(a1, b1), Value::Cons(a2, b2)) => {
+            let first = values_equal(a1, a2)?;
+            Ok(values_equal(b1, b2)? && first)
+        }
+        (Value::Tuple(vs1), Value::Tuple(vs2)) => {
+            let mut equal = vs1.len() == vs2.len();
+            for (v1, v2) in vs1.iter().zip(vs2.iter()) {
+                equal = values_equal(v1, v2)? && equal;
+            }
+            Ok(equal)
+        }
+        (Value::Record(fields1), Value::Record(fields2)) => {
+            let mut equal = fields1.len() == fields2.len();
+            for (l, v1) in fields1.iter() {
+                equal = match fields2.iter().find(|(l2, _)| l2 == l) {
+                    Some((_, v2)) => values_equal(v1, v2)? && equal,
+                    None => false,
+                };
+            }
+            Ok(equal)
+        }
+        (Value::Inl(v1, _), Value::Inl(v2, _)) | (Value::Inr(v1, _), Value::Inr(v2, _)) => {
+            values_equal(v1, v2)
+        }
+        (Value::Tag(l1, v1, _), Value::Tag(l2, v2, _)) => Ok(values_equal(v1, v2)? && l1 == l2),
+        (Value::Fold(v1, _), Value::Fold(v2, _)) => values_equal(v1, v2),
+        _ => Ok(false),
+    }
+}
+
+/// Orders two integers, characters or strings.
+///
+/// Returns the constructor of the error and the offending value if they are not both of the same
+/// of these types, where the first value determines the expected type of the second one.
+fn compare<'v, 't>(
+    v1: &'v Value<'t>,
+    v2: &'v Value<'t>,
+) -> Result<Ordering, (StuckError, &'v Value<'t>)> {
+    match (v1, v2) {
+        (Value::Int(i1), Value::Int(i2)) => Ok(i1.cmp(i2)),
+        (Value::Char(c1), Value::Char(c2)) => Ok(c1.cmp(c2)),
+        (Value::Str(s1), Value::Str(s2)) => Ok(s1.cmp(s2)),
+        (Value::Int(_), _) => Err((not_an_integer, v2)),
+        (Value::Char(_), _) => Err((not_a_char, v2)),
+        (Value::Str(_), _) => Err((not_a_string, v2)),
+        _ => Err((not_an_integer, v1)),
+    }
+}
+
+/// The state of the caller saved by a call.
+struct Frame<'t> {
+    closure: Option<Rc<Closure<'t>>>,
+    types: TypeEnv,
+    base: usize,
+    ret: usize,
+}
+
+struct Machine<'p, 't> {
+    program: &'p Program<'t>,
+    stack: Vec<Value<'t>>,
+    frames: Vec<Frame<'t>>,
+    /// The closure being run, `None` at the top level
+    closure: Option<Rc<Closure<'t>>>,
+    /// The types bound to the type variables of the closure being run
+    types: TypeEnv,
+    /// Index of the first slot of the current frame
+    base: usize,
+    pc: usize,
+}
+
+impl<'t> Machine<'_, 't> {
+    fn push(&mut self, v: Value<'t>) {
+        self.stack.push(v)
+    }
+
+    fn pop(&mut self) -> Value<'t> {
+        self.stack.pop().expect("compiled code to keep the stack balanced")
+    }
+
+    /// Builds the error for the instruction at `pc`, which got stuck on `value`.
+    fn stuck(
+        &self,
+        pc: usize,
+        ctor: fn(Box<Term>, Box<Term>) -> EvalError,
+        value: &Value<'t>,
+    ) -> EvalError {
+        let redex = self.program.redexes[&pc].clone();
+        ctor(Box::new(redex), Box::new(value.to_term()))
+    }
+
+    /// Calls `c` with the arguments `args`: none for a type abstraction, one for an abstraction,
+    /// and the whole group for a recursive binding.
+    fn call(&mut self, c: Rc<Closure<'t>>, args: impl IntoIterator<Item = Value<'t>>) {
+        self.frames.push(Frame {
+            closure: self.closure.take(),
+            types: std::mem::replace(&mut self.types, c.types.clone()),
+            base: self.base,
+            ret: self.pc,
+        });
+        self.base = self.stack.len();
+        self.stack.extend(args);
+        self.pc = c.function.entry;
+        self.closure = Some(c);
+    }
+
+    fn tail_call(&mut self, c: Rc<Closure<'t>>, arg: Value<'t>) {
+        self.stack.truncate(self.base);
+        self.push(arg);
+        self.types = c.types.clone();
+        self.pc = c.function.entry;
+        self.closure = Some(c);
+    }
+
+    /// Returns the type annotation `ty` with the types bound by the current closure substituted.
+    fn annotation(&self, ty: &Rc<Type>) -> Rc<Type> {
+        if self.types.is_empty() {
+            ty.clone()
+        } else {
+            Rc::new(self.types.close_type(ty))
+        }
+    }
+
+    /// Creates a closure of `function`, capturing its free variables from the current frame.
+    fn closure(&self, function: &Rc<Function<'t>>) -> Rc<Closure<'t>> {
+        let captures = function
+            .captures
+            .iter()
+            .map(|(_, slot)| match *slot {
+                Slot::Local(i) => self.stack[self.base + i].clone(),
+                Slot::Capture(i) => self.closure.as_ref().unwrap().captures[i].clone(),
+            })
+            .collect();
+        Rc::new(Closure {
+            function: function.clone(),
+            captures,
+            types: self.types.clone(),
+        })
+    }
+
+    /// Pushes `v`, unfolding it if it is a fixed point or a recursive binding.
+    fn load(&mut self, v: Value<'t>) {
+        match v {
+            Value::Fix(c) => self.call(c.clone(), Some(Value::Fix(c))),
+            Value::Rec(group, i) => {
+                let args = (0..group.len()).map(|j| Value::Rec(group.clone(), j));
+                self.call(group[i].clone(), args)
+            }
+            v => self.push(v),
+        }
+    }
+
+    fn pop_int(&mut self, pc: usize) -> Result<Integer, EvalError> {
+        match self.pop() {
+            Value::Int(i) => Ok(i),
+            v => Err(self.stuck(pc, not_an_integer, &v)),
+        }
+    }
+
+    /// Pushes the result of checked arithmetic, failing with [`EvalError::Overflow`] on `None`.
+    fn arith(&mut self, pc: usize, i: Option<Integer>) -> Result<(), EvalError> {
+        match i {
+            Some(i) => {
+                self.push(Value::Int(i));
+                Ok(())
+            }
+            None => {
+                let redex = self.program.redexes[&pc].clone();
+                Err(EvalError::Overflow {
+                    redex: Box::new(redex),
+                })
+            }
+        }
+    }
+
+    fn int_op2(
+        &mut self,
+        pc: usize,
+        op: fn(&Integer, &Integer) -> Integer,
+    ) -> Result<(), EvalError> {
+        let i2 = self.pop_int(pc)?;
+        let i1 = self.pop_int(pc)?;
+        self.arith(pc, checked(op, &i1, &i2))
+    }
+
+    /// Pops a division or remainder of two integers, failing if the divisor is zero.
+    fn div(&mut self, pc: usize, op: fn(&Integer, &Integer) -> Integer) -> Result<(), EvalError> {
+        let i2 = self.pop_int(pc)?;
+        let i1 = self.pop_int(pc)?;
+        if i2.is_zero() {
+            let redex = self.program.redexes[&pc].clone();
+            return Err(EvalError::DivisionByZero {
+                redex: Box::new(redex),
+            });
+        }
+        self.arith(pc, checked(op, &i1, &i2))
+    }
+
+    fn pop_str(&mut self, pc: usize) -> Result<Rc<str>, EvalError> {
+        match self.pop() {
+            Value::Str(s) => Ok(s),
+            v => Err(self.stuck(pc, not_a_string, &v)),
+        }
+    }
+
+    /// Pops the ordering of two values, pushing whether it satisfies `op`.
+    fn ord(&mut self, pc: usize, op: fn(Ordering) -> bool) -> Result<(), EvalError> {
+        let v2 = self.pop();
+        let v1 = self.pop();
+        match compare(&v1, &v2) {
+            Ok(ordering) => {
+                self.push(Value::Bool(op(ordering)));
+                Ok(())
+            }
+            Err((ctor, v)) => Err(self.stuck(pc, ctor, v)),
+        }
+    }
+
+    /// Pops the equality of two values, pushing whether it is `expected`.
+    fn eq(&mut self, pc: usize, expected: bool) -> Result<(), EvalError> {
+        let v2 = self.pop();
+        let v1 = self.pop();
+        match values_equal(&v1, &v2) {
+            Ok(equal) => {
+                self.push(Value::Bool(equal == expected));
+                Ok(())
+            }
+            Err(v) => Err(self.stuck(pc, not_comparable, v)),
+        }
+    }
+
+    /// Pops the argument and the closure of a call.
+    fn pop_call(&mut self, pc: usize) -> Result<(Rc<Closure<'t>>, Value<'t>), EvalError> {
+        let arg = self.pop();
+        match self.pop() {
+            Value::Closure(c) if c.is_abs() => Ok((c, arg)),
+            v => Err(self.stuck(pc, not_a_function, &v)),
+        }
+    }
+
+    fn run(&mut self) -> Result<Value<'t>, EvalError> {
+        loop {
+            let pc = self.pc;
+            self.pc += 1;
+            match &self.program.code[pc] {
+                Instr::Unit => self.push(Value::Unit),
+                Instr::Int(i) => self.push(Value::Int(i.clone())),
+                Instr::Str(s) => self.push(Value::Str(s.clone())),
+                Instr::Char(c) => self.push(Value::Char(*c)),
+                Instr::Bool(b) => self.push(Value::Bool(*b)),
+                Instr::Nil(ty) => self.push(Value::Nil(self.annotation(ty))),
+                Instr::Load(Slot::Local(i)) => self.load(self.stack[self.base + i].clone()),
+                Instr::Load(Slot::Capture(i)) => {
+                    let closure = self.closure.as_ref().expect("captures to be in a closure");
+                    self.load(closure.captures[*i].clone())
+                }
+                Instr::Free(x) => return Err(EvalError::FreeVariable(x.clone())),
+                Instr::Slide(n) => {
+                    let v = self.pop();
+                    self.stack.truncate(self.stack.len() - n);
+                    self.push(v);
+                }
+                Instr::Closure(function) => {
+                    let c = self.closure(function);
+                    self.push(Value::Closure(c));
+                }
+                Instr::LetRec(functions) => {
+                    let group: Rc<[_]> = functions.iter().map(|f| self.closure(f)).collect();
+                    for i in 0..group.len() {
+                        self.push(Value::Rec(group.clone(), i));
+                    }
+                }
+                Instr::Call => {
+                    let (c, arg) = self.pop_call(pc)?;
+                    self.call(c, Some(arg))
+                }
+                Instr::TailCall => {
+                    let (c, arg) = self.pop_call(pc)?;
+                    self.tail_call(c, arg)
+                }
+                Instr::Return => {
+                    let v = self.pop();
+                    self.stack.truncate(self.base);
+                    let Some(frame) = self.frames.pop() else {
+                        return Ok(v);
+                    };
+                    self.closure = frame.closure;
+                    self.types = frame.types;
+                    self.base = frame.base;
+                    self.pc = frame.ret;
+                    self.push(v);
+                }
+                Instr::Fix => match self.pop() {
+                    Value::Closure(c) if c.is_abs() => self.call(c.clone(), Some(Value::Fix(c))),
+                    v => return Err(self.stuck(pc, not_a_function, &v)),
+                },
+                Instr::TApp(ty) => match self.pop() {
+                    Value::Closure(c) if !c.is_abs() => {
+                        let Term::TAbs { var, .. } = &c.function.source else {
+                            unreachable!("closures of non-abstractions to be type abstractions")
+                        };
+                        let types = c.types.bind(var, self.types.close_type(ty));
+                        self.call(c, None);
+                        self.types = types;
+                    }
+                    v => return Err(self.stuck(pc, not_a_type_abstraction, &v)),
+                },
+                Instr::Jump(target) => self.pc = *target,
+                Instr::JumpIfFalse(target) => match self.pop() {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => self.pc = *target,
+                    v => return Err(self.stuck(pc, not_a_boolean, &v)),
+                },
+
+                Instr::Add => self.int_op2(pc, Integer::add)?,
+                Instr::Sub => self.int_op2(pc, Integer::sub)?,
+                Instr::Mul => self.int_op2(pc, Integer::mul)?,
+                Instr::Div => self.div(pc, Integer::div)?,
+                Instr::Mod => self.div(pc, Integer::rem)?,
+                Instr::Not => match self.pop() {
+                    Value::Bool(b) => self.push(Value::Bool(!b)),
+                    v => return Err(self.stuck(pc, not_a_boolean, &v)),
+                },
+                Instr::Neg => {
+                    let i = self.pop_int(pc)?;
+                    self.arith(pc, Arithmetic::current().check(i.neg()))?;
+                }
+                Instr::Eq => self.eq(pc, true)?,
+                Instr::Ne => self.eq(pc, false)?,
+                Instr::Lt => self.ord(pc, Ordering::is_lt)?,
+                Instr::Le => self.ord(pc, Ordering::is_le)?,
+                Instr::Gt => self.ord(pc, Ordering::is_gt)?,
+                Instr::Ge => self.ord(pc, Ordering::is_ge)?,
+
+                Instr::Concat => {
+                    let s2 = self.pop_str(pc)?;
+                    let s1 = self.pop_str(pc)?;
+                    self.push(Value::Str(format!("{s1}{s2}").into()));
+                }
+                Instr::StrLen => {
+                    let s = self.pop_str(pc)?;
+                    self.push(Value::Int(s.chars().count().into()));
+                }
+                Instr::CharAt => {
+                    let i = self.pop_int(pc)?;
+                    let s = self.pop_str(pc)?;
+                    match i.to_usize().and_then(|i| s.chars().nth(i)) {
+                        Some(c) => self.push(Value::Char(c)),
+                        None => return Err(self.stuck(pc, index_out_of_bounds, &Value::Int(i))),
+                    }
+                }
+                Instr::IntToString => {
+                    let i = self.pop_int(pc)?;
+                    self.push(Value::Str(i.to_string().into()));
+                }
+
+                Instr::Pair => {
+                    let v2 = self.pop();
+                    let v1 = self.pop();
+                    self.push(Value::Pair(Rc::new(v1), Rc::new(v2)));
+                }
+                Instr::Fst => match self.pop() {
+                    Value::Pair(v1, _) => self.push((*v1).clone()),
+                    v => return Err(self.stuck(pc, not_a_pair, &v)),
+                },
+                Instr::Snd => match self.pop() {
+                    Value::Pair(_, v2) => self.push((*v2).clone()),
+                    v => return Err(self.stuck(pc, not_a_pair, &v)),
+                },
+                Instr::Tuple(n) => {
+                    let vs = self.stack.split_off(self.stack.len() - n);
+                    self.push(Value::Tuple(vs.into()));
+                }
+                Instr::Proj(i) => match (self.pop(), *i) {
+                    (Value::Pair(v1, _), 0) => self.push((*v1).clone()),
+                    (Value::Pair(_, v2), 1) => self.push((*v2).clone()),
+                    (Value::Tuple(vs), i) if i < vs.len() => self.push(vs[i].clone()),
+                    (v, _) => return Err(self.stuck(pc, not_a_tuple, &v)),
+                },
+                Instr::Record(labels) => {
+                    let vs = self.stack.split_off(self.stack.len() - labels.len());
+                    let fields = labels.iter().cloned().zip(vs).collect();
+                    self.push(Value::Record(fields));
+                }
+                Instr::Field(l) => {
+                    let v = self.pop();
+                    let field = match &v {
+                        Value::Record(fields) => fields.iter().find(|(l2, _)| l2 == l),
+                        _ => None,
+                    };
+                    match field {
+                        Some((_, field)) => self.push(field.clone()),
+                        None => return Err(self.stuck(pc, not_a_record, &v)),
+                    }
+                }
+                Instr::Update(labels) => {
+                    let updates = self.stack.split_off(self.stack.len() - labels.len());
+                    match self.pop() {
+                        Value::Record(fields)
+                            if labels.iter().all(|l| fields.iter().any(|(l2, _)| l2 == l)) =>
+                        {
+                            let mut fields = fields.to_vec();
+                            for (l, u) in labels.iter().zip(updates) {
+                                let field = fields.iter_mut().find(|(l2, _)| l2 == l);
+                                field.expect("the field to exist").1 = u;
+                            }
+                            self.push(Value::Record(fields.into()));
+                        }
+                        v => return Err(self.stuck(pc, not_a_record, &v)),
+                    }
+                }
+
+                Instr::Cons => {
+                    let tl = self.pop();
+                    let h = self.pop();
+                    self.push(Value::Cons(Rc::new(h), Rc::new(tl)));
+                }
+                Instr::ListCase(target) => match self.pop() {
+                    Value::Nil(_) => {}
+                    Value::Cons(h, tl) => {
+                        self.push((*h).clone());
+                        self.push((*tl).clone());
+                        self.pc = *target;
+                    }
+                    v => return Err(self.stuck(pc, not_a_list, &v)),
+                },
+                Instr::Append => {
+                    let v2 = self.pop();
+                    let mut v1 = self.pop();
+                    let mut heads = vec![];
+                    loop {
+                        match v1 {
+                            Value::Nil(_) => break,
+                            Value::Cons(h, tl) => {
+                                heads.push(h);
+                                v1 = (*tl).clone();
+                            }
+                            v => return Err(self.stuck(pc, not_a_list, &v)),
+                        }
+                    }
+                    let v = heads.into_iter().rev().fold(v2, |tl, h| Value::Cons(h, Rc::new(tl)));
+                    self.push(v);
+                }
+
+                Instr::Inl(ty) => {
+                    let v = self.pop();
+                    self.push(Value::Inl(Rc::new(v), self.annotation(ty)));
+                }
+                Instr::Inr(ty) => {
+                    let v = self.pop();
+                    self.push(Value::Inr(Rc::new(v), self.annotation(ty)));
+                }
+                Instr::SumCase(target) => match self.pop() {
+                    Value::Inl(v, _) => self.push((*v).clone()),
+                    Value::Inr(v, _) => {
+                        self.push((*v).clone());
+                        self.pc = *target;
+                    }
+                    v => return Err(self.stuck(pc, not_a_sum, &v)),
+                },
+
+                Instr::Tag(l, ty) => {
+                    let v = self.pop();
+                    self.push(Value::Tag(l.clone(), Rc::new(v), self.annotation(ty)));
+                }
+                Instr::Fold(ty) => {
+                    let v = self.pop();
+                    self.push(Value::Fold(Rc::new(v), self.annotation(ty)));
+                }
+                Instr::Unfold => match self.pop() {
+                    Value::Fold(v, _) => self.push((*v).clone()),
+                    v => return Err(self.stuck(pc, not_a_fold, &v)),
+                },
+                Instr::VariantCase(targets) => {
+                    let v = self.pop();
+                    let target = match &v {
+                        Value::Tag(l, _, _) => targets.iter().find(|(l2, _)| l2 == l),
+                        _ => None,
+                    };
+                    match (&v, target) {
+                        (Value::Tag(_, content, _), Some((_, target))) => {
+                            self.push((**content).clone());
+                            self.pc = *target;
+                        }
+                        _ => return Err(self.stuck(pc, not_a_variant, &v)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn not_a_function(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAFunction { redex, value }
+}
+fn not_a_boolean(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotABoolean { redex, value }
+}
+fn not_an_integer(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAnInteger { redex, value }
+}
+fn not_a_string(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAString { redex, value }
+}
+fn not_a_char(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAChar { redex, value }
+}
+fn index_out_of_bounds(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::IndexOutOfBounds { redex, value }
+}
+fn not_a_pair(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAPair { redex, value }
+}
+fn not_a_tuple(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotATuple { redex, value }
+}
+fn not_a_record(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotARecord { redex, value }
+}
+fn not_a_list(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAList { redex, value }
+}
+fn not_a_sum(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotASum { redex, value }
+}
+fn not_a_variant(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAVariant { redex, value }
+}
+fn not_comparable(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotComparable { redex, value }
+}
+fn not_a_fold(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotAFold { redex, value }
+}
+fn not_a_type_abstraction(redex: Box<Term>, value: Box<Term>) -> EvalError {
+    EvalError::NotATypeAbstraction { redex, value }
+}
+
+impl<'t> Program<'t> {
+    /// Runs the program to a [`Value`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Term::try_multistep`] on the compiled term, with the stuck
+    /// redex as it was written in the term.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::vm;
+    /// let fact = fix(abs("f", arrow(Integer, Integer), abs("n", Integer,
+    ///     ite(eq("n", 0), 1, mul("n", app("f", sub("n", 1))))
+    /// )));
+    /// let t = app(fact, 5);
+    /// let program = vm::compile(&t);
+    /// assert_eq!(program.run().unwrap().to_term(), Int(120.into()));
+    /// ```
+    pub fn run(&self) -> Result<Value<'t>, EvalError> {
+        self.run_located().map_err(|e| e.error)
+    }
+
+    /// Like [`Program::run`], but also returns the term of the instruction which failed on error.
+    ///
+    /// ```rust
+    /// # use stlc_project::term::util::*;
+    /// # use stlc_project::vm;
+    /// let t = add(1, div(2, 0));
+    /// let Add(_, div) = &t else { unreachable!() };
+    /// assert!(std::ptr::eq(vm::compile(&t).run_located().unwrap_err().node, &**div));
+    /// ```
+    pub fn run_located(&self) -> Result<Value<'t>, Located<'t, EvalError>> {
+        Arithmetic::current()
+            .check_literals(self.term)
+            .map_err(|error| Located {
+                error,
+                node: self.term,
+            })?;
+        let mut machine = Machine {
+            program: self,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            closure: None,
+            types: TypeEnv::default(),
+            base: 0,
+            pc: 0,
+        };
+        // Instructions fail before jumping, so the failed one is the last one fetched
+        machine.run().map_err(|error| Located {
+            error,
+            node: self.redexes[&(machine.pc - 1)],
+        })
+    }
+}
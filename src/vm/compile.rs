@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::{Function, Instr, Program, Slot};
+use crate::term::Term::{self, *};
+
+/// The variables in scope and the number of slots used by the current frame.
+#[derive(Debug, Default)]
+struct Scope {
+    vars: Vec<(String, Slot)>,
+    depth: usize,
+}
+
+impl Scope {
+    fn lookup(&self, x: &str) -> Option<Slot> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(var, _)| var == x)
+            .map(|(_, slot)| *slot)
+    }
+
+    /// Binds `x` to the topmost slot.
+    fn bind(&mut self, x: &str) {
+        self.vars.push((x.to_string(), Slot::Local(self.depth - 1)));
+    }
+
+    fn unbind(&mut self, n: usize) {
+        self.vars.truncate(self.vars.len() - n);
+    }
+}
+
+/// Returns the labels of the fields of a record or record update.
+fn labels(fields: &[(String, Term)]) -> Rc<[Rc<str>]> {
+    fields.iter().map(|(l, _)| l.as_str().into()).collect()
+}
+
+#[derive(Debug, Default)]
+struct Compiler<'t> {
+    code: Vec<Instr<'t>>,
+    redexes: HashMap<usize, &'t Term>,
+}
+
+impl<'t> Compiler<'t> {
+    fn emit(&mut self, instr: Instr<'t>) -> usize {
+        self.code.push(instr);
+        self.code.len() - 1
+    }
+
+    /// Emits an instruction which may get stuck on the redex `t`.
+    fn emit_redex(&mut self, instr: Instr<'t>, t: &'t Term) -> usize {
+        let pc = self.emit(instr);
+        self.redexes.insert(pc, t);
+        pc
+    }
+
+    /// Sets the target of the jump at `pc` to the next instruction.
+    fn patch(&mut self, pc: usize) {
+        let next = self.code.len();
+        match &mut self.code[pc] {
+            Instr::Jump(target)
+            | Instr::JumpIfFalse(target)
+            | Instr::ListCase(target)
+            | Instr::SumCase(target) => *target = next,
+            instr => unreachable!("{instr:?} is not a jump"),
+        }
+    }
+
+    /// Emits the code of a binary operator, which pops both operands and pushes the result.
+    fn op2(
+        &mut self,
+        instr: Instr<'t>,
+        t: &'t Term,
+        t1: &'t Term,
+        t2: &'t Term,
+        scope: &mut Scope,
+    ) {
+        self.term(t1, scope, false);
+        self.term(t2, scope, false);
+        self.emit_redex(instr, t);
+        scope.depth -= 1;
+    }
+
+    /// Emits the code of a unary operator, which pops its operand and pushes the result.
+    fn op1(&mut self, instr: Instr<'t>, t: &'t Term, t1: &'t Term, scope: &mut Scope) {
+        self.term(t1, scope, false);
+        self.emit_redex(instr, t);
+    }
+
+    /// Emits the code of a conditional `t` on the boolean `cond`, which gets stuck on `t`.
+    fn branch(
+        &mut self,
+        t: &'t Term,
+        cond: &'t Term,
+        if_true: &'t Term,
+        if_false: &'t Term,
+        scope: &mut Scope,
+        tail: bool,
+    ) {
+        self.term(cond, scope, false);
+        let jump_false = self.emit_redex(Instr::JumpIfFalse(0), t);
+        scope.depth -= 1;
+        self.term(if_true, scope, tail);
+        scope.depth -= 1;
+        let jump_end = (!tail).then(|| self.emit(Instr::Jump(0)));
+        self.patch(jump_false);
+        self.term(if_false, scope, tail);
+        if let Some(jump_end) = jump_end {
+            self.patch(jump_end);
+        }
+    }
+
+    /// Compiles `body` out of line as a function of the source term `t`, capturing the
+    /// `free_vars` in scope.
+    ///
+    /// The body starts with `vars` in the first slots: the argument of an abstraction, nothing for
+    /// a type abstraction, and the whole group for a recursive binding.
+    fn function(
+        &mut self,
+        t: &'t Term,
+        free_vars: HashSet<String>,
+        vars: &[&str],
+        body: &'t Term,
+        scope: &Scope,
+    ) -> Rc<Function<'t>> {
+        let mut free_vars: Vec<String> = free_vars.into_iter().collect();
+        free_vars.sort();
+
+        let mut inner = Scope::default();
+        let mut captures = Vec::new();
+        for x in free_vars {
+            if let Some(slot) = scope.lookup(&x) {
+                inner.vars.push((x.clone(), Slot::Capture(captures.len())));
+                captures.push((x, slot));
+            }
+        }
+        for var in vars {
+            inner.depth += 1;
+            inner.bind(var);
+        }
+
+        let skip = self.emit(Instr::Jump(0));
+        let entry = self.code.len();
+        self.term(body, &mut inner, true);
+        self.patch(skip);
+
+        Rc::new(Function {
+            entry,
+            source: t,
+            captures,
+        })
+    }
+
+    /// Emits the code pushing the value of `t`, and returning it if `t` is in tail position.
+    fn term(&mut self, t: &'t Term, scope: &mut Scope, tail: bool) {
+        match t {
+            App(t1, t2) => {
+                self.term(t1, scope, false);
+                self.term(t2, scope, false);
+                scope.depth -= 1;
+                let instr = if tail { Instr::TailCall } else { Instr::Call };
+                self.emit_redex(instr, t);
+                return;
+            }
+            Let { var, val_t, body } => {
+                self.term(val_t, scope, false);
+                scope.bind(var);
+                self.term(body, scope, tail);
+                scope.unbind(1);
+                scope.depth -= 1;
+                if !tail {
+                    self.emit(Instr::Slide(1));
+                }
+                return;
+            }
+            LetRec { bindings, body } => {
+                let vars: Vec<&str> = bindings.iter().map(|(var, ..)| var.as_str()).collect();
+                let mut free_vars = HashSet::new();
+                for (_, _, t1) in bindings {
+                    free_vars.extend(t1.free_vars());
+                }
+                free_vars.retain(|x| !vars.contains(&x.as_str()));
+                let functions = bindings
+                    .iter()
+                    .map(|(_, _, t1)| self.function(t, free_vars.clone(), &vars, t1, scope))
+                    .collect();
+                self.emit(Instr::LetRec(functions));
+                for var in &vars {
+                    scope.depth += 1;
+                    scope.bind(var);
+                }
+                self.term(body, scope, tail);
+                scope.unbind(vars.len());
+                scope.depth -= vars.len();
+                if !tail {
+                    self.emit(Instr::Slide(vars.len()));
+                }
+                return;
+            }
+            Ite {
+                cond,
+                if_true,
+                if_false,
+            } => {
+                self.branch(t, cond, if_true, if_false, scope, tail);
+                return;
+            }
+            And(t1, t2) => {
+                self.branch(t, t1, t2, &False, scope, tail);
+                return;
+            }
+            Or(t1, t2) => {
+                self.branch(t, t1, &True, t2, scope, tail);
+                return;
+            }
+            LCase {
+                t: t1,
+                nil_t,
+                head_var,
+                tail_var,
+                cons_t,
+            } => {
+                self.term(t1, scope, false);
+                let list_case = self.emit_redex(Instr::ListCase(0), t);
+                scope.depth -= 1;
+                self.term(nil_t, scope, tail);
+                scope.depth -= 1;
+                let jump_end = (!tail).then(|| self.emit(Instr::Jump(0)));
+                self.patch(list_case);
+                scope.depth += 1;
+                scope.bind(head_var);
+                scope.depth += 1;
+                scope.bind(tail_var);
+                self.term(cons_t, scope, tail);
+                scope.unbind(2);
+                scope.depth -= 2;
+                if let Some(jump_end) = jump_end {
+                    self.emit(Instr::Slide(2));
+                    self.patch(jump_end);
+                }
+                return;
+            }
+            Case {
+                t: t1,
+                inl_var,
+                inl_t,
+                inr_var,
+                inr_t,
+            } => {
+                self.term(t1, scope, false);
+                let sum_case = self.emit_redex(Instr::SumCase(0), t);
+                scope.bind(inl_var);
+                self.term(inl_t, scope, tail);
+                scope.unbind(1);
+                scope.depth -= 1;
+                let jump_end = (!tail).then(|| {
+                    self.emit(Instr::Slide(1));
+                    self.emit(Instr::Jump(0))
+                });
+                self.patch(sum_case);
+                scope.bind(inr_var);
+                self.term(inr_t, scope, tail);
+                scope.unbind(1);
+                scope.depth -= 1;
+                if let Some(jump_end) = jump_end {
+                    self.emit(Instr::Slide(1));
+                    self.patch(jump_end);
+                }
+                return;
+            }
+            Match { t: t1, arms } => {
+                self.term(t1, scope, false);
+                let variant_case = self.emit_redex(Instr::VariantCase(Rc::new([])), t);
+                let mut targets = Vec::new();
+                let mut jumps_end = Vec::new();
+                for (l, var, arm) in arms {
+                    targets.push((l.as_str().into(), self.code.len()));
+                    scope.bind(var);
+                    self.term(arm, scope, tail);
+                    scope.unbind(1);
+                    scope.depth -= 1;
+                    if !tail {
+                        self.emit(Instr::Slide(1));
+                        jumps_end.push(self.emit(Instr::Jump(0)));
+                    }
+                }
+                self.code[variant_case] = Instr::VariantCase(targets.into());
+                for jump_end in jumps_end {
+                    self.patch(jump_end);
+                }
+                return;
+            }
+
+            Data { body, .. } => {
+                self.term(body, scope, tail);
+                return;
+            }
+
+            Var(x) => {
+                scope.depth += 1;
+                match scope.lookup(x) {
+                    Some(slot) => self.emit(Instr::Load(slot)),
+                    None => self.emit_redex(Instr::Free(x.clone()), t),
+                };
+            }
+            Abs { var, body, .. } => {
+                scope.depth += 1;
+                let function = self.function(t, t.free_vars(), &[var], body, scope);
+                self.emit(Instr::Closure(function));
+            }
+            TAbs { body, .. } => {
+                scope.depth += 1;
+                let function = self.function(t, t.free_vars(), &[], body, scope);
+                self.emit(Instr::Closure(function));
+            }
+
+            Unit | True | False | Int(_) | Str(_) | Char(_) | Nil(_) => {
+                scope.depth += 1;
+                self.emit(match t {
+                    Unit => Instr::Unit,
+                    Str(s) => Instr::Str(s.as_str().into()),
+                    Char(c) => Instr::Char(*c),
+                    True => Instr::Bool(true),
+                    False => Instr::Bool(false),
+                    Int(i) => Instr::Int(i.clone()),
+                    Nil(ty) => Instr::Nil(Rc::new(ty.clone())),
+                    _ => unreachable!(),
+                });
+            }
+
+            Add(t1, t2) => self.op2(Instr::Add, t, t1, t2, scope),
+            Sub(t1, t2) => self.op2(Instr::Sub, t, t1, t2, scope),
+            Mul(t1, t2) => self.op2(Instr::Mul, t, t1, t2, scope),
+            Div(t1, t2) => self.op2(Instr::Div, t, t1, t2, scope),
+            Mod(t1, t2) => self.op2(Instr::Mod, t, t1, t2, scope),
+            Neg(t1) => self.op1(Instr::Neg, t, t1, scope),
+            Not(t1) => self.op1(Instr::Not, t, t1, scope),
+            Eq(t1, t2) => self.op2(Instr::Eq, t, t1, t2, scope),
+            Ne(t1, t2) => self.op2(Instr::Ne, t, t1, t2, scope),
+            Lt(t1, t2) => self.op2(Instr::Lt, t, t1, t2, scope),
+            Le(t1, t2) => self.op2(Instr::Le, t, t1, t2, scope),
+            Gt(t1, t2) => self.op2(Instr::Gt, t, t1, t2, scope),
+            Ge(t1, t2) => self.op2(Instr::Ge, t, t1, t2, scope),
+            Concat(t1, t2) => self.op2(Instr::Concat, t, t1, t2, scope),
+            CharAt(t1, t2) => self.op2(Instr::CharAt, t, t1, t2, scope),
+            Pair(t1, t2) => self.op2(Instr::Pair, t, t1, t2, scope),
+            Cons(t1, t2) => self.op2(Instr::Cons, t, t1, t2, scope),
+            Append(t1, t2) => self.op2(Instr::Append, t, t1, t2, scope),
+
+            Fst(t1) => self.op1(Instr::Fst, t, t1, scope),
+            Snd(t1) => self.op1(Instr::Snd, t, t1, scope),
+            Proj(t1, i) => self.op1(Instr::Proj(*i), t, t1, scope),
+            Tuple(ts) => {
+                for t1 in ts {
+                    self.term(t1, scope, false);
+                }
+                self.emit(Instr::Tuple(ts.len()));
+                scope.depth = scope.depth + 1 - ts.len();
+            }
+            Record(fields) => {
+                for (_, t1) in fields {
+                    self.term(t1, scope, false);
+                }
+                self.emit(Instr::Record(labels(fields)));
+                scope.depth = scope.depth + 1 - fields.len();
+            }
+            Field(t1, l) => self.op1(Instr::Field(l.as_str().into()), t, t1, scope),
+            Update(t1, fields) => {
+                self.term(t1, scope, false);
+                for (_, t2) in fields {
+                    self.term(t2, scope, false);
+                }
+                self.emit_redex(Instr::Update(labels(fields)), t);
+                scope.depth -= fields.len();
+            }
+            Inl(t1, ty) => self.op1(Instr::Inl(Rc::new(ty.clone())), t, t1, scope),
+            Inr(t1, ty) => self.op1(Instr::Inr(Rc::new(ty.clone())), t, t1, scope),
+            Tag(l, t1, ty) => self.op1(Instr::Tag(l.as_str().into(), Rc::new(ty.clone())), t, t1, scope),
+            Fold(t1, ty) => self.op1(Instr::Fold(Rc::new(ty.clone())), t, t1, scope),
+            Unfold(t1) => self.op1(Instr::Unfold, t, t1, scope),
+            Fix(t1) => self.op1(Instr::Fix, t, t1, scope),
+            StrLen(t1) => self.op1(Instr::StrLen, t, t1, scope),
+            IntToString(t1) => self.op1(Instr::IntToString, t, t1, scope),
+            TApp(t1, ty) => self.op1(Instr::TApp(Rc::new(ty.clone())), t, t1, scope),
+        }
+        if tail {
+            self.emit(Instr::Return);
+        }
+    }
+}
+
+/// Compiles the term `t` to bytecode.
+///
+/// Free variables of `t` are only reported when they are evaluated, as with [`Term::multistep`].
+///
+/// # Examples
+///
+/// ```rust
+/// # use stlc_project::term::util::*;
+/// # use stlc_project::vm::{self, Instr};
+/// let t = add(1, 2);
+/// let program = vm::compile(&t);
+/// assert_eq!(program.code, [Instr::Int(1.into()), Instr::Int(2.into()), Instr::Add, Instr::Return]);
+/// assert_eq!(program.redexes[&2], &t);
+/// ```
+pub fn compile(t: &Term) -> Program<'_> {
+    let mut compiler = Compiler::default();
+    compiler.term(t, &mut Scope::default(), true);
+    Program {
+        term: t,
+        code: compiler.code,
+        redexes: compiler.redexes,
+    }
+}
@@ -0,0 +1,471 @@
+//! Checks that the evaluators agree with [`Term::multistep`] on all language constructs.
+
+use std::path::Path;
+
+use nom::{combinator::all_consuming, Parser};
+use stlc_project::{
+    arith::{with_arithmetic, Arithmetic},
+    module::parse::parse_module,
+    term::{parse::parse_term, EvalError::{self, Overflow}, Term},
+    vm,
+};
+
+fn parse(code: &str) -> Term {
+    all_consuming(parse_term)
+        .parse(code)
+        .unwrap_or_else(|e| panic!("failed to parse {code}: {e}"))
+        .1
+}
+
+/// Parses `code` with the declarations of `examples/{name}.stlc` in scope.
+fn parse_with_module(name: &str, code: &str) -> Term {
+    let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/examples");
+    let source = std::fs::read_to_string(examples.join(format!("{name}.stlc"))).unwrap();
+    let module = all_consuming(parse_module).parse(&source).unwrap().1;
+    module.to_term(examples, parse(code)).unwrap()
+}
+
+/// Asserts that all evaluators agree with [`Term::try_multistep`] on `t`.
+///
+/// Stuck terms only need to fail in every evaluator, as the redexes they report may differ.
+fn assert_conforms(t: Term) {
+    let expected = t.clone().try_multistep();
+    let actual = [
+        ("eval", t.eval().map(|v| v.to_term())),
+        ("vm", vm::compile(&t).run().map(|v| v.to_term())),
+    ];
+    for (backend, actual) in actual {
+        match (&expected, &actual) {
+            (Ok(expected), Ok(actual)) => assert_eq!(actual, expected, "{backend} of {t}"),
+            (Err(_), Err(_)) => {}
+            _ => panic!("{backend} of {t}: expected {expected:?}, got {actual:?}"),
+        }
+    }
+}
+
+const PROGRAMS: &[&str] = &[
+    // Abstractions and application
+    "fun x : Integer, x",
+    "(fun x : Integer, x + 1) 2",
+    "(fun f : Integer -> Integer, f (f 1)) (fun x : Integer, x * 2)",
+    "(fun x : Integer, fun y : Integer, x - y) 5",
+    "(fun x : Integer, fun x : Integer, x) 1 2",
+    "(fun x : Integer, (fun y : Integer, fun x : Integer, x + y) x) 1",
+    "(fun f, fun x, f (f x)) (fun x, x * 3) 2",
+    // Let
+    "let x = 5 in x * x",
+    "let x = 1 in let y = x + 1 in let x = 10 in x + y",
+    "let f = fun x : Integer, x + 1 in f (f 1)",
+    "let x = 1 in fun y : Integer, x + y",
+    // Unit and sequencing
+    "unit",
+    "unit; 1",
+    "(fun u : Unit, u) unit; unit; True",
+    "let f = fun x : Integer, unit in f 1; f 2; 3",
+    "(unit, unit) == (unit, unit)",
+    // Booleans
+    "True",
+    "False",
+    "if True then 1 else 2",
+    "if 1 < 2 then False else True",
+    "if (fun b : Boolean, b) False then 1 else 2",
+    "True && False || True",
+    "False || True && False",
+    "not 1 == 2 && 3 < 4",
+    "not not False",
+    "False && (fix (fun x : Boolean, x))",
+    "True || (fix (fun x : Boolean, x))",
+    "True && 5",
+    "(fun x, 0 < x && x < 10 || x == 42) 42",
+    // Integers and comparisons
+    "1 + 2 * 3 - 4",
+    "(1 + 2) * (3 - 4)",
+    "-5",
+    "7 / 2",
+    "-7 / 2",
+    "-7 % 2",
+    "7 % -2",
+    "-(1 + 2) * -3",
+    "- -3",
+    "3 -1",
+    "(fun x : Integer, -x) 4",
+    "2147483647 + 0",
+    "-2147483648 % -1",
+    "1 == 1",
+    "1 != 1",
+    "1 < 2",
+    "2 <= 1",
+    "3 > 2",
+    "3 >= 4",
+    // Strings and characters
+    "\"hello\"",
+    "'a'",
+    "\"tab\\t, quote \\\" and newline\\n\"",
+    "'\\''",
+    "\"foo\" ^ \"bar\"",
+    "strlen (\"foo\" ^ \"bar\")",
+    "charat \"hello\" 4",
+    "(itos (6 * 7)) ^ \"!\"",
+    "\"abc\" < \"abd\"",
+    "'z' <= 'a'",
+    "\"b\" > \"abc\"",
+    "(\"x\", 'y') == (\"x\", 'y')",
+    "let greet = fun name : String, \"hello, \" ^ name in greet \"world\"",
+    // Structural equality
+    "True == True",
+    "(1, True) == (1, True)",
+    "(1, True) != (1, False)",
+    "(cons 1 (nil Integer)) == (cons 1 (nil Integer))",
+    "(cons 1 (nil Integer)) == (nil Integer)",
+    "(inl 1 Boolean) == (inr True Integer)",
+    "(inr True Integer) == (inr True Integer)",
+    // Pairs
+    "(1 + 1, True)",
+    "fst (1, 2)",
+    "snd (1, (2, 3))",
+    "fst (snd (1, (2, 3)))",
+    "(fun p : (Integer, Integer), (fst p) + (snd p)) (3, 4)",
+    // Tuples
+    "(1 + 1, True, \"a\")",
+    "(1, 2, 3).2",
+    "(1, 2).1",
+    "let t = (1 + 1, 2, (3, 4, 5)) in t.2.1 + t.0",
+    "(fun t : (Integer, Boolean, Integer), if t.1 then t.0 else t.2) (1, False, 3)",
+    "(fun p, p.0 * 2) (4, True)",
+    "(1, (2, 3), 4) == (1, (2, 3), 4)",
+    "(1, 2, 3) != (1, 2, 4)",
+    // Records
+    "{}",
+    "{x = 1 + 1, y = True}",
+    "{x = 1, y = True}.y",
+    "let r = {x = 1 + 1, y = True} in {r with x = r.x * 10}",
+    "(fun r : {y : Boolean, x : Integer}, {r with y = not r.y, x = 0}) {x = 3, y = True}",
+    "{p = (1, 2, 3), q = {z = \"a\"}}.q.z",
+    "{x = 1, y = 2} == {y = 2, x = 1}",
+    "{x = (fun x : Integer, x + 1)}.x 2",
+    // Lists
+    "nil Integer",
+    "cons (1 + 1) (cons 2 (nil Integer))",
+    "lcase nil Integer of | nil => 0 | cons x xs => x",
+    "lcase cons 1 (cons 2 (nil Integer)) of | nil => nil Integer | cons x xs => xs",
+    "cons (fun x : Integer, x) (nil (Integer -> Integer))",
+    "lcase cons 1 (nil) of | nil => nil | cons x xs => cons (x + 1) xs",
+    // Sums
+    "inl (1 + 1) Boolean",
+    "inr (1 == 2) Integer",
+    "case inl 1 Boolean of | inl x => x + 1 | inr b => 0",
+    "case inr True Integer of | inl x => False | inr b => b",
+    "(fun s : Integer + Integer, case s of | inl x => x | inr y => y * 10) (inr 4 Integer)",
+    // Variants
+    "tag some (1 + 1) <some : Integer, none : Unit>",
+    "match tag none unit <some : Integer, none : Unit> of | some x => x | none u => 0",
+    "(fun o : <some : Integer, none : Unit>, match o of | none u => 0 | some x => x * 2) (tag some 4 <some : Integer, none : Unit>)",
+    "let c = tag b True <a : Integer, b : Boolean, c : String> in match c of | a x => itos x | b y => if y then \"yes\" else \"no\" | c z => z",
+    "(fun x : Integer, match tag a x <a : Integer, b : Integer> of | a x => fun y : Integer, x + y | b y => fun x : Integer, x) 1 2",
+    "let v = 10 in match tag p (1, 2) <p : (Integer, Integer), q : Unit> of | p x y => x + y + v | q => v",
+    "(tag a 1 <a : Integer, b : Integer>) == (tag a 1 <a : Integer, b : Integer>)",
+    "(tag a 1 <a : Integer, b : Integer>) == (tag b 1 <a : Integer, b : Integer>)",
+    // Fix
+    "fix (fun x : Integer, 5)",
+    "(fix (fun f : Integer -> Integer, fun n : Integer, if n == 0 then 1 else n * f (n - 1))) 5",
+    "let fib = fix (fun fib : Integer -> Integer, fun n : Integer, if n < 2 then n else fib (n - 1) + fib (n - 2)) in fib 10",
+    "fix (fun f : Integer -> Integer, fun n : Integer, n)",
+    // Type abstraction and application
+    "Fun a, fun x : a, x",
+    "(Fun a, fun x : a, x) [Integer] 1",
+    "let id = Fun a, fun x : a, x in (id [Integer] 1, id [Boolean] True)",
+    "let twice = Fun a, fun f : a -> a, fun x : a, f (f x) in twice [Integer] (fun x : Integer, x * 3) 2",
+    "(Fun a, Fun b, fun p : (a, b), (snd p, fst p)) [Integer] [Boolean] (1, True)",
+    "(Fun a, lcase cons 1 (nil Integer) of | nil => 0 | cons x xs => x) [Boolean]",
+    "(Fun a, fun x : a, x) [Integer]",
+    "(Fun a, nil a) [Integer]",
+    "(Fun a, Fun b, fun x : a, inl x b) [Integer] [Boolean]",
+    "(Fun b, (Fun a, fun x : a, fun y : b, x) [b]) [Integer]",
+    "(Fun a, Fun a, fun x : a, x) [Integer]",
+    "(Fun a, Fun b, fun x : a, x) [Integer]",
+    // Nested patterns
+    "match (1, cons 2 (cons 3 (nil Integer))) with | (x, cons y ys) => x + y | _ => 0",
+    "match (1, nil Integer) with | (x, cons y ys) => x + y | (x, nil) => x",
+    "match inr True Integer with | inl 0 => 0 | inl n => n * 2 | inr True => 1 | inr False => 2",
+    "(fun n : Integer, match n with | 0 => 1 | 1 => 2 | n => n * n) 7",
+    "match -1 with | -1 => True | _ => False",
+    "match (unit, (False, inl (cons 1 (nil Integer)) Unit)) with | (unit, (True, _)) => 0 | (_, (False, inl (cons x nil))) => x | _ => 2",
+    "let x = 5 in match x with | y => x + y",
+    "(fix (fun len : [Integer] -> Integer, fun xs : [Integer], match xs with | nil => 0 | cons _ ys => 1 + len ys)) (cons 1 (cons 2 (nil Integer)))",
+    // Recursive types
+    "fold (inl unit) (mu l. Unit + (Integer, l))",
+    "unfold (fold (inr (1, fold (inl unit) (mu l. Unit + (Integer, l)))) (mu l. Unit + (Integer, l)))",
+    "fold (1 + 1) (mu a. Integer)",
+    "(fun x : (mu a. Integer), (unfold x) + 1) (fold 1 (mu a. Integer))",
+    "(fold 1 (mu a. Integer)) == (fold (2 - 1) (mu a. Integer))",
+    // List literals
+    "[1, 2, 3]",
+    "[] : [Integer]",
+    "1 + 1 :: 2 :: []",
+    "[1] ++ [2, 3]",
+    "0 :: [1] ++ [] ++ [2]",
+    "[[1], []] ++ [[2 * 3]]",
+    "([1] ++ [2]) == [1, 2]",
+    "lcase [1, 2] ++ [3] of | nil => 0 | cons x xs => x",
+    "(fun xs : [Integer], xs ++ xs) ([1])",
+    // Recursive bindings
+    "let rec fact : Integer -> Integer = fun n : Integer, if n == 0 then 1 else n * fact (n - 1)
+     in fact 5",
+    "let rec even : Integer -> Boolean = fun n : Integer, if n == 0 then True else odd (n - 1)
+     and odd : Integer -> Boolean = fun n : Integer, if n == 0 then False else even (n - 1)
+     in (even 7, odd 7)",
+    "let rec a : Integer -> Integer = fun n : Integer, if n < 1 then 0 else b (n - 1)
+     and b : Integer -> Integer = fun n : Integer, c n + 1
+     and c : Integer -> Integer = fun n : Integer, a n * 2
+     in a 5",
+    "let rec f = fun n : Integer, if n == 0 then [] else n :: g (n - 1)
+     and g = fun n : Integer, f n in g 3",
+];
+
+#[test]
+fn programs() {
+    for code in PROGRAMS {
+        assert_conforms(parse(code));
+    }
+}
+
+/// Terms which type-check must evaluate without getting stuck in every evaluator.
+#[test]
+fn well_typed_terms_evaluate() {
+    for (code, well_typed) in [
+        // Explicitly polymorphic terms must be applied to types
+        ("let id = Fun a, fun x : a, x in id 1", false),
+        ("let id = Fun a, fun x : a, x in id [Integer] 1", true),
+        ("let rec id : forall a. a -> a = Fun a, fun x : a, x in id 1", false),
+        ("let rec id : forall a. a -> a = Fun a, fun x : a, x in id [Integer] 1", true),
+        ("let rec id : forall a. a -> a = fun x : a, x in (id 1, id True)", true),
+        // Compared types are generalized, but only instantiated with comparable types
+        ("let eq = fun x, fun y, x == y in (eq 1 1, eq True True)", true),
+        ("let eq = fun x, fun y, x == y in let ne = fun x, fun y, not eq x y in ne 'a' 'b'", true),
+        ("let eq = fun x, fun y, x == y in eq (fun x : Integer, x) (fun x : Integer, x)", false),
+    ] {
+        let t = parse(code);
+        assert_eq!(t.type_check().is_ok(), well_typed, "type of {code}");
+        if well_typed {
+            assert!(t.clone().try_multistep().is_ok(), "value of {code}");
+            assert_conforms(t);
+        }
+    }
+}
+
+#[test]
+fn displayed_lists_parse() {
+    for code in ["[1, 2] ++ [3]", "[(1, True)] ++ []", "[[1], []]", "[\"a\", \"b\"]"] {
+        let value = parse(code).multistep();
+        assert_eq!(parse(&value.to_string()), value, "{code}");
+    }
+}
+
+#[test]
+fn std_module() {
+    for code in [
+        "range 1 10",
+        "sum (range 1 20)",
+        "reverse (range 1 10)",
+        "append (range 1 4) (reverse (range 1 4))",
+        "ab",
+        "append (cons 1 (nil Integer))",
+        "map (fun x, x * 2) (range 1 5)",
+        "length (reverse (map (fun x, x == 3) (range 1 5)))",
+        "map even (range 1 5)",
+        "gcd 48 18",
+        "reverse [1, 2, 3] ++ [4]",
+        "map (fun x, x :: []) [1, 2]",
+    ] {
+        assert_conforms(parse_with_module("std", code));
+    }
+}
+
+#[test]
+fn tree_module() {
+    for code in [
+        "toList (fromList (cons 3 (cons 1 (cons 2 (cons 3 (nil Integer))))))",
+        "size (fromList (range 1 10))",
+        "insert 2 Leaf",
+        "fun t : Tree, Node t 0 t",
+        "match Node Leaf 1 Leaf of | Leaf => 0 | Node l x r => x",
+    ] {
+        assert_conforms(parse_with_module("tree", code));
+    }
+}
+
+#[test]
+fn parity_module() {
+    for code in [
+        "isEven 10",
+        "isOdd 7",
+        "parities (range 0 4)",
+        "(evens [1, 2, 3, 4, 5], odds [True, False])",
+        "evens (odds (range 0 10))",
+    ] {
+        assert_conforms(parse_with_module("parity", code));
+    }
+}
+
+/// Lists re-expressed as the recursive type `μl. Unit + (a, l)`, with conversions from and to
+/// the built-in lists.
+const REC_LIST: &str = "
+    let rnil = Fun a, fold (inl unit) (μl. Unit + (a, l)) in
+    let rcons = Fun a, fun x : a, fun xs : (μl. Unit + (a, l)), fold (inr (x, xs)) (μl. Unit + (a, l)) in
+    let foldr = Fun a, Fun b, fun f : a -> b -> b, fun z : b,
+        fix (fun go : (μl. Unit + (a, l)) -> b, fun xs : (μl. Unit + (a, l)),
+            case unfold xs of | inl u => z | inr p => f (fst p) (go (snd p))) in
+    let fromList = Fun a, fix (fun go : [a] -> (μl. Unit + (a, l)), fun xs : [a],
+        lcase xs of | nil => rnil [a] | cons x xs => rcons [a] x (go xs)) in
+    let toList = Fun a, foldr [a] [[a]] (fun x : a, fun xs : [a], cons x xs) (nil a) in
+    let rmap = Fun a, Fun b, fun f : a -> b,
+        foldr [a] [μl. Unit + (b, l)] (fun x : a, fun ys : (μl. Unit + (b, l)), rcons [b] (f x) ys) (rnil [b]) in
+";
+
+#[test]
+fn recursive_list() {
+    for (code, expected) in [
+        (
+            "toList [Integer] (rcons [Integer] 1 (rcons [Integer] 2 (rnil [Integer])))",
+            "cons 1 (cons 2 (nil Integer))",
+        ),
+        (
+            "foldr [Integer] [Integer] (fun x : Integer, fun y : Integer, x + y) 0 (fromList [Integer] (cons 1 (cons 2 (cons 3 (nil Integer)))))",
+            "6",
+        ),
+        (
+            "foldr [Boolean] [Integer] (fun b : Boolean, fun n : Integer, if b then n + 1 else n) 0 (rmap [Integer] [Boolean] (fun x : Integer, x < 3) (fromList [Integer] (cons 1 (cons 2 (cons 3 (nil Integer))))))",
+            "2",
+        ),
+        (
+            "toList [Integer] (rmap [Integer] [Integer] (fun x : Integer, x * x) (fromList [Integer] (cons 2 (cons 3 (nil Integer)))))",
+            "cons 4 (cons 9 (nil Integer))",
+        ),
+        ("toList [Integer] (rnil [Integer])", "nil Integer"),
+    ] {
+        let t = parse(&format!("{} {code}", REC_LIST.trim()));
+        let expected = parse(expected);
+        assert_eq!(t.type_check(), expected.type_check(), "type of {code}");
+        assert_eq!(t.clone().multistep(), expected, "value of {code}");
+        assert_conforms(t);
+    }
+}
+
+/// Non-tail recursion `1 + f (n - 1)`, which nests as deep as `n` in every evaluator.
+const DEEP_RECURSION: &str =
+    "fix (fun f : Integer -> Integer, fun n : Integer, if n == 0 then 0 else 1 + f (n - 1))";
+
+#[test]
+fn deep_recursion() {
+    assert_conforms(parse(&format!("let f = {DEEP_RECURSION} in f 50")));
+
+    // The small-step evaluator recurses on the depth of the term, so only the others go deeper
+    let t = parse(&format!("let f = {DEEP_RECURSION} in f 100000"));
+    assert_eq!(t.eval().unwrap().to_term(), parse("100000"));
+    assert_eq!(vm::compile(&t).run().unwrap().to_term(), parse("100000"));
+}
+
+#[test]
+fn stuck_terms() {
+    for code in [
+        "x",
+        "1 2",
+        "if 1 then 2 else 3",
+        "1 && True",
+        "0 || True",
+        "not 1",
+        "True + 1",
+        "fst 1",
+        "1.0",
+        "(1, 2, 3).3",
+        "(1, 2).2",
+        "1.x",
+        "{x = 1}.y",
+        "{1 with x = 2}",
+        "{{x = 1} with y = 2}",
+        "lcase 1 of | nil => 0 | cons x xs => x",
+        "case 1 of | inl x => x | inr y => y",
+        "match 1 of | a x => x",
+        "match tag b 1 <a : Integer, b : Integer> of | a x => x",
+        "fix 1",
+        "unfold 1",
+        "unfold (inl 1)",
+        "(fun x : Integer, x) == (fun x : Integer, x)",
+        "1 [Integer]",
+        "(fun x : Integer, x) [Integer]",
+        "(Fun a, 1) 2",
+        "fix (Fun a, 1)",
+        "(Fun a, 1) == (Fun a, 1)",
+        "1; 2",
+        "1 / 0",
+        "5 % (2 - 2)",
+        "-True",
+        "True / 1",
+        "2147483647 + 1",
+        "-2147483648 - 1",
+        "65536 * 65536",
+        "-2147483648 / -1",
+        "-(-2147483648)",
+        "charat \"abc\" 3",
+        "charat \"abc\" (0 - 1)",
+        "strlen 1",
+        "\"a\" ^ 1",
+        "\"a\" < 1",
+        "'a' < \"a\"",
+        "1 ++ [2]",
+        "(1 :: 2) ++ [3]",
+    ] {
+        assert_conforms(parse(code));
+    }
+}
+
+#[test]
+fn arbitrary_arithmetic() {
+    for (code, expected) in [
+        ("2147483647 + 1", "2147483648"),
+        ("-2147483648 - 1", "-2147483649"),
+        ("65536 * 65536", "4294967296"),
+        ("-2147483648 / -1", "2147483648"),
+        ("-(-2147483648)", "2147483648"),
+        ("-2147483648 % -1", "0"),
+        ("(65536 * 65536) / 65536", "65536"),
+        ("100000000000000000000 % 7", "2"),
+        ("-100000000000000000000 < 1", "True"),
+        ("4294967296 == 65536 * 65536", "True"),
+        ("itos (99999999999 + 1)", "\"100000000000\""),
+    ] {
+        with_arithmetic(Arithmetic::Arbitrary, || {
+            let t = parse(code);
+            assert_conforms(t.clone());
+            assert_eq!(t.multistep().to_string(), expected, "{code}");
+        });
+    }
+    with_arithmetic(Arithmetic::Arbitrary, || {
+        assert_conforms(parse("charat \"abc\" 4294967296"));
+        assert_conforms(parse("4294967296 / 0"));
+    });
+}
+
+#[test]
+fn checked_literals() {
+    for code in [
+        "99999999999",
+        "99999999999 - 99999999990",
+        "if True then 1 else 99999999999",
+    ] {
+        let t = parse(code);
+        let overflow = |result: Result<Term, EvalError>| matches!(result, Err(Overflow { .. }));
+        assert!(overflow(t.clone().try_multistep()), "step of {code}");
+        assert!(overflow(t.eval().map(|v| v.to_term())), "eval of {code}");
+        assert!(overflow(vm::compile(&t).run().map(|v| v.to_term())), "vm of {code}");
+    }
+}
+
+#[test]
+fn big_integers_are_sent_across_threads() {
+    let t = parse("99999999999 + 1");
+    let result = std::thread::spawn(|| with_arithmetic(Arithmetic::Arbitrary, || t.try_multistep()))
+        .join()
+        .unwrap();
+    assert_eq!(result.unwrap().to_string(), "100000000000");
+}